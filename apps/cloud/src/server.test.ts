@@ -0,0 +1,83 @@
+import { afterAll, beforeAll, describe, expect, test } from 'bun:test';
+import { mkdtempSync, rmSync } from 'fs';
+import { tmpdir } from 'os';
+import { join } from 'path';
+
+const token = 'test-token';
+const data = (title: string) => ({ tasks: [{ id: 't1', title }], projects: [] });
+
+let dataDir: string;
+let baseUrl: string;
+let server: ReturnType<typeof Bun.spawn>;
+
+const putData = (body: unknown, headers: Record<string, string> = {}) =>
+    fetch(`${baseUrl}/v1/data`, {
+        method: 'PUT',
+        headers: { Authorization: `Bearer ${token}`, 'Content-Type': 'application/json', ...headers },
+        body: JSON.stringify(body),
+    });
+
+const getData = () => fetch(`${baseUrl}/v1/data`, { headers: { Authorization: `Bearer ${token}` } });
+
+beforeAll(async () => {
+    dataDir = mkdtempSync(join(tmpdir(), 'mindwtr-cloud-'));
+    const probe = Bun.serve({ port: 0, fetch: () => new Response() });
+    const port = probe.port;
+    probe.stop(true);
+    baseUrl = `http://127.0.0.1:${port}`;
+    server = Bun.spawn([process.execPath, 'run', join(import.meta.dir, 'server.ts'), '--port', String(port), '--host', '127.0.0.1'], {
+        env: { ...process.env, MINDWTR_CLOUD_DATA_DIR: dataDir },
+        stdout: 'ignore',
+        stderr: 'inherit',
+    });
+    for (let attempt = 0; attempt < 50; attempt += 1) {
+        try {
+            if ((await fetch(`${baseUrl}/health`)).ok) return;
+        } catch {
+            // Not listening yet.
+        }
+        await Bun.sleep(100);
+    }
+    throw new Error('Cloud server did not start');
+});
+
+afterAll(() => {
+    server?.kill();
+    rmSync(dataDir, { recursive: true, force: true });
+});
+
+describe('conditional writes', () => {
+    test('If-None-Match: * creates the data only while there is none', async () => {
+        const created = await putData(data('first'), { 'If-None-Match': '*' });
+        expect(created.status).toBe(200);
+        expect(created.headers.get('ETag')).toBeTruthy();
+
+        const again = await putData(data('second'), { 'If-None-Match': '*' });
+        expect(again.status).toBe(412);
+        expect(await (await getData()).json()).toEqual(data('first'));
+    });
+
+    test('If-Match replaces only the data that was read', async () => {
+        const read = await getData();
+        const etag = read.headers.get('ETag');
+        expect(etag).toBeTruthy();
+
+        const written = await putData(data('edited'), { 'If-Match': etag! });
+        expect(written.status).toBe(200);
+        const newEtag = written.headers.get('ETag');
+        expect(newEtag).toBeTruthy();
+        expect(newEtag).not.toBe(etag);
+        expect((await getData()).headers.get('ETag')).toBe(newEtag);
+
+        const stale = await putData(data('stale'), { 'If-Match': etag! });
+        expect(stale.status).toBe(412);
+        expect(await stale.json()).toEqual({ error: 'Data changed since it was read' });
+        expect(await (await getData()).json()).toEqual(data('edited'));
+    });
+
+    test('writes without preconditions are unconditional', async () => {
+        const written = await putData(data('unconditional'));
+        expect(written.status).toBe(200);
+        expect(await (await getData()).json()).toEqual(data('unconditional'));
+    });
+});
@@ -73,8 +73,9 @@ function jsonResponse(body: unknown, init: ResponseInit = {}) {
     const headers = new Headers(init.headers);
     headers.set('Content-Type', 'application/json; charset=utf-8');
     headers.set('Access-Control-Allow-Origin', corsOrigin);
-    headers.set('Access-Control-Allow-Headers', 'Authorization, Content-Type');
+    headers.set('Access-Control-Allow-Headers', 'Authorization, Content-Type, If-Match, If-None-Match');
     headers.set('Access-Control-Allow-Methods', 'GET,PUT,OPTIONS');
+    headers.set('Access-Control-Expose-Headers', 'ETag');
     return new Response(JSON.stringify(body, null, 2), { ...init, headers });
 }
 
@@ -123,18 +124,51 @@ function validateAppData(value: unknown): { ok: true; data: Record<string, unkno
     return { ok: true, data: value };
 }
 
-function readData(filePath: string): any | null {
+function etagOf(raw: string): string {
+    return `"${createHash('sha256').update(raw).digest('hex')}"`;
+}
+
+function readData(filePath: string): { data: any; etag: string } | null {
     try {
         const raw = readFileSync(filePath, 'utf8');
-        return JSON.parse(raw);
+        return { data: JSON.parse(raw), etag: etagOf(raw) };
+    } catch {
+        return null;
+    }
+}
+
+/** The ETag of the stored data, or null when there is none. */
+function currentEtag(filePath: string): string | null {
+    try {
+        return etagOf(readFileSync(filePath, 'utf8'));
     } catch {
         return null;
     }
 }
 
-function writeData(filePath: string, data: unknown) {
+function matchesEtag(header: string, etag: string | null): boolean {
+    if (etag === null) return false;
+    return header.split(',').some((candidate) => {
+        const trimmed = candidate.trim();
+        return trimmed === '*' || trimmed === etag;
+    });
+}
+
+/** Checks `If-Match` / `If-None-Match`, so a client only replaces the data it last read. */
+function preconditionFailed(req: Request, filePath: string): boolean {
+    const ifMatch = req.headers.get('if-match');
+    const ifNoneMatch = req.headers.get('if-none-match');
+    if (ifMatch === null && ifNoneMatch === null) return false;
+    const etag = currentEtag(filePath);
+    if (ifMatch !== null && !matchesEtag(ifMatch, etag)) return true;
+    return ifNoneMatch !== null && matchesEtag(ifNoneMatch, etag);
+}
+
+function writeData(filePath: string, data: unknown): string {
     mkdirSync(dirname(filePath), { recursive: true });
-    writeFileSync(filePath, JSON.stringify(data, null, 2));
+    const raw = JSON.stringify(data, null, 2);
+    writeFileSync(filePath, raw);
+    return etagOf(raw);
 }
 
 async function main() {
@@ -199,11 +233,11 @@ async function main() {
                 const filePath = join(dataDir, `${key}.json`);
 
                 if (req.method === 'GET') {
-                    const data = readData(filePath);
-                    if (!data) return errorResponse('Not found', 404);
-                    const validated = validateAppData(data);
+                    const stored = readData(filePath);
+                    if (!stored) return errorResponse('Not found', 404);
+                    const validated = validateAppData(stored.data);
                     if (!validated.ok) return errorResponse(validated.error, 500);
-                    return jsonResponse(validated.data);
+                    return jsonResponse(validated.data, { headers: { ETag: stored.etag } });
                 }
 
                 if (req.method === 'PUT') {
@@ -224,8 +258,11 @@ async function main() {
                     }
                     const validated = validateAppData(parsed);
                     if (!validated.ok) return errorResponse(validated.error, 400);
-                    writeData(filePath, validated.data);
-                    return jsonResponse({ ok: true });
+                    if (preconditionFailed(req, filePath)) {
+                        return errorResponse('Data changed since it was read', 412);
+                    }
+                    const etag = writeData(filePath, validated.data);
+                    return jsonResponse({ ok: true }, { headers: { ETag: etag } });
                 }
             }
 
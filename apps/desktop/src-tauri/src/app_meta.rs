@@ -0,0 +1,25 @@
+//! Small per-database settings owned by the backend, stored in the `app_meta` key/value table.
+
+use rusqlite::{Connection, OptionalExtension};
+
+pub fn get(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row("SELECT value FROM app_meta WHERE key = ?1", [key], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+pub fn set(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO app_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        [key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn remove(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM app_meta WHERE key = ?1", [key])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
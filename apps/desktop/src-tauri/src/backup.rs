@@ -0,0 +1,372 @@
+//! Rotating snapshots of the database and the `data.json` mirror.
+//!
+//! Each backup is a directory under `backups/` named by its UTC creation time, holding a copy of
+//! `mindwtr.db` taken with SQLite's online backup API, a copy of `data.json`, and a small
+//! manifest. Automatic backups are taken at most hourly while the data keeps changing; rotation
+//! then keeps the newest automatic backup of each of the last 24 hours, 7 days and 4 ISO weeks.
+//! Manual and pre-restore backups are never rotated out.
+
+use chrono::{DateTime, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encryption::{self, StorageKey};
+use crate::migrations;
+
+pub const BACKUP_DIR_NAME: &str = "backups";
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const DB_FILE_NAME: &str = "mindwtr.db";
+const DATA_FILE_NAME: &str = "data.json";
+const ID_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+/// `reason` of the backups taken on a schedule, the only ones `prune` removes.
+pub const AUTO_REASON: &str = "auto";
+
+/// Minimum time between automatic backups.
+const AUTO_BACKUP_INTERVAL_MINUTES: i64 = 60;
+const KEEP_HOURLY: usize = 24;
+const KEEP_DAILY: usize = 7;
+const KEEP_WEEKLY: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub id: String,
+    pub created_at: String,
+    /// "auto", "manual" or "pre-restore".
+    pub reason: String,
+    pub schema_version: i64,
+    /// Combined size of the files in the backup.
+    #[serde(default)]
+    pub size_bytes: u64,
+}
+
+fn backup_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(id)
+}
+
+/// The database and `data.json` copies of backup `id`.
+pub fn database_path(dir: &Path, id: &str) -> PathBuf {
+    backup_path(dir, id).join(DB_FILE_NAME)
+}
+
+pub fn data_path(dir: &Path, id: &str) -> PathBuf {
+    backup_path(dir, id).join(DATA_FILE_NAME)
+}
+
+/// Copies all of `source` into `target` with the online backup API. When encryption is on both
+/// sides must use the same key, which SQLCipher requires.
+fn copy_database(source: &Connection, target: &mut Connection) -> Result<(), String> {
+    Backup::new(source, target)
+        .and_then(|backup| backup.run_to_completion(256, std::time::Duration::from_millis(0), None))
+        .map_err(|e| e.to_string())
+}
+
+/// Snapshots `conn` and `data_path` into a new backup directory. `key` is the key `conn` is
+/// encrypted with, if any; the copy uses it too and `data_path` is copied as is.
+pub fn create(
+    dir: &Path,
+    conn: &Connection,
+    key: Option<&StorageKey>,
+    data_path: &Path,
+    reason: &str,
+    now: DateTime<Utc>,
+) -> Result<BackupInfo, String> {
+    let mut id = now.format(ID_FORMAT).to_string();
+    let mut suffix = 1;
+    while backup_path(dir, &id).exists() {
+        suffix += 1;
+        id = format!("{}-{}", now.format(ID_FORMAT), suffix);
+    }
+    let path = backup_path(dir, &id);
+    fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        let mut target = encryption::open_with(&path.join(DB_FILE_NAME), key)?;
+        copy_database(conn, &mut target)?;
+        drop(target);
+        if data_path.exists() {
+            fs::copy(data_path, path.join(DATA_FILE_NAME)).map_err(|e| e.to_string())?;
+        }
+        let info = BackupInfo {
+            id: id.clone(),
+            created_at: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            reason: reason.to_string(),
+            schema_version: migrations::current_version(conn)?,
+            size_bytes: 0,
+        };
+        let manifest = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+        fs::write(path.join(MANIFEST_FILE_NAME), manifest).map_err(|e| e.to_string())?;
+        Ok(info)
+    })();
+    match result {
+        Ok(info) => Ok(BackupInfo {
+            size_bytes: dir_size(&path),
+            ..info
+        }),
+        Err(err) => {
+            let _ = fs::remove_dir_all(&path);
+            Err(err)
+        }
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Backups in `dir`, newest first. Directories without a readable manifest are skipped.
+pub fn list(dir: &Path) -> Result<Vec<BackupInfo>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let manifest = match fs::read_to_string(path.join(MANIFEST_FILE_NAME)) {
+            Ok(manifest) => manifest,
+            Err(_) => continue,
+        };
+        if let Ok(info) = serde_json::from_str::<BackupInfo>(&manifest) {
+            backups.push(BackupInfo {
+                size_bytes: dir_size(&path),
+                ..info
+            });
+        }
+    }
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(backups)
+}
+
+fn created_at(info: &BackupInfo) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&info.created_at)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// True when the newest backup is older than the automatic backup interval.
+pub fn is_due(dir: &Path, now: DateTime<Utc>) -> Result<bool, String> {
+    Ok(match list(dir)?.first().and_then(created_at) {
+        Some(latest) => now - latest >= chrono::Duration::minutes(AUTO_BACKUP_INTERVAL_MINUTES),
+        None => true,
+    })
+}
+
+/// Deletes automatic backups that no retention bucket needs. Returns how many were removed.
+pub fn prune(dir: &Path) -> Result<usize, String> {
+    let mut hours = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    let mut removed = 0;
+    for info in list(dir)?.into_iter().filter(|info| info.reason == AUTO_REASON) {
+        let time = match created_at(&info) {
+            Some(time) => time,
+            None => continue,
+        };
+        let mut keep = false;
+        for (buckets, key, limit) in [
+            (&mut hours, time.format("%Y-%m-%dT%H").to_string(), KEEP_HOURLY),
+            (&mut days, time.format("%Y-%m-%d").to_string(), KEEP_DAILY),
+            (&mut weeks, time.format("%G-W%V").to_string(), KEEP_WEEKLY),
+        ] {
+            if buckets.len() < limit && buckets.insert(key) {
+                keep = true;
+            }
+        }
+        if !keep {
+            fs::remove_dir_all(backup_path(dir, &info.id)).map_err(|e| e.to_string())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Copies backup `id` to `scratch_path`, checks its integrity, brings it up to the current
+/// schema and encrypts it with `target_key` (or decrypts it without one), unlocking it with any
+/// of `keys`. The returned connection can then be read to validate the contents before the live
+/// database is replaced from `scratch_path`.
+pub fn open_for_restore(
+    dir: &Path,
+    id: &str,
+    scratch_path: &Path,
+    keys: &[StorageKey],
+    target_key: Option<&StorageKey>,
+) -> Result<Connection, String> {
+    if !list(dir)?.iter().any(|info| info.id == id) {
+        return Err(format!("Backup {} not found", id));
+    }
+    let path = backup_path(dir, id);
+    if path.join(DATA_FILE_NAME).exists() {
+        let bytes = fs::read(path.join(DATA_FILE_NAME)).map_err(|e| e.to_string())?;
+        let raw = encryption::decode(&bytes, keys)?;
+        let value = serde_json::from_str(&raw).map_err(|e| format!("Backup data.json is unreadable: {}", e))?;
+        crate::model::AppData::from_value(value).map_err(|e| format!("Backup data.json is invalid: {}", e))?;
+    }
+    fs::copy(path.join(DB_FILE_NAME), scratch_path).map_err(|e| e.to_string())?;
+    encryption::convert_database(scratch_path, keys, target_key)?;
+    let mut conn = encryption::open_with(scratch_path, target_key)?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if integrity != "ok" {
+        return Err(format!("Backup database failed its integrity check: {}", integrity));
+    }
+    migrations::migrate(&mut conn).map_err(|e| format!("Backup database can't be upgraded: {}", e))?;
+    Ok(conn)
+}
+
+/// Replaces the contents of `conn` with the database at `source_path`, which must be encrypted
+/// with the same `key` as `conn`.
+pub fn restore_into(conn: &mut Connection, source_path: &Path, key: Option<&StorageKey>) -> Result<(), String> {
+    let source = encryption::open_with(source_path, key)?;
+    copy_database(&source, conn)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// An empty directory of its own under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mindwtr-backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn database(title: &str) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO tasks (id, title, status, createdAt, updatedAt)
+             VALUES ('t', ?1, 'inbox', '2024-01-01', '2024-01-01')",
+            [title],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn title(conn: &Connection) -> String {
+        conn.query_row("SELECT title FROM tasks WHERE id = 't'", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap() + chrono::Duration::hours(hours)
+    }
+
+    #[test]
+    fn prune_rotates_only_automatic_backups() {
+        let dir = scratch_dir("prune");
+        let conn = database("Task");
+        let missing = dir.join("no-data.json");
+        let manual = create(&dir, &conn, None, &missing, "manual", at(-24 * 60)).unwrap();
+        let safety = create(&dir, &conn, None, &missing, "pre-restore", at(-24 * 60 + 1)).unwrap();
+        // Thirty hourly backups span 10 Jan 00:00 to 11 Jan 05:00, within one ISO week.
+        for hour in 0..30 {
+            create(&dir, &conn, None, &missing, AUTO_REASON, at(hour)).unwrap();
+        }
+        // A second backup within the newest hour.
+        create(&dir, &conn, None, &missing, AUTO_REASON, at(29) - chrono::Duration::minutes(20)).unwrap();
+
+        // The six oldest hours and the older backup of the newest hour have no bucket left.
+        assert_eq!(prune(&dir).unwrap(), 7);
+
+        let remaining = list(&dir).unwrap();
+        let ids: Vec<&str> = remaining.iter().map(|info| info.id.as_str()).collect();
+        assert!(ids.contains(&manual.id.as_str()) && ids.contains(&safety.id.as_str()));
+        let auto: Vec<&BackupInfo> = remaining.iter().filter(|info| info.reason == AUTO_REASON).collect();
+        assert_eq!(auto.len(), KEEP_HOURLY);
+        assert_eq!(auto.first().unwrap().created_at, "2024-01-11T05:00:00.000Z");
+        assert_eq!(auto.last().unwrap().created_at, "2024-01-10T06:00:00.000Z");
+        assert_eq!(prune(&dir).unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn is_due_follows_the_newest_backup() {
+        let dir = scratch_dir("due");
+        assert!(is_due(&dir, at(0)).unwrap());
+        create(&dir, &database("Task"), None, &dir.join("no-data.json"), AUTO_REASON, at(0)).unwrap();
+        assert!(!is_due(&dir, at(0) + chrono::Duration::minutes(59)).unwrap());
+        assert!(is_due(&dir, at(1)).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_round_trip() {
+        let dir = scratch_dir("restore");
+        let data_path = dir.join("data.json");
+        fs::write(&data_path, r#"{"tasks":[],"projects":[]}"#).unwrap();
+        let mut live = database("Before");
+        let info = create(&dir, &live, None, &data_path, "manual", at(0)).unwrap();
+        assert_eq!(info.schema_version, migrations::latest_version());
+        assert!(info.size_bytes > 0);
+        assert!(super::data_path(&dir, &info.id).exists());
+        live.execute("UPDATE tasks SET title = 'After'", []).unwrap();
+
+        let scratch = dir.join("restore.db");
+        let candidate = open_for_restore(&dir, &info.id, &scratch, &[], None).unwrap();
+        assert_eq!(title(&candidate), "Before");
+        drop(candidate);
+        restore_into(&mut live, &scratch, None).unwrap();
+
+        assert_eq!(title(&live), "Before");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_for_restore_rejects_a_corrupt_database() {
+        let dir = scratch_dir("corrupt-db");
+        let info = create(&dir, &database("Task"), None, &dir.join("no-data.json"), "manual", at(0)).unwrap();
+        let path = database_path(&dir, &info.id);
+        let mut bytes = fs::read(&path).unwrap();
+        // Keep the header so the file still looks like SQLite, and wreck the pages after it.
+        for byte in bytes.iter_mut().skip(100) {
+            *byte = 0xA5;
+        }
+        fs::write(&path, bytes).unwrap();
+
+        assert!(open_for_restore(&dir, &info.id, &dir.join("restore.db"), &[], None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_for_restore_rejects_an_unreadable_data_file() {
+        let dir = scratch_dir("corrupt-json");
+        let data_path = dir.join("data.json");
+        fs::write(&data_path, "{ not json").unwrap();
+        let info = create(&dir, &database("Task"), None, &data_path, "manual", at(0)).unwrap();
+
+        let error = open_for_restore(&dir, &info.id, &dir.join("restore.db"), &[], None).unwrap_err();
+        assert!(error.starts_with("Backup data.json is unreadable"), "{}", error);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_for_restore_rejects_a_newer_schema() {
+        let dir = scratch_dir("newer");
+        let conn = database("Task");
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (?1)", [migrations::latest_version() + 1])
+            .unwrap();
+        let info = create(&dir, &conn, None, &dir.join("no-data.json"), "manual", at(0)).unwrap();
+
+        let error = open_for_restore(&dir, &info.id, &dir.join("restore.db"), &[], None).unwrap_err();
+        assert!(error.starts_with("Backup database can't be upgraded"), "{}", error);
+        assert!(error.contains("newer than this version"), "{}", error);
+        assert!(open_for_restore(&dir, "missing", &dir.join("restore.db"), &[], None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
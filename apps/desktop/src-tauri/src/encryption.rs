@@ -0,0 +1,323 @@
+//! Optional encryption at rest for the database, the JSON mirror and backups.
+//!
+//! Databases are encrypted by SQLCipher with a random 256-bit raw key. JSON files are sealed with
+//! AES-256-GCM under the same key and start with `MAGIC`, so plain and encrypted files can be
+//! told apart without any configuration: a database whose header isn't SQLite's is encrypted, and
+//! so is a JSON file that starts with the magic bytes. The key itself lives in the OS keyring.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::Connection;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const KEY_LEN: usize = 32;
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+const MAGIC: &[u8] = b"MINDWTR-ENC1\n";
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct StorageKey([u8; KEY_LEN]);
+
+impl StorageKey {
+    pub fn generate() -> Result<Self, String> {
+        let mut bytes = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| "Failed to generate an encryption key".to_string())?;
+        Ok(StorageKey(bytes))
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub fn from_hex(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        if raw.len() != KEY_LEN * 2 || !raw.is_ascii() {
+            return Err("Stored encryption key is malformed".to_string());
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&raw[index * 2..index * 2 + 2], 16)
+                .map_err(|_| "Stored encryption key is malformed".to_string())?;
+        }
+        Ok(StorageKey(bytes))
+    }
+
+    /// SQLCipher's raw key syntax, which skips its passphrase derivation.
+    fn sqlcipher_key(&self) -> String {
+        format!("x'{}'", self.to_hex())
+    }
+
+    fn aead_key(&self) -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &self.0).expect("key has the AES-256 length"))
+    }
+}
+
+/// True when `path` holds a database that isn't plain SQLite. Missing and empty files are not
+/// encrypted, since SQLite creates them in plain text.
+pub fn is_encrypted_database(path: &Path) -> bool {
+    let mut header = Vec::with_capacity(SQLITE_HEADER.len());
+    match fs::File::open(path).and_then(|file| file.take(SQLITE_HEADER.len() as u64).read_to_end(&mut header)) {
+        Ok(read) if read > 0 => header != SQLITE_HEADER,
+        _ => false,
+    }
+}
+
+pub fn is_encrypted_file(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn apply_key(conn: &Connection, key: &StorageKey) -> Result<(), String> {
+    conn.pragma_update(None, "key", key.sqlcipher_key())
+        .map_err(|e| e.to_string())?;
+    // SQLCipher only notices a wrong key on the first read.
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Opens the database at `path`, trying each of `keys` if it is encrypted. Returns the index of
+/// the key that unlocked it, or None for a plain database.
+pub fn open(path: &Path, keys: &[StorageKey]) -> Result<(Connection, Option<usize>), String> {
+    if !is_encrypted_database(path) {
+        return Ok((Connection::open(path).map_err(|e| e.to_string())?, None));
+    }
+    for (index, key) in keys.iter().enumerate() {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        if apply_key(&conn, key).is_ok() {
+            return Ok((conn, Some(index)));
+        }
+    }
+    Err(format!(
+        "{} is encrypted and none of the keys in the system keyring unlock it",
+        path.display()
+    ))
+}
+
+/// Opens or creates a database encrypted with `key`, or a plain one without.
+pub fn open_with(path: &Path, key: Option<&StorageKey>) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    if let Some(key) = key {
+        apply_key(&conn, key)?;
+    }
+    Ok(conn)
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn replace_file(tmp_path: &Path, path: &Path) -> Result<(), String> {
+    if cfg!(windows) && path.exists() {
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+    fs::rename(tmp_path, path).map_err(|e| e.to_string())
+}
+
+/// Rewrites the database at `path` encrypted with `next`, or in plain text without it. The
+/// current contents are read with whichever of `keys` unlocks them. Nothing may have the
+/// database open.
+pub fn convert_database(path: &Path, keys: &[StorageKey], next: Option<&StorageKey>) -> Result<(), String> {
+    let tmp_path = sibling(path, ".converting");
+    let _ = fs::remove_file(&tmp_path);
+    {
+        let (conn, _) = open(path, keys)?;
+        let next_key = next.map(StorageKey::sqlcipher_key).unwrap_or_default();
+        conn.execute(
+            "ATTACH DATABASE ?1 AS converted KEY ?2",
+            [tmp_path.to_string_lossy().as_ref(), next_key.as_str()],
+        )
+        .map_err(|e| e.to_string())?;
+        let exported = conn
+            .query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(()))
+            .and_then(|_| conn.execute_batch("DETACH DATABASE converted;"));
+        if let Err(err) = exported {
+            drop(conn);
+            let _ = fs::remove_file(&tmp_path);
+            return Err(err.to_string());
+        }
+    }
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(sibling(path, suffix));
+    }
+    replace_file(&tmp_path, path)
+}
+
+/// Seals `text` under `key`, or returns it unchanged without one.
+pub fn encode(text: &str, key: Option<&StorageKey>) -> Result<Vec<u8>, String> {
+    let key = match key {
+        Some(key) => key,
+        None => return Ok(text.as_bytes().to_vec()),
+    };
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "Failed to generate a nonce".to_string())?;
+    let mut sealed = text.as_bytes().to_vec();
+    key.aead_key()
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), &mut sealed)
+        .map_err(|_| "Failed to encrypt data".to_string())?;
+    let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// Reverses `encode`, trying each of `keys` on encrypted content. Plain content is returned as is.
+pub fn decode(bytes: &[u8], keys: &[StorageKey]) -> Result<String, String> {
+    if !is_encrypted_file(bytes) {
+        return String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string());
+    }
+    let body = &bytes[MAGIC.len()..];
+    if body.len() < NONCE_LEN {
+        return Err("Encrypted file is truncated".to_string());
+    }
+    let (nonce, sealed) = body.split_at(NONCE_LEN);
+    for key in keys {
+        let mut buffer = sealed.to_vec();
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce".to_string())?;
+        if let Ok(plain) = key.aead_key().open_in_place(nonce, Aad::from(MAGIC), &mut buffer) {
+            return String::from_utf8(plain.to_vec()).map_err(|e| e.to_string());
+        }
+    }
+    Err("Encrypted file can't be opened with the keys in the system keyring".to_string())
+}
+
+/// Re-encodes the file at `path` under `next`, replacing it atomically.
+pub fn convert_file(path: &Path, keys: &[StorageKey], next: Option<&StorageKey>) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let text = decode(&bytes, keys)?;
+    let tmp_path = sibling(path, ".converting");
+    fs::write(&tmp_path, encode(&text, next)?).map_err(|e| e.to_string())?;
+    replace_file(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"{"tasks":[{"id":"t","title":"Secret"}]}"#;
+
+    /// A directory holding a database with one task and a JSON file, both in plain text.
+    fn plain_storage(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("mindwtr-encryption-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("mindwtr.db");
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE tasks (id TEXT PRIMARY KEY, title TEXT NOT NULL);
+             INSERT INTO tasks (id, title) VALUES ('t', 'Secret');",
+        )
+        .unwrap();
+        drop(conn);
+        let file = dir.join("data.json");
+        fs::write(&file, DOCUMENT).unwrap();
+        (dir, db, file)
+    }
+
+    /// The task title, read with whichever of `keys` unlocks the database, and that key's index.
+    fn read_title(db: &Path, keys: &[StorageKey]) -> Result<(String, Option<usize>), String> {
+        let (conn, unlocked_with) = open(db, keys)?;
+        let title = conn
+            .query_row("SELECT title FROM tasks WHERE id = 't'", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        Ok((title, unlocked_with))
+    }
+
+    #[test]
+    fn keys_round_trip_through_hex() {
+        let key = StorageKey::generate().unwrap();
+        assert_eq!(key.to_hex().len(), KEY_LEN * 2);
+        assert!(StorageKey::from_hex(&format!(" {}\n", key.to_hex())).unwrap() == key);
+        assert!(StorageKey::from_hex("abcd").is_err());
+        assert!(StorageKey::from_hex(&"zz".repeat(KEY_LEN)).is_err());
+        assert!(StorageKey::generate().unwrap() != key);
+    }
+
+    #[test]
+    fn database_encrypts_rotates_and_decrypts() {
+        let (dir, db, _) = plain_storage("database");
+        let first = StorageKey::generate().unwrap();
+        let second = StorageKey::generate().unwrap();
+        assert!(!is_encrypted_database(&db));
+        assert_eq!(read_title(&db, &[]).unwrap(), ("Secret".to_string(), None));
+
+        convert_database(&db, &[], Some(&first)).unwrap();
+        assert!(is_encrypted_database(&db));
+        assert!(!fs::read(&db).unwrap().windows(6).any(|window| window == b"Secret"));
+        assert!(read_title(&db, &[]).is_err());
+        assert!(read_title(&db, std::slice::from_ref(&second)).is_err());
+        assert_eq!(read_title(&db, &[second.clone(), first.clone()]).unwrap(), ("Secret".to_string(), Some(1)));
+        assert!(open_with(&db, Some(&first)).is_ok());
+        assert!(open_with(&db, Some(&second)).is_err());
+
+        convert_database(&db, std::slice::from_ref(&first), Some(&second)).unwrap();
+        assert!(read_title(&db, std::slice::from_ref(&first)).is_err());
+        assert_eq!(read_title(&db, std::slice::from_ref(&second)).unwrap(), ("Secret".to_string(), Some(0)));
+
+        convert_database(&db, &[second], None).unwrap();
+        assert!(!is_encrypted_database(&db));
+        assert_eq!(read_title(&db, &[]).unwrap(), ("Secret".to_string(), None));
+        assert!(!sibling(&db, ".converting").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_encrypts_rotates_and_decrypts() {
+        let (dir, _, file) = plain_storage("file");
+        let first = StorageKey::generate().unwrap();
+        let second = StorageKey::generate().unwrap();
+
+        convert_file(&file, &[], Some(&first)).unwrap();
+        let sealed = fs::read(&file).unwrap();
+        assert!(is_encrypted_file(&sealed));
+        assert!(!sealed.windows(6).any(|window| window == b"Secret"));
+        assert!(decode(&sealed, std::slice::from_ref(&second)).is_err());
+        assert_eq!(decode(&sealed, &[second.clone(), first.clone()]).unwrap(), DOCUMENT);
+
+        convert_file(&file, std::slice::from_ref(&first), Some(&second)).unwrap();
+        assert!(decode(&fs::read(&file).unwrap(), std::slice::from_ref(&first)).is_err());
+        assert_eq!(decode(&fs::read(&file).unwrap(), std::slice::from_ref(&second)).unwrap(), DOCUMENT);
+
+        convert_file(&file, &[second], None).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), DOCUMENT);
+        assert!(decode(MAGIC, &[first]).unwrap_err().contains("truncated"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_key_change_opens_with_the_pending_key() {
+        let (dir, db, file) = plain_storage("interrupted");
+        let current = StorageKey::generate().unwrap();
+        let pending = StorageKey::generate().unwrap();
+        convert_database(&db, &[], Some(&current)).unwrap();
+        convert_file(&file, &[], Some(&current)).unwrap();
+
+        // A rotation that converted the database, then stopped before the JSON file and before
+        // the pending key replaced the current one in the keyring. A stray scratch copy from an
+        // earlier attempt is left behind too.
+        fs::write(sibling(&db, ".converting"), b"partial").unwrap();
+        let stored = [current.clone(), pending.clone()];
+        convert_database(&db, &stored, Some(&pending)).unwrap();
+
+        assert_eq!(read_title(&db, &stored).unwrap(), ("Secret".to_string(), Some(1)));
+        assert_eq!(decode(&fs::read(&file).unwrap(), &stored).unwrap(), DOCUMENT);
+
+        // The next key change reads every file with the stored keys, whichever one it is under.
+        let next = StorageKey::generate().unwrap();
+        let keys = [current, pending, next.clone()];
+        convert_database(&db, &keys, Some(&next)).unwrap();
+        convert_file(&file, &keys, Some(&next)).unwrap();
+        assert!(read_title(&db, &keys[..2]).is_err());
+        assert_eq!(read_title(&db, std::slice::from_ref(&next)).unwrap(), ("Secret".to_string(), Some(0)));
+        assert_eq!(decode(&fs::read(&file).unwrap(), &[next]).unwrap(), DOCUMENT);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
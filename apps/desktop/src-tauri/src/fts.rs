@@ -0,0 +1,537 @@
+//! Full-text indexes and their per-database configuration.
+//!
+//! Every index stores its own copy of the searchable text, derived from the source row by the
+//! expressions in `INDEXES`. That lets checklist items and attachment titles, which live inside
+//! JSON columns, be indexed as plain text, and lets snippet() and highlight() read it back.
+//!
+//! The default unicode61 tokenizer splits on spaces and punctuation, which leaves Chinese and
+//! Japanese titles as one long token. The trigram tokenizer indexes every three-character
+//! substring instead, so any part of a title can be found. Porter stemming can be layered on
+//! unicode61 for English.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::app_meta;
+
+const FTS_CONFIG_KEY: &str = "fts_config";
+const FTS_STALE_KEY: &str = "fts_stale";
+
+/// Trigram only indexes substrings of this many characters; shorter terms can't use the index.
+pub const TRIGRAM_MIN_CHARS: usize = 3;
+
+/// One FTS table. Column expressions refer to the source row as `{r}`, which becomes `new` in
+/// triggers and the table alias when rebuilding. Rows failing `indexed_when` are left out.
+struct FtsIndex {
+    table: &'static str,
+    source: &'static str,
+    indexed_when: &'static str,
+    columns: &'static [(&'static str, &'static str)],
+}
+
+/// Titles of the objects in a JSON array column, skipping soft-deleted entries.
+macro_rules! json_titles {
+    ($column:literal) => {
+        concat!(
+            "coalesce((SELECT group_concat(json_extract(value, '$.title'), ' ') FROM json_each(",
+            "CASE WHEN json_valid({r}.",
+            $column,
+            ") THEN {r}.",
+            $column,
+            " ELSE '[]' END) WHERE type = 'object' AND json_extract(value, '$.deletedAt') IS NULL), '')"
+        )
+    };
+}
+
+const INDEXES: &[FtsIndex] = &[
+    FtsIndex {
+        table: "tasks_fts",
+        source: "tasks",
+        indexed_when: "{r}.purgedAt IS NULL",
+        columns: &[
+            ("title", "{r}.title"),
+            ("description", "coalesce({r}.description, '')"),
+            ("tags", "coalesce({r}.tags, '')"),
+            ("contexts", "coalesce({r}.contexts, '')"),
+            ("checklist", json_titles!("checklist")),
+            ("attachments", json_titles!("attachments")),
+        ],
+    },
+    FtsIndex {
+        table: "projects_fts",
+        source: "projects",
+        indexed_when: "{r}.purgedAt IS NULL",
+        columns: &[
+            ("title", "{r}.title"),
+            ("supportNotes", "coalesce({r}.supportNotes, '')"),
+            ("tagIds", "coalesce({r}.tagIds, '')"),
+            ("areaTitle", "coalesce({r}.areaTitle, '')"),
+            ("attachments", json_titles!("attachments")),
+        ],
+    },
+    FtsIndex {
+        table: "sections_fts",
+        source: "sections",
+        indexed_when: "{r}.purgedAt IS NULL",
+        columns: &[
+            ("title", "{r}.title"),
+            ("description", "coalesce({r}.description, '')"),
+        ],
+    },
+    FtsIndex {
+        table: "areas_fts",
+        source: "areas",
+        indexed_when: "1",
+        columns: &[("name", "{r}.name")],
+    },
+];
+
+impl FtsIndex {
+    fn column_names(&self) -> String {
+        self.columns.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+    }
+
+    fn column_values(&self, row: &str) -> String {
+        self.columns
+            .iter()
+            .map(|(_, expression)| expression.replace("{r}", row))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn condition(&self, row: &str) -> String {
+        self.indexed_when.replace("{r}", row)
+    }
+
+    fn create_sql(&self, tokenize: &str) -> String {
+        format!(
+            "DROP TABLE IF EXISTS {table};
+             CREATE VIRTUAL TABLE {table} USING fts5(id UNINDEXED, {columns}, tokenize='{tokenize}');
+             DROP TRIGGER IF EXISTS {source}_ai;
+             DROP TRIGGER IF EXISTS {source}_ad;
+             DROP TRIGGER IF EXISTS {source}_au;
+             CREATE TRIGGER {source}_ai AFTER INSERT ON {source} BEGIN
+               INSERT INTO {table} (rowid, id, {columns}) SELECT new.rowid, new.id, {new_values} WHERE {new_condition};
+             END;
+             CREATE TRIGGER {source}_ad AFTER DELETE ON {source} BEGIN
+               DELETE FROM {table} WHERE rowid = old.rowid;
+             END;
+             CREATE TRIGGER {source}_au AFTER UPDATE ON {source} BEGIN
+               DELETE FROM {table} WHERE rowid = old.rowid;
+               INSERT INTO {table} (rowid, id, {columns}) SELECT new.rowid, new.id, {new_values} WHERE {new_condition};
+             END;",
+            table = self.table,
+            source = self.source,
+            columns = self.column_names(),
+            new_values = self.column_values("new"),
+            new_condition = self.condition("new"),
+            tokenize = tokenize
+        )
+    }
+
+    fn rebuild(&self, conn: &Connection) -> Result<(), String> {
+        conn.execute_batch(&format!(
+            "DELETE FROM {table};
+             INSERT INTO {table} (rowid, id, {columns}) SELECT r.rowid, r.id, {values} FROM {source} r WHERE {condition};",
+            table = self.table,
+            source = self.source,
+            columns = self.column_names(),
+            values = self.column_values("r"),
+            condition = self.condition("r")
+        ))
+        .map_err(|e| e.to_string())
+    }
+
+    /// True when FTS5's own consistency check fails on the index structures.
+    fn corrupt(&self, conn: &Connection) -> bool {
+        conn.execute(
+            &format!("INSERT INTO {table} ({table}) VALUES ('integrity-check')", table = self.table),
+            [],
+        )
+        .is_err()
+    }
+
+    /// True when the index and its source table disagree about which rows exist.
+    fn drifted(&self, conn: &Connection) -> Result<bool, String> {
+        conn.query_row(
+            &format!(
+                "SELECT (SELECT COUNT(*) FROM {source} r WHERE {condition}) != (SELECT COUNT(*) FROM {table})
+                    OR EXISTS (SELECT 1 FROM {source} r WHERE {condition} AND r.rowid NOT IN (SELECT rowid FROM {table}))",
+                source = self.source,
+                table = self.table,
+                condition = self.condition("r")
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FtsTokenizer {
+    Unicode61,
+    Trigram,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FtsConfig {
+    pub tokenizer: FtsTokenizer,
+    /// Porter stemming, so "meeting" also finds "meetings". Only valid with unicode61.
+    #[serde(default)]
+    pub stemming: bool,
+}
+
+impl Default for FtsConfig {
+    fn default() -> Self {
+        FtsConfig {
+            tokenizer: FtsTokenizer::Unicode61,
+            stemming: false,
+        }
+    }
+}
+
+impl FtsConfig {
+    /// Picks a starting configuration from the app language setting.
+    pub fn for_language(language: Option<&str>) -> Self {
+        match language {
+            Some("zh") | Some("ja") | Some("ko") => FtsConfig {
+                tokenizer: FtsTokenizer::Trigram,
+                stemming: false,
+            },
+            _ => FtsConfig::default(),
+        }
+    }
+
+    pub fn is_trigram(&self) -> bool {
+        self.tokenizer == FtsTokenizer::Trigram
+    }
+
+    fn tokenize_option(&self) -> Result<&'static str, String> {
+        match (self.tokenizer, self.stemming) {
+            (FtsTokenizer::Unicode61, false) => Ok("unicode61 remove_diacritics 2"),
+            (FtsTokenizer::Unicode61, true) => Ok("porter unicode61 remove_diacritics 2"),
+            (FtsTokenizer::Trigram, false) => Ok("trigram"),
+            (FtsTokenizer::Trigram, true) => Err("Stemming is only available with the unicode61 tokenizer".to_string()),
+        }
+    }
+}
+
+pub fn load_config(conn: &Connection) -> Result<FtsConfig, String> {
+    match app_meta::get(conn, FTS_CONFIG_KEY)? {
+        Some(raw) => serde_json::from_str(&raw).map_err(|e| e.to_string()),
+        None => Ok(FtsConfig::default()),
+    }
+}
+
+pub fn save_config(conn: &Connection, config: &FtsConfig) -> Result<(), String> {
+    config.tokenize_option()?;
+    let raw = serde_json::to_string(config).map_err(|e| e.to_string())?;
+    app_meta::set(conn, FTS_CONFIG_KEY, &raw)
+}
+
+/// Recreates every FTS table and its sync triggers with the configured tokenizer, reindexes
+/// them and records the configuration.
+pub fn apply_config(conn: &Connection, config: &FtsConfig) -> Result<(), String> {
+    let tokenize = config.tokenize_option()?;
+    for index in INDEXES {
+        conn.execute_batch(&index.create_sql(tokenize))
+            .map_err(|e| e.to_string())?;
+    }
+    ensure_populated(conn, true)?;
+    save_config(conn, config)?;
+    app_meta::remove(conn, FTS_STALE_KEY)
+}
+
+/// Records that the FTS tables no longer match `INDEXES`. Migrations use this instead of
+/// rebuilding themselves, because the indexes can refer to columns added by later migrations.
+pub fn mark_stale(conn: &Connection) -> Result<(), String> {
+    app_meta::set(conn, FTS_STALE_KEY, "1")
+}
+
+/// Recreates the FTS tables if a migration marked them stale.
+pub fn rebuild_if_stale(conn: &Connection) -> Result<(), String> {
+    match app_meta::get(conn, FTS_STALE_KEY)? {
+        Some(_) => apply_config(conn, &load_config(conn)?),
+        None => Ok(()),
+    }
+}
+
+/// Reindexes any FTS table that has drifted from its source table, or all of them when
+/// `force_rebuild` is set.
+pub fn ensure_populated(conn: &Connection, force_rebuild: bool) -> Result<(), String> {
+    for index in INDEXES {
+        if force_rebuild || index.drifted(conn)? {
+            index.rebuild(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Names of the FTS tables that are corrupt or out of step with their source tables.
+pub fn inconsistent_indexes(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut tables = Vec::new();
+    for index in INDEXES {
+        if index.corrupt(conn) || index.drifted(conn)? {
+            tables.push(index.table.to_string());
+        }
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO tasks (id, title, status, checklist, createdAt, updatedAt) VALUES
+               ('t1', 'Weekly meetings', 'next', '[{\"title\": \"book the kitchen\"}]', '2024-01-01', '2024-01-01'),
+               ('t2', '会議の準備', 'next', NULL, '2024-01-01', '2024-01-01');",
+        )
+        .unwrap();
+        conn
+    }
+
+    /// Ids of the tasks whose index entry matches the FTS expression.
+    fn task_matches(conn: &Connection, expression: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT id FROM tasks_fts WHERE tasks_fts MATCH ?1 ORDER BY id")
+            .unwrap();
+        let rows = stmt.query_map([expression], |row| row.get(0)).unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn table_sql(conn: &Connection, table: &str) -> String {
+        conn.query_row("SELECT sql FROM sqlite_master WHERE name = ?1", [table], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn tokenizer_follows_the_language() {
+        let trigram = FtsConfig {
+            tokenizer: FtsTokenizer::Trigram,
+            stemming: false,
+        };
+        for language in ["zh", "ja", "ko"] {
+            assert_eq!(FtsConfig::for_language(Some(language)), trigram);
+        }
+        assert_eq!(FtsConfig::for_language(Some("en")), FtsConfig::default());
+        assert_eq!(FtsConfig::for_language(None), FtsConfig::default());
+    }
+
+    #[test]
+    fn unicode61_matches_whole_words_by_prefix() {
+        let conn = connection();
+        assert!(table_sql(&conn, "tasks_fts").contains("tokenize='unicode61 remove_diacritics 2'"));
+        assert_eq!(task_matches(&conn, "meet*"), ["t1"]);
+        assert_eq!(task_matches(&conn, "kitchen"), ["t1"], "checklist titles are indexed");
+        assert!(task_matches(&conn, "meeting").is_empty());
+        assert!(task_matches(&conn, "itch").is_empty());
+    }
+
+    #[test]
+    fn porter_stemming_matches_other_word_forms() {
+        let conn = connection();
+        let porter = FtsConfig {
+            tokenizer: FtsTokenizer::Unicode61,
+            stemming: true,
+        };
+        apply_config(&conn, &porter).unwrap();
+
+        assert!(table_sql(&conn, "tasks_fts").contains("tokenize='porter unicode61 remove_diacritics 2'"));
+        assert_eq!(task_matches(&conn, "meeting"), ["t1"]);
+        assert_eq!(load_config(&conn).unwrap(), porter);
+    }
+
+    #[test]
+    fn trigram_matches_substrings() {
+        let conn = connection();
+        let trigram = FtsConfig::for_language(Some("ja"));
+        apply_config(&conn, &trigram).unwrap();
+
+        assert!(table_sql(&conn, "tasks_fts").contains("tokenize='trigram'"));
+        assert_eq!(task_matches(&conn, "\"itch\""), ["t1"]);
+        assert_eq!(task_matches(&conn, "\"の準備\""), ["t2"]);
+        assert_eq!(load_config(&conn).unwrap(), trigram);
+    }
+
+    #[test]
+    fn trigram_with_stemming_is_rejected() {
+        let conn = connection();
+        let invalid = FtsConfig {
+            tokenizer: FtsTokenizer::Trigram,
+            stemming: true,
+        };
+        assert!(apply_config(&conn, &invalid).is_err());
+        assert!(save_config(&conn, &invalid).is_err());
+        assert_eq!(load_config(&conn).unwrap(), FtsConfig::default());
+        assert!(table_sql(&conn, "tasks_fts").contains("unicode61"));
+    }
+
+    #[test]
+    fn apply_config_rebuilds_every_index_and_its_triggers() {
+        let conn = connection();
+        conn.execute_batch(
+            "INSERT INTO areas (id, name, orderNum, createdAt, updatedAt)
+             VALUES ('a1', 'Garden', 0, '2024-01-01', '2024-01-01');
+             DELETE FROM tasks_fts;",
+        )
+        .unwrap();
+
+        apply_config(&conn, &FtsConfig::for_language(Some("zh"))).unwrap();
+
+        for index in INDEXES {
+            assert!(table_sql(&conn, index.table).contains("tokenize='trigram'"), "{}", index.table);
+        }
+        assert_eq!(task_matches(&conn, "\"meetings\""), ["t1"]);
+        let areas: i64 = conn
+            .query_row("SELECT count(*) FROM areas_fts WHERE areas_fts MATCH '\"arde\"'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(areas, 1);
+        // The recreated triggers keep the new tables in step.
+        conn.execute("UPDATE tasks SET title = 'Quarterly review' WHERE id = 't1'", [])
+            .unwrap();
+        assert_eq!(task_matches(&conn, "\"arterly\""), ["t1"]);
+        assert!(task_matches(&conn, "\"meetings\"").is_empty());
+    }
+
+    #[test]
+    fn ensure_populated_fills_only_drifted_indexes() {
+        let conn = connection();
+        conn.execute_batch(
+            "DELETE FROM tasks_fts WHERE id = 't2';
+             INSERT INTO sections_fts (rowid, id, title, description) VALUES (99, 'stray', 'stray', '');",
+        )
+        .unwrap();
+
+        ensure_populated(&conn, false).unwrap();
+
+        assert_eq!(task_matches(&conn, "会議の準備"), ["t2"]);
+        let sections: i64 = conn
+            .query_row("SELECT count(*) FROM sections_fts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sections, 0);
+    }
+
+    #[test]
+    fn forced_rebuild_rewrites_the_indexed_text() {
+        let conn = connection();
+        conn.execute("UPDATE tasks_fts SET title = 'something else' WHERE id = 't1'", [])
+            .unwrap();
+        assert!(task_matches(&conn, "meetings").is_empty());
+
+        // Every row is present, so only a forced rebuild notices the stale text.
+        ensure_populated(&conn, false).unwrap();
+        assert!(task_matches(&conn, "meetings").is_empty());
+
+        ensure_populated(&conn, true).unwrap();
+        assert_eq!(task_matches(&conn, "meetings"), ["t1"]);
+    }
+
+    #[test]
+    fn stale_indexes_are_rebuilt_once() {
+        let conn = connection();
+        conn.execute("DELETE FROM tasks_fts", []).unwrap();
+        rebuild_if_stale(&conn).unwrap();
+        assert!(task_matches(&conn, "meetings").is_empty(), "nothing was marked stale");
+
+        mark_stale(&conn).unwrap();
+        rebuild_if_stale(&conn).unwrap();
+
+        assert_eq!(task_matches(&conn, "meetings"), ["t1"]);
+        assert_eq!(app_meta::get(&conn, FTS_STALE_KEY).unwrap(), None);
+    }
+
+    #[test]
+    fn stale_rebuild_keeps_the_configured_tokenizer() {
+        let conn = connection();
+        apply_config(&conn, &FtsConfig::for_language(Some("ko"))).unwrap();
+
+        mark_stale(&conn).unwrap();
+        rebuild_if_stale(&conn).unwrap();
+
+        assert!(table_sql(&conn, "tasks_fts").contains("tokenize='trigram'"));
+    }
+
+    #[test]
+    fn inconsistent_indexes_reports_drift() {
+        let conn = connection();
+        assert!(inconsistent_indexes(&conn).unwrap().is_empty());
+
+        // Purged rows are not indexed, so they are not drift either.
+        conn.execute("UPDATE tasks SET purgedAt = '2024-02-01' WHERE id = 't2'", [])
+            .unwrap();
+        assert_eq!(task_matches(&conn, "会議の準備"), Vec::<String>::new());
+        assert!(inconsistent_indexes(&conn).unwrap().is_empty());
+
+        // t1 goes missing while the purged t2 takes its place, so only the row check notices;
+        // areas_fts gains a row for an area that doesn't exist.
+        conn.execute_batch(
+            "DELETE FROM tasks_fts WHERE id = 't1';
+             INSERT INTO tasks_fts (rowid, id, title) VALUES (2, 't2', 'purged');
+             INSERT INTO areas_fts (rowid, id, name) VALUES (5, 'gone', 'gone');",
+        )
+        .unwrap();
+        assert_eq!(inconsistent_indexes(&conn).unwrap(), ["tasks_fts", "areas_fts"]);
+
+        ensure_populated(&conn, false).unwrap();
+        assert!(inconsistent_indexes(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn inconsistent_indexes_reports_corruption() {
+        let conn = connection();
+        // Drop the index segments while the structure record still points at them.
+        conn.execute("DELETE FROM tasks_fts_data WHERE id > 10", []).unwrap();
+
+        assert_eq!(inconsistent_indexes(&conn).unwrap(), ["tasks_fts"]);
+    }
+
+    #[test]
+    fn external_content_tables_are_replaced_when_marked_stale() {
+        // The layout an older migration left behind: tasks_fts reading its text from tasks by
+        // rowid, kept in step by triggers that only cover the original columns.
+        let conn = connection();
+        conn.execute_batch(
+            "DROP TABLE tasks_fts;
+             CREATE VIRTUAL TABLE tasks_fts USING fts5(
+               id UNINDEXED, title, description, tags, contexts, content='tasks'
+             );
+             DROP TRIGGER tasks_ai;
+             DROP TRIGGER tasks_ad;
+             DROP TRIGGER tasks_au;
+             CREATE TRIGGER tasks_ai AFTER INSERT ON tasks BEGIN
+               INSERT INTO tasks_fts (rowid, id, title, description, tags, contexts)
+               VALUES (new.rowid, new.id, new.title, new.description, new.tags, new.contexts);
+             END;
+             INSERT INTO tasks_fts(tasks_fts) VALUES('rebuild');",
+        )
+        .unwrap();
+
+        mark_stale(&conn).unwrap();
+        rebuild_if_stale(&conn).unwrap();
+
+        let sql = table_sql(&conn, "tasks_fts");
+        assert!(!sql.contains("content="), "{}", sql);
+        assert!(sql.contains("checklist"), "{}", sql);
+        assert_eq!(task_matches(&conn, "kitchen"), ["t1"]);
+        assert!(inconsistent_indexes(&conn).unwrap().is_empty());
+        // The index answers from its own copy of the text, and follows later writes.
+        let highlighted: String = conn
+            .query_row(
+                "SELECT highlight(tasks_fts, 1, '[', ']') FROM tasks_fts WHERE tasks_fts MATCH 'weekly'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(highlighted, "[Weekly] meetings");
+        conn.execute("DELETE FROM tasks WHERE id = 't1'", []).unwrap();
+        assert!(task_matches(&conn, "weekly").is_empty());
+        assert!(inconsistent_indexes(&conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+//! Per-entity change history.
+//!
+//! Triggers on `tasks` and `projects` append one `entity_history` row per insert, update or
+//! delete, holding the fields that changed with their old and new values. Each entry records the
+//! device whose database made the change and the journaled operation it belonged to, so the
+//! timeline can tell a local edit from one that arrived through a sync save.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::app_meta;
+use crate::journal;
+
+const DEVICE_ID_KEY: &str = "device_id";
+
+/// (table, entity type recorded in the history)
+const TRACKED_TABLES: &[(&str, &str)] = &[("tasks", "task"), ("projects", "project")];
+
+/// Bumped on every write, so listing it as a change would only add noise.
+const IGNORED_COLUMNS: &[&str] = &["updatedAt"];
+
+/// Columns holding JSON text, recorded as JSON values rather than strings.
+const JSON_COLUMNS: &[&str] = &["tags", "contexts", "checklist", "attachments", "recurrence", "tagIds", "extra"];
+
+const NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub entity_type: String,
+    pub entity_id: String,
+    /// "created", "updated" or "deleted".
+    pub kind: String,
+    /// The entity's `updatedAt` after the change, or the time of removal for deletions.
+    pub changed_at: Option<String>,
+    /// When this database recorded the change.
+    pub recorded_at: String,
+    pub device_id: Option<String>,
+    /// Command that made the change, e.g. `update_task` or `save_data`, when known.
+    pub source: Option<String>,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+/// Identifies this installation's database in history entries. Created on first use.
+pub fn device_id(conn: &Connection) -> Result<String, String> {
+    if let Some(id) = app_meta::get(conn, DEVICE_ID_KEY)? {
+        return Ok(id);
+    }
+    let id: String = conn
+        .query_row("SELECT lower(hex(randomblob(16)))", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    app_meta::set(conn, DEVICE_ID_KEY, &id)?;
+    Ok(id)
+}
+
+fn value_sql(column: &str, row: &str) -> String {
+    if JSON_COLUMNS.contains(&column) {
+        format!(
+            "CASE WHEN json_valid({row}.{column}) THEN json({row}.{column}) ELSE {row}.{column} END",
+            row = row,
+            column = column
+        )
+    } else {
+        format!("{}.{}", row, column)
+    }
+}
+
+/// (Re)creates the history triggers from the current table columns. Run after migrations so
+/// columns added later are tracked too.
+pub fn install_triggers(conn: &Connection) -> Result<(), String> {
+    let device = format!("(SELECT value FROM app_meta WHERE key = '{}')", DEVICE_ID_KEY);
+    let source = "(SELECT label FROM operations WHERE id = (SELECT operationId FROM journal_cursor WHERE id = 1))";
+    for (table, entity_type) in TRACKED_TABLES {
+        let columns: Vec<String> = journal::table_columns(conn, table)?
+            .into_iter()
+            .filter(|column| !IGNORED_COLUMNS.contains(&column.as_str()))
+            .collect();
+        let diffs: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "SELECT json_object('field', '{column}', 'from', {old}, 'to', {new}) AS change
+                     WHERE old.{column} IS NOT new.{column}",
+                    column = column,
+                    old = value_sql(column, "old"),
+                    new = value_sql(column, "new")
+                )
+            })
+            .collect();
+        conn.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {table}_history_ai;
+             DROP TRIGGER IF EXISTS {table}_history_au;
+             DROP TRIGGER IF EXISTS {table}_history_ad;
+             CREATE TRIGGER {table}_history_ai AFTER INSERT ON {table} BEGIN
+               INSERT INTO entity_history (entityType, entityId, kind, changedAt, recordedAt, deviceId, source, changes)
+               VALUES ('{entity_type}', new.id, 'created', new.updatedAt, {now}, {device}, {source}, '[]');
+             END;
+             CREATE TRIGGER {table}_history_au AFTER UPDATE ON {table} BEGIN
+               INSERT INTO entity_history (entityType, entityId, kind, changedAt, recordedAt, deviceId, source, changes)
+               SELECT '{entity_type}', new.id, 'updated', new.updatedAt, {now}, {device}, {source}, json_group_array(json(change))
+               FROM ({diffs})
+               HAVING COUNT(*) > 0;
+             END;
+             CREATE TRIGGER {table}_history_ad AFTER DELETE ON {table} BEGIN
+               INSERT INTO entity_history (entityType, entityId, kind, changedAt, recordedAt, deviceId, source, changes)
+               VALUES ('{entity_type}', old.id, 'deleted', {now}, {now}, {device}, {source}, '[]');
+             END;",
+            table = table,
+            entity_type = entity_type,
+            now = NOW,
+            device = device,
+            source = source,
+            diffs = diffs.join(" UNION ALL "),
+        ))
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Every recorded change to the task or project `id`, oldest first.
+pub fn entity_history(conn: &Connection, id: &str) -> Result<Vec<HistoryEntry>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT entityType, entityId, kind, changedAt, recordedAt, deviceId, source, changes
+             FROM entity_history WHERE entityId = ?1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([id], |row| {
+            Ok((
+                HistoryEntry {
+                    entity_type: row.get(0)?,
+                    entity_id: row.get(1)?,
+                    kind: row.get(2)?,
+                    changed_at: row.get(3)?,
+                    recorded_at: row.get(4)?,
+                    device_id: row.get(5)?,
+                    source: row.get(6)?,
+                    changes: Vec::new(),
+                },
+                row.get::<_, String>(7)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    for row in rows {
+        let (mut entry, changes) = row.map_err(|e| e.to_string())?;
+        entry.changes = serde_json::from_str(&changes).map_err(|e| e.to_string())?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Drops the history of entities that were just purged, since it still holds their content.
+pub fn forget_purged(conn: &Connection, purged_at: &str) -> Result<(), String> {
+    for (table, entity_type) in TRACKED_TABLES {
+        conn.execute(
+            &format!(
+                "DELETE FROM entity_history
+                 WHERE entityType = ?1 AND entityId IN (SELECT id FROM {} WHERE purgedAt = ?2)",
+                table
+            ),
+            [*entity_type, purged_at],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        conn
+    }
+
+    /// Runs `sql` as a journaled operation named `label`.
+    fn write(conn: &Connection, label: &str, sql: &str) {
+        let now = "2024-01-02T00:00:00.000Z";
+        journal::begin(conn, label, now).unwrap();
+        conn.execute_batch(sql).unwrap();
+        journal::finish(conn, now).unwrap();
+    }
+
+    fn changes(entry: &HistoryEntry) -> Vec<(&str, &Value, &Value)> {
+        entry
+            .changes
+            .iter()
+            .map(|change| (change.field.as_str(), &change.from, &change.to))
+            .collect()
+    }
+
+    #[test]
+    fn device_id_is_created_once() {
+        let conn = connection();
+        let id = device_id(&conn).unwrap();
+        assert_eq!(id.len(), 32);
+        assert_eq!(device_id(&conn).unwrap(), id);
+    }
+
+    #[test]
+    fn triggers_record_field_changes_with_device_and_source() {
+        let conn = connection();
+        let device = device_id(&conn).unwrap();
+        write(
+            &conn,
+            "create_task",
+            "INSERT INTO tasks (id, title, status, tags, createdAt, updatedAt)
+             VALUES ('t', 'Draft', 'inbox', '[\"#a\"]', '2024-01-01T00:00:00.000Z', '2024-01-01T00:00:00.000Z')",
+        );
+        write(
+            &conn,
+            "update_task",
+            "UPDATE tasks SET title = 'Final', tags = '[\"#a\",\"#b\"]', updatedAt = '2024-01-02T00:00:00.000Z'
+             WHERE id = 't'",
+        );
+        // Only updatedAt moves, so nothing is recorded.
+        write(&conn, "update_task", "UPDATE tasks SET updatedAt = '2024-01-03T00:00:00.000Z' WHERE id = 't'");
+        // Outside any operation, e.g. a sync merge written directly.
+        conn.execute("UPDATE tasks SET status = 'next' WHERE id = 't'", []).unwrap();
+        write(&conn, "delete_task", "DELETE FROM tasks WHERE id = 't'");
+
+        let entries = entity_history(&conn, "t").unwrap();
+        let kinds: Vec<&str> = entries.iter().map(|entry| entry.kind.as_str()).collect();
+        assert_eq!(kinds, ["created", "updated", "updated", "deleted"]);
+        for entry in &entries {
+            assert_eq!(entry.entity_type, "task");
+            assert_eq!(entry.device_id.as_deref(), Some(device.as_str()));
+        }
+        let sources: Vec<Option<&str>> = entries.iter().map(|entry| entry.source.as_deref()).collect();
+        assert_eq!(sources, [Some("create_task"), Some("update_task"), None, Some("delete_task")]);
+
+        assert!(entries[0].changes.is_empty());
+        assert_eq!(entries[0].changed_at.as_deref(), Some("2024-01-01T00:00:00.000Z"));
+        assert_eq!(
+            changes(&entries[1]),
+            [
+                ("title", &Value::from("Draft"), &Value::from("Final")),
+                ("tags", &serde_json::json!(["#a"]), &serde_json::json!(["#a", "#b"])),
+            ]
+        );
+        assert_eq!(entries[1].changed_at.as_deref(), Some("2024-01-02T00:00:00.000Z"));
+        assert_eq!(changes(&entries[2]), [("status", &Value::from("inbox"), &Value::from("next"))]);
+    }
+
+    #[test]
+    fn projects_are_tracked_too() {
+        let conn = connection();
+        conn.execute_batch(
+            "INSERT INTO projects (id, title, status, color, createdAt, updatedAt)
+             VALUES ('p', 'Move', 'active', '#000', '2024-01-01', '2024-01-01');
+             UPDATE projects SET supportNotes = 'Boxes' WHERE id = 'p';",
+        )
+        .unwrap();
+
+        let entries = entity_history(&conn, "p").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].entity_type, "project");
+        assert_eq!(changes(&entries[1]), [("supportNotes", &Value::Null, &Value::from("Boxes"))]);
+    }
+
+    #[test]
+    fn forget_purged_removes_only_the_purged_entities() {
+        let conn = connection();
+        conn.execute_batch(
+            "INSERT INTO tasks (id, title, status, createdAt, updatedAt) VALUES
+               ('gone', 'Secret', 'inbox', '2024-01-01', '2024-01-01'),
+               ('kept', 'Kept', 'inbox', '2024-01-01', '2024-01-01');
+             INSERT INTO projects (id, title, status, color, createdAt, updatedAt, deletedAt)
+             VALUES ('p', 'Secret project', 'active', '#000', '2024-01-01', '2024-01-01', '2024-01-01');
+             UPDATE tasks SET purgedAt = '2024-02-01', title = '' WHERE id = 'gone';
+             UPDATE projects SET purgedAt = '2024-02-01', title = '' WHERE id = 'p';
+             UPDATE tasks SET purgedAt = '2024-01-15' WHERE id = 'kept';",
+        )
+        .unwrap();
+
+        forget_purged(&conn, "2024-02-01").unwrap();
+
+        assert!(entity_history(&conn, "gone").unwrap().is_empty());
+        assert!(entity_history(&conn, "p").unwrap().is_empty());
+        // Purged at another time, so not part of this purge.
+        assert_eq!(entity_history(&conn, "kept").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,530 @@
+//! The HTTP client shared by the remote sync backends.
+//!
+//! One client is built per set of options and reused, so connections are pooled across
+//! requests. Requests that fail with a network error or a transient server error are retried
+//! with exponential backoff. Self-hosted servers with private certificates are trusted either
+//! through an extra CA certificate or by pinning the SHA-256 fingerprint of the server's own
+//! certificate.
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode, Url};
+use ring::digest::{digest, SHA256};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+/// Longest `Retry-After` honoured; longer waits fall back to the backoff delay.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+/// Collections created at most by one `ensure_collection` call.
+const MAX_COLLECTION_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientOptions {
+    pub connect_timeout: Duration,
+    /// Limit on a whole request, including reading the response.
+    pub timeout: Duration,
+    pub max_retries: u32,
+    /// PEM file with certificates trusted in addition to the built-in roots.
+    pub ca_certificate_path: Option<PathBuf>,
+    /// SHA-256 of the server certificate, as 64 hex digits. When set, a server presenting exactly
+    /// this certificate is trusted and every other certificate is refused.
+    pub pinned_sha256: Option<String>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            max_retries: DEFAULT_MAX_RETRIES,
+            ca_certificate_path: None,
+            pinned_sha256: None,
+        }
+    }
+}
+
+/// Normalizes a certificate fingerprint as shown by browsers or `openssl x509 -fingerprint`,
+/// e.g. `AB:CD:...`, to 64 lowercase hex digits.
+pub fn normalize_fingerprint(raw: &str) -> Result<String, String> {
+    let hex: String = raw
+        .trim()
+        .trim_start_matches("sha256/")
+        .chars()
+        .filter(|c| !matches!(c, ':' | ' '))
+        .collect::<String>()
+        .to_lowercase();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Certificate fingerprint must be a SHA-256 hash of 64 hex digits".to_string());
+    }
+    Ok(hex)
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    digest(&SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Accepts exactly the pinned certificate, still checking that the server holds its key.
+#[derive(Debug)]
+struct PinnedCertificate {
+    sha256: String,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if hex_digest(end_entity.as_ref()) == self.sha256 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "server certificate does not match the pinned fingerprint".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    max_retries: u32,
+}
+
+impl HttpClient {
+    pub fn new(options: &ClientOptions) -> Result<HttpClient, String> {
+        let mut builder = Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .timeout(options.timeout);
+        if let Some(pin) = &options.pinned_sha256 {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let verifier = PinnedCertificate {
+                sha256: normalize_fingerprint(pin)?,
+                algorithms: provider.signature_verification_algorithms,
+            };
+            let tls = rustls::ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .map_err(|e| e.to_string())?
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth();
+            builder = builder.use_preconfigured_tls(tls);
+        } else if let Some(path) = &options.ca_certificate_path {
+            let pem = fs::read(path).map_err(|e| format!("Failed to read CA certificate {}: {}", path.display(), e))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid CA certificate {}: {}", path.display(), e))?;
+            if certificates.is_empty() {
+                return Err(format!("No certificates found in {}", path.display()));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        let client = builder.build().map_err(|e| e.to_string())?;
+        Ok(HttpClient {
+            client,
+            max_retries: options.max_retries,
+        })
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Sends `request`, retrying connection failures, timeouts, `429` and `5xx` responses up to
+    /// `max_retries` times. Waits double from half a second, or follow `Retry-After`. A conditional
+    /// write (`If-Match` or `If-None-Match`) is only retried when it couldn't connect: once sent
+    /// it may have been applied, and sending it again would fail its own precondition.
+    pub fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let precondition = [reqwest::header::IF_MATCH, reqwest::header::IF_NONE_MATCH];
+        let conditional =
+            !request.method().is_safe() && precondition.iter().any(|name| request.headers().contains_key(name));
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut attempt = 0;
+        loop {
+            // Bodies set from bytes or JSON can always be cloned; anything else is sent once.
+            let retry = if attempt < self.max_retries { request.try_clone() } else { None };
+            let Some(next) = retry else {
+                return client.execute(request);
+            };
+            attempt += 1;
+            let wait = match client.execute(next) {
+                Ok(response) if is_transient(response.status()) && !conditional => {
+                    retry_after(&response).unwrap_or(delay)
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if e.is_connect() || (e.is_timeout() && !conditional) => delay,
+                Err(e) => return Err(e),
+            };
+            log::warn!("Retrying HTTP request in {:?} (attempt {})", wait, attempt);
+            std::thread::sleep(wait);
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+
+    /// Creates the WebDAV collection `url` (ending in `/`) and any missing parents with MKCOL.
+    /// `authorize` adds credentials to each request.
+    pub fn ensure_collection(
+        &self,
+        url: &Url,
+        authorize: &dyn Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<(), String> {
+        // Walk up until a MKCOL succeeds or finds the collection, then back down.
+        let mut missing = vec![url.clone()];
+        while let Some(collection) = missing.last() {
+            if missing.len() > MAX_COLLECTION_DEPTH || collection.path() == "/" {
+                return Err(format!("Failed to create WebDAV folder {}", url));
+            }
+            let mkcol = Method::from_bytes(b"MKCOL").map_err(|e| e.to_string())?;
+            let response = self
+                .send(authorize(self.request(mkcol, collection.as_str())))
+                .map_err(|e| format!("WebDAV request failed: {e}"))?;
+            match response.status() {
+                // 405: the collection already exists.
+                status if status.is_success() || status == StatusCode::METHOD_NOT_ALLOWED => {
+                    missing.pop();
+                }
+                // 409: its parent doesn't exist yet.
+                StatusCode::CONFLICT => {
+                    let parent = collection.join("..").map_err(|e| e.to_string())?;
+                    missing.push(parent);
+                }
+                status => return Err(format!("Failed to create WebDAV folder {}: {}", collection, status)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status.is_server_error()
+            && status != StatusCode::NOT_IMPLEMENTED
+            && status != StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds: u64 = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds)).filter(|wait| *wait <= MAX_RETRY_AFTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::PrivateKeyDer;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    /// Method and path of each request a test server received.
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    fn testdata(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name)
+    }
+
+    fn client(max_retries: u32) -> HttpClient {
+        HttpClient::new(&ClientOptions {
+            max_retries,
+            ..ClientOptions::default()
+        })
+        .unwrap()
+    }
+
+    /// Reads one request, returning its method and path.
+    fn read_request(stream: impl Read) -> (String, String) {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut parts = line.split_whitespace().map(str::to_string);
+        let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let Some((name, value)) = header.trim_end().split_once(':') else {
+                break;
+            };
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        (method, path)
+    }
+
+    fn write_response(mut stream: impl Write, status: &str) {
+        write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+        stream.flush().unwrap();
+    }
+
+    /// Serves plain HTTP, answering each request with the status line and headers `respond` gives
+    /// for it and the requests before it.
+    fn serve(respond: impl Fn(&str, &str, &[(String, String)]) -> String + Send + 'static) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Requests = Arc::default();
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (method, path) = read_request(stream.try_clone().unwrap());
+                let status = respond(&method, &path, &log.lock().unwrap());
+                log.lock().unwrap().push((method, path));
+                write_response(stream, &status);
+            }
+        });
+        (url, requests)
+    }
+
+    /// Serves HTTPS with the test server certificate, signed by `testdata/ca.pem`, answering `200`.
+    fn serve_tls() -> String {
+        let certificates = CertificateDer::pem_file_iter(testdata("server.pem"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_file(testdata("server.key")).unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(certificates, key)
+            .unwrap();
+        let config = Arc::new(config);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://127.0.0.1:{}/", listener.local_addr().unwrap().port());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+                let mut tls = rustls::StreamOwned::new(connection, stream);
+                // A client that refuses the certificate ends the handshake, failing the read.
+                let mut line = String::new();
+                if BufReader::new(&mut tls).read_line(&mut line).is_ok() && !line.is_empty() {
+                    write_response(&mut tls, "200 OK");
+                }
+            }
+        });
+        url
+    }
+
+    fn get(client: &HttpClient, url: &str) -> Result<Response, reqwest::Error> {
+        client.send(client.request(Method::GET, url))
+    }
+
+    fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/", listener.local_addr().unwrap())
+    }
+
+    #[test]
+    fn transient_failures_are_retried_with_backoff() {
+        let (url, requests) = serve(|_, _, before| {
+            if before.len() < 2 { "503 Service Unavailable" } else { "200 OK" }.to_string()
+        });
+        let started = Instant::now();
+        let response = get(&client(3), &url).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        // Half a second, then a second.
+        assert!(started.elapsed() >= Duration::from_millis(1500), "{:?}", started.elapsed());
+
+        // Out of retries, the last response is returned.
+        let (url, requests) = serve(|_, _, _| "500 Internal Server Error".to_string());
+        assert_eq!(get(&client(1), &url).unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        // Errors that won't go away aren't retried.
+        let (url, requests) = serve(|_, _, _| "501 Not Implemented".to_string());
+        assert_eq!(get(&client(3), &url).unwrap().status(), StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // Nor is a failure to connect once retries run out.
+        let error = get(&client(0), &closed_port()).unwrap_err();
+        assert!(error.is_connect(), "{error}");
+    }
+
+    #[test]
+    fn retry_after_replaces_the_backoff_delay() {
+        let (url, _) = serve(|_, _, before| {
+            if before.is_empty() { "429 Too Many Requests\r\nRetry-After: 2" } else { "200 OK" }.to_string()
+        });
+        let started = Instant::now();
+        assert_eq!(get(&client(1), &url).unwrap().status(), StatusCode::OK);
+        assert!(started.elapsed() >= Duration::from_secs(2), "{:?}", started.elapsed());
+
+        // A wait longer than MAX_RETRY_AFTER falls back to the backoff delay.
+        let (url, _) = serve(|_, _, before| {
+            if before.is_empty() { "503 Service Unavailable\r\nRetry-After: 3600" } else { "200 OK" }.to_string()
+        });
+        let started = Instant::now();
+        assert_eq!(get(&client(1), &url).unwrap().status(), StatusCode::OK);
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn conditional_writes_are_only_retried_when_they_could_not_connect() {
+        let (url, requests) = serve(|_, _, _| "503 Service Unavailable".to_string());
+        let http = client(3);
+        for (name, value) in [(reqwest::header::IF_MATCH, "\"1\""), (reqwest::header::IF_NONE_MATCH, "*")] {
+            let put = http.request(Method::PUT, &url).header(name, value).body("{}");
+            assert_eq!(http.send(put).unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        // A conditional read is retried like any other.
+        let read = http.request(Method::GET, &url).header(reqwest::header::IF_NONE_MATCH, "\"1\"");
+        assert_eq!(http.send(read).unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.lock().unwrap().len(), 6);
+
+        // A write that never reached the server is sent again.
+        let started = Instant::now();
+        let put = client(1).request(Method::PUT, &closed_port()).header(reqwest::header::IF_MATCH, "\"1\"");
+        assert!(client(1).send(put).unwrap_err().is_connect());
+        assert!(started.elapsed() >= FIRST_RETRY_DELAY, "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn ensure_collection_creates_missing_parents() {
+        let existing = Arc::new(Mutex::new(vec!["/dav/".to_string()]));
+        let collections = existing.clone();
+        let (url, requests) = serve(move |method, path, _| {
+            let mut collections = collections.lock().unwrap();
+            let parent = format!("{}/", path.trim_end_matches('/').rsplit_once('/').unwrap().0);
+            if method != "MKCOL" {
+                "400 Bad Request"
+            } else if collections.iter().any(|collection| collection == path) {
+                "405 Method Not Allowed"
+            } else if !collections.contains(&parent) {
+                "409 Conflict"
+            } else {
+                collections.push(path.to_string());
+                "201 Created"
+            }
+            .to_string()
+        });
+        let http = client(0);
+        let folder = Url::parse(&format!("{}/dav/a/b/c/", url)).unwrap();
+        http.ensure_collection(&folder, &|request| request).unwrap();
+
+        let paths: Vec<String> = requests.lock().unwrap().iter().map(|(_, path)| path.clone()).collect();
+        assert_eq!(paths, ["/dav/a/b/c/", "/dav/a/b/", "/dav/a/", "/dav/a/b/", "/dav/a/b/c/"]);
+        assert!(existing.lock().unwrap().contains(&"/dav/a/b/c/".to_string()));
+
+        // An existing collection needs nothing more.
+        http.ensure_collection(&folder, &|request| request).unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 6);
+
+        // The walk stops at the root rather than climbing forever.
+        let (url, _) = serve(|_, _, _| "409 Conflict".to_string());
+        let folder = Url::parse(&format!("{}/x/y/", url)).unwrap();
+        assert!(http.ensure_collection(&folder, &|request| request).is_err());
+    }
+
+    #[test]
+    fn custom_ca_certificates_are_trusted() {
+        let url = serve_tls();
+        assert!(get(&client(0), &url).is_err(), "the test CA is not a built-in root");
+
+        let trusting = HttpClient::new(&ClientOptions {
+            max_retries: 0,
+            ca_certificate_path: Some(testdata("ca.pem")),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        assert_eq!(get(&trusting, &url).unwrap().status(), StatusCode::OK);
+
+        let with_ca = |path: PathBuf| {
+            HttpClient::new(&ClientOptions {
+                ca_certificate_path: Some(path),
+                ..ClientOptions::default()
+            })
+        };
+        assert!(with_ca(testdata("missing.pem")).is_err());
+        assert!(with_ca(testdata("server.key")).unwrap_err().starts_with("No certificates found"));
+    }
+
+    #[test]
+    fn pinned_certificate_is_the_only_one_trusted() {
+        let url = serve_tls();
+        let fingerprint = |name: &str| {
+            let certificate = CertificateDer::from_pem_file(testdata(name)).unwrap();
+            hex_digest(certificate.as_ref())
+        };
+        let pinned = |sha256: String| {
+            HttpClient::new(&ClientOptions {
+                max_retries: 0,
+                pinned_sha256: Some(sha256),
+                // The pin replaces the CA, rather than adding to it.
+                ca_certificate_path: Some(testdata("ca.pem")),
+                ..ClientOptions::default()
+            })
+            .unwrap()
+        };
+
+        // As `openssl x509 -fingerprint -sha256` prints it.
+        let server = fingerprint("server.pem");
+        let openssl_style = server
+            .to_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| String::from_utf8_lossy(pair).into_owned())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(normalize_fingerprint(&openssl_style).unwrap(), server);
+        assert_eq!(get(&pinned(openssl_style), &url).unwrap().status(), StatusCode::OK);
+
+        let error = get(&pinned(fingerprint("ca.pem")), &url).unwrap_err();
+        assert!(format!("{:?}", error).contains("pinned fingerprint"), "{error:?}");
+        assert!(normalize_fingerprint("ab:cd").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Diagnosis and repair of a damaged database.
+//!
+//! The check covers SQLite's own page and index consistency, the FTS indexes, and references
+//! between entities that point at rows which no longer exist. Repair fixes what can be fixed in
+//! place; page-level corruption it can't undo is left in the report, and the way out is
+//! restoring a backup.
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::fts;
+
+/// Color given to projects recreated for orphaned sections, matching the adapter's fallback.
+const RECOVERED_PROJECT_COLOR: &str = "#6B7280";
+const RECOVERED_PROJECT_TITLE: &str = "Recovered project";
+
+/// A reference checked for orphans: (table, entity type, column, referenced table).
+/// Sections come first because repairing them recreates projects tasks may point at too.
+const REFERENCES: &[(&str, &str, &str, &str)] = &[
+    ("sections", "section", "projectId", "projects"),
+    ("tasks", "task", "projectId", "projects"),
+    ("tasks", "task", "sectionId", "sections"),
+    ("tasks", "task", "areaId", "areas"),
+    ("projects", "project", "areaId", "areas"),
+];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Orphan {
+    pub entity_type: String,
+    pub id: String,
+    pub field: String,
+    /// The id the field points at, which has no row.
+    pub missing_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckReport {
+    /// "quick_check" or "integrity_check".
+    pub check: String,
+    /// Problems reported by SQLite; empty when the check passed.
+    pub errors: Vec<String>,
+    /// FTS tables that are corrupt or out of step with their source tables.
+    pub inconsistent_fts: Vec<String>,
+    pub orphans: Vec<Orphan>,
+    /// True when all of the above came back clean.
+    pub healthy: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanFix {
+    #[serde(flatten)]
+    pub orphan: Orphan,
+    /// "detached" when the field was cleared, "reassigned" when a placeholder project was
+    /// created under the missing id.
+    pub action: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairReport {
+    pub before: CheckReport,
+    pub fts_rebuilt: Vec<String>,
+    pub orphans_fixed: Vec<OrphanFix>,
+    /// Whether `REINDEX` ran, which it does when SQLite reported problems.
+    pub reindexed: bool,
+    pub after: CheckReport,
+}
+
+/// Runs `PRAGMA quick_check`, or the slower `integrity_check` when `full` is set, plus the FTS
+/// and orphan checks.
+pub fn check(conn: &Connection, full: bool) -> Result<CheckReport, String> {
+    let pragma = if full { "integrity_check" } else { "quick_check" };
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA {}", pragma))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    let mut errors = Vec::new();
+    for row in rows {
+        let message = row.map_err(|e| e.to_string())?;
+        if message != "ok" {
+            errors.push(message);
+        }
+    }
+    let inconsistent_fts = fts::inconsistent_indexes(conn)?;
+    let orphans = find_orphans(conn)?;
+    Ok(CheckReport {
+        check: pragma.to_string(),
+        healthy: errors.is_empty() && inconsistent_fts.is_empty() && orphans.is_empty(),
+        errors,
+        inconsistent_fts,
+        orphans,
+    })
+}
+
+fn orphans_of(conn: &Connection, reference: &(&str, &str, &str, &str)) -> Result<Vec<Orphan>, String> {
+    let (table, entity_type, column, target) = *reference;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, {column} FROM {table}
+             WHERE {column} IS NOT NULL AND {column} NOT IN (SELECT id FROM {target})
+             ORDER BY id",
+            table = table,
+            column = column,
+            target = target
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(Orphan {
+                entity_type: entity_type.to_string(),
+                id: row.get(0)?,
+                field: column.to_string(),
+                missing_id: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Entities whose project, section or area reference points at a row that doesn't exist.
+/// Soft-deleted and purged rows still exist, so references to them are not orphans.
+pub fn find_orphans(conn: &Connection) -> Result<Vec<Orphan>, String> {
+    let mut orphans = Vec::new();
+    for reference in REFERENCES {
+        orphans.extend(orphans_of(conn, reference)?);
+    }
+    Ok(orphans)
+}
+
+/// Fixes orphaned references. Sections can't exist without a project, so a placeholder project
+/// is created under each missing project id they use; every other orphaned field is cleared.
+/// Fixed rows get `updatedAt = now` so the repair reaches other devices on the next sync.
+pub fn fix_orphans(conn: &Connection, now: &str) -> Result<Vec<OrphanFix>, String> {
+    let mut fixes = Vec::new();
+    for reference in REFERENCES {
+        let (table, _, column, _) = *reference;
+        for orphan in orphans_of(conn, reference)? {
+            let action = if table == "sections" {
+                conn.execute(
+                    "INSERT OR IGNORE INTO projects (id, title, status, color, createdAt, updatedAt)
+                     VALUES (?1, ?2, 'active', ?3, ?4, ?4)",
+                    rusqlite::params![orphan.missing_id, RECOVERED_PROJECT_TITLE, RECOVERED_PROJECT_COLOR, now],
+                )
+                .map_err(|e| e.to_string())?;
+                "reassigned"
+            } else {
+                conn.execute(
+                    &format!("UPDATE {} SET {} = NULL, updatedAt = ?1 WHERE id = ?2", table, column),
+                    [now, orphan.id.as_str()],
+                )
+                .map_err(|e| e.to_string())?;
+                "detached"
+            };
+            fixes.push(OrphanFix {
+                orphan,
+                action: action.to_string(),
+            });
+        }
+    }
+    Ok(fixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: &str = "2024-02-01T00:00:00.000Z";
+
+    /// A database with one orphan per reference, next to valid and soft-deleted references.
+    fn damaged() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO areas (id, name, orderNum, createdAt, updatedAt)
+             VALUES ('home', 'Home', 0, '2024-01-01', '2024-01-01');
+             INSERT INTO projects (id, title, status, color, areaId, createdAt, updatedAt, deletedAt) VALUES
+               ('kept', 'Kept', 'active', '#000', 'home', '2024-01-01', '2024-01-01', NULL),
+               ('trashed', 'Trashed', 'active', '#000', NULL, '2024-01-01', '2024-01-01', '2024-01-02'),
+               ('lost-area', 'Lost area', 'active', '#000', 'gone-area', '2024-01-01', '2024-01-01', NULL);
+             INSERT INTO sections (id, projectId, title, createdAt, updatedAt) VALUES
+               ('s-kept', 'kept', 'Kept', '2024-01-01', '2024-01-01'),
+               ('s-lost', 'gone-project', 'Lost', '2024-01-01', '2024-01-01');
+             INSERT INTO tasks (id, title, status, projectId, sectionId, areaId, createdAt, updatedAt) VALUES
+               ('fine', 'Fine', 'inbox', 'trashed', 's-kept', 'home', '2024-01-01', '2024-01-01'),
+               ('no-project', 'No project', 'inbox', 'other-project', NULL, NULL, '2024-01-01', '2024-01-01'),
+               ('no-section', 'No section', 'inbox', NULL, 'gone-section', NULL, '2024-01-01', '2024-01-01'),
+               ('no-area', 'No area', 'inbox', NULL, NULL, 'gone-area', '2024-01-01', '2024-01-01');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn orphans(report: &[Orphan]) -> Vec<(&str, &str, &str, &str)> {
+        report
+            .iter()
+            .map(|orphan| {
+                (
+                    orphan.entity_type.as_str(),
+                    orphan.id.as_str(),
+                    orphan.field.as_str(),
+                    orphan.missing_id.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn updated_at(conn: &Connection, table: &str, id: &str) -> String {
+        conn.query_row(&format!("SELECT updatedAt FROM {} WHERE id = ?1", table), [id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn check_reports_orphans_and_fts_drift() {
+        let conn = damaged();
+        conn.execute("DELETE FROM tasks_fts WHERE id = 'fine'", []).unwrap();
+
+        let report = check(&conn, true).unwrap();
+
+        assert_eq!(report.check, "integrity_check");
+        assert!(report.errors.is_empty());
+        assert_eq!(report.inconsistent_fts, ["tasks_fts"]);
+        assert_eq!(
+            orphans(&report.orphans),
+            [
+                ("section", "s-lost", "projectId", "gone-project"),
+                ("task", "no-project", "projectId", "other-project"),
+                ("task", "no-section", "sectionId", "gone-section"),
+                ("task", "no-area", "areaId", "gone-area"),
+                ("project", "lost-area", "areaId", "gone-area"),
+            ]
+        );
+        assert!(!report.healthy);
+        assert_eq!(check(&conn, false).unwrap().check, "quick_check");
+    }
+
+    #[test]
+    fn fix_orphans_detaches_references_and_recovers_projects() {
+        let conn = damaged();
+
+        let fixes = fix_orphans(&conn, NOW).unwrap();
+
+        let actions: Vec<(&str, &str)> = fixes
+            .iter()
+            .map(|fix| (fix.orphan.id.as_str(), fix.action.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                ("s-lost", "reassigned"),
+                ("no-project", "detached"),
+                ("no-section", "detached"),
+                ("no-area", "detached"),
+                ("lost-area", "detached"),
+            ]
+        );
+        let recovered: (String, String, String) = conn
+            .query_row("SELECT title, color, updatedAt FROM projects WHERE id = 'gone-project'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(
+            recovered,
+            (RECOVERED_PROJECT_TITLE.to_string(), RECOVERED_PROJECT_COLOR.to_string(), NOW.to_string())
+        );
+        let project: Option<String> = conn
+            .query_row("SELECT projectId FROM tasks WHERE id = 'no-project'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(project, None);
+        // Repaired rows are stamped so the fix syncs; untouched ones keep their timestamp.
+        assert_eq!(updated_at(&conn, "tasks", "no-area"), NOW);
+        assert_eq!(updated_at(&conn, "projects", "lost-area"), NOW);
+        assert_eq!(updated_at(&conn, "tasks", "fine"), "2024-01-01");
+
+        assert!(find_orphans(&conn).unwrap().is_empty());
+        assert!(fix_orphans(&conn, NOW).unwrap().is_empty());
+        fts::ensure_populated(&conn, false).unwrap();
+        assert!(check(&conn, true).unwrap().healthy);
+    }
+}
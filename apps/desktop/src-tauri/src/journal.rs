@@ -0,0 +1,445 @@
+//! Undo/redo journal.
+//!
+//! Every write command runs as one operation. While an operation is open, triggers on the data
+//! tables copy the old and new image of each changed row into `operation_changes`, so undo can
+//! put the old images back and redo the new ones. Row images are JSON objects keyed by column
+//! name, built by SQLite itself so the triggers and the conflict checks agree byte for byte.
+//!
+//! Operations form a stack: undo takes the newest operation that is still applied, redo the
+//! oldest undone one, and a new operation discards everything that was undone.
+//!
+//! Rows put back by undo or redo get a fresh `updatedAt`, so sync treats them as the newest
+//! edit instead of reverting them to the remote copy.
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// Tables whose changes are journaled. Each has an `id` primary key.
+const JOURNALED_TABLES: &[&str] = &["tasks", "projects", "sections", "areas", "settings"];
+
+/// Journaled tables whose rows can be purged from the trash.
+const PURGEABLE_TABLES: &[&str] = &["tasks", "projects", "sections"];
+
+/// The journal keeps at most this many operations...
+const MAX_OPERATIONS: i64 = 200;
+/// ...and none older than this many days.
+const MAX_AGE_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationSummary {
+    pub id: i64,
+    pub label: String,
+    pub created_at: String,
+    pub undone_at: Option<String>,
+    /// Number of row changes recorded for the operation.
+    pub changes: i64,
+}
+
+struct Change {
+    table: String,
+    entity_id: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+/// Column names of `table`, in schema order.
+pub fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// `json_object(...)` over every column of `table`, reading them from `row`.
+fn row_image(columns: &[String], row: &str) -> String {
+    let pairs: Vec<String> = columns
+        .iter()
+        .map(|column| format!("'{column}', {row}.{column}", column = column, row = row))
+        .collect();
+    format!("json_object({})", pairs.join(", "))
+}
+
+/// (Re)creates the journal triggers from the current table columns. Run after migrations so
+/// columns added later are captured too.
+pub fn install_triggers(conn: &Connection) -> Result<(), String> {
+    for table in JOURNALED_TABLES {
+        let columns = table_columns(conn, table)?;
+        let active = "(SELECT operationId FROM journal_cursor WHERE id = 1)";
+        conn.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {table}_journal_ai;
+             DROP TRIGGER IF EXISTS {table}_journal_au;
+             DROP TRIGGER IF EXISTS {table}_journal_ad;
+             CREATE TRIGGER {table}_journal_ai AFTER INSERT ON {table} WHEN {active} IS NOT NULL BEGIN
+               INSERT INTO operation_changes (operationId, entityType, entityId, before, after)
+               VALUES ({active}, '{table}', new.id, NULL, {new_image});
+             END;
+             CREATE TRIGGER {table}_journal_au AFTER UPDATE ON {table} WHEN {active} IS NOT NULL BEGIN
+               INSERT INTO operation_changes (operationId, entityType, entityId, before, after)
+               VALUES ({active}, '{table}', new.id, {old_image}, {new_image});
+             END;
+             CREATE TRIGGER {table}_journal_ad AFTER DELETE ON {table} WHEN {active} IS NOT NULL BEGIN
+               INSERT INTO operation_changes (operationId, entityType, entityId, before, after)
+               VALUES ({active}, '{table}', old.id, {old_image}, NULL);
+             END;",
+            table = table,
+            active = active,
+            old_image = row_image(&columns, "old"),
+            new_image = row_image(&columns, "new"),
+        ))
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Opens an operation; changes are recorded against it until `finish`.
+pub fn begin(conn: &Connection, label: &str, now: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO operations (label, createdAt) VALUES (?1, ?2)",
+        params![label, now],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO journal_cursor (id, operationId) VALUES (1, ?1)
+         ON CONFLICT(id) DO UPDATE SET operationId = excluded.operationId",
+        [conn.last_insert_rowid()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Closes the open operation. An operation that changed nothing is dropped; otherwise the redo
+/// stack is discarded and the journal trimmed to its bounds.
+pub fn finish(conn: &Connection, now: &str) -> Result<(), String> {
+    let operation_id: Option<i64> = conn
+        .query_row("SELECT operationId FROM journal_cursor WHERE id = 1", [], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    conn.execute("UPDATE journal_cursor SET operationId = NULL WHERE id = 1", [])
+        .map_err(|e| e.to_string())?;
+    let operation_id = match operation_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let changes: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM operation_changes WHERE operationId = ?1",
+            [operation_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if changes == 0 {
+        conn.execute("DELETE FROM operations WHERE id = ?1", [operation_id])
+            .map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    let cutoff = (chrono::DateTime::parse_from_rfc3339(now).map_err(|e| e.to_string())?
+        - chrono::Duration::days(MAX_AGE_DAYS))
+    .with_timezone(&chrono::Utc)
+    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    conn.execute(
+        "DELETE FROM operations
+         WHERE undoneAt IS NOT NULL
+            OR createdAt < ?1
+            OR id <= (SELECT id FROM operations ORDER BY id DESC LIMIT 1 OFFSET ?2)",
+        params![cutoff, MAX_OPERATIONS],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM operation_changes WHERE operationId NOT IN (SELECT id FROM operations)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Drops the row images of entities that were just purged, since they still hold their content,
+/// along with operations left without changes. That includes the operation doing the purge, so
+/// it can't be undone; it stays open until `finish`.
+pub fn forget_purged(conn: &Connection, purged_at: &str) -> Result<(), String> {
+    for table in PURGEABLE_TABLES {
+        conn.execute(
+            &format!(
+                "DELETE FROM operation_changes
+                 WHERE entityType = ?1 AND entityId IN (SELECT id FROM {} WHERE purgedAt = ?2)",
+                table
+            ),
+            [*table, purged_at],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    conn.execute(
+        "DELETE FROM operations
+         WHERE id NOT IN (SELECT operationId FROM operation_changes)
+           AND id NOT IN (SELECT operationId FROM journal_cursor WHERE operationId IS NOT NULL)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn list_recent(conn: &Connection, limit: i64) -> Result<Vec<OperationSummary>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.id, o.label, o.createdAt, o.undoneAt,
+                    (SELECT COUNT(*) FROM operation_changes c WHERE c.operationId = o.id)
+             FROM operations o
+             ORDER BY o.id DESC
+             LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([limit], row_to_summary)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+fn row_to_summary(row: &rusqlite::Row<'_>) -> Result<OperationSummary, rusqlite::Error> {
+    Ok(OperationSummary {
+        id: row.get(0)?,
+        label: row.get(1)?,
+        created_at: row.get(2)?,
+        undone_at: row.get(3)?,
+        changes: row.get(4)?,
+    })
+}
+
+fn summary(conn: &Connection, operation_id: i64) -> Result<OperationSummary, String> {
+    conn.query_row(
+        "SELECT o.id, o.label, o.createdAt, o.undoneAt,
+                (SELECT COUNT(*) FROM operation_changes c WHERE c.operationId = o.id)
+         FROM operations o WHERE o.id = ?1",
+        [operation_id],
+        row_to_summary,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Reverts the newest applied operation. Returns None when there is nothing to undo.
+pub fn undo(conn: &Connection, now: &str) -> Result<Option<OperationSummary>, String> {
+    let operation_id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM operations WHERE undoneAt IS NULL ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let operation_id = match operation_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let mut changes = load_changes(conn, operation_id)?;
+    changes.reverse();
+    restore(conn, &changes, |change| change.after.as_deref(), |change| change.before.as_deref(), now)?;
+    conn.execute("UPDATE operations SET undoneAt = ?1 WHERE id = ?2", params![now, operation_id])
+        .map_err(|e| e.to_string())?;
+    summary(conn, operation_id).map(Some)
+}
+
+/// Re-applies the most recently undone operation. Returns None when there is nothing to redo.
+pub fn redo(conn: &Connection, now: &str) -> Result<Option<OperationSummary>, String> {
+    let operation_id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM operations WHERE undoneAt IS NOT NULL ORDER BY id ASC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let operation_id = match operation_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let changes = load_changes(conn, operation_id)?;
+    restore(conn, &changes, |change| change.before.as_deref(), |change| change.after.as_deref(), now)?;
+    conn.execute("UPDATE operations SET undoneAt = NULL WHERE id = ?1", [operation_id])
+        .map_err(|e| e.to_string())?;
+    summary(conn, operation_id).map(Some)
+}
+
+fn load_changes(conn: &Connection, operation_id: i64) -> Result<Vec<Change>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT entityType, entityId, before, after FROM operation_changes
+             WHERE operationId = ?1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([operation_id], |row| {
+            Ok(Change {
+                table: row.get(0)?,
+                entity_id: row.get(1)?,
+                before: row.get(2)?,
+                after: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Writes the `target` image of every change, in order, after checking that each touched row
+/// still holds the `expected` image of its first change. A row edited outside the journal since
+/// the operation ran makes the whole undo or redo fail rather than silently clobbering it.
+/// `updatedAt` is left out of the check, since an earlier undo or redo stamped it.
+fn restore(
+    conn: &Connection,
+    changes: &[Change],
+    expected: fn(&Change) -> Option<&str>,
+    target: fn(&Change) -> Option<&str>,
+    now: &str,
+) -> Result<(), String> {
+    let mut checked: HashSet<(&str, &str)> = HashSet::new();
+    for change in changes {
+        if !checked.insert((change.table.as_str(), change.entity_id.as_str())) {
+            continue;
+        }
+        let columns = table_columns(conn, &change.table)?;
+        let current: Option<String> = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM {} r WHERE r.id = ?1",
+                    row_image(&columns, "r"),
+                    change.table
+                ),
+                [&change.entity_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if !same_content(current.as_deref(), expected(change))? {
+            return Err(format!(
+                "{} {} has changed since this operation and can't be restored",
+                change.table, change.entity_id
+            ));
+        }
+    }
+
+    for change in changes {
+        match target(change) {
+            Some(image) => write_image(conn, &change.table, image, now)?,
+            None => {
+                conn.execute(&format!("DELETE FROM {} WHERE id = ?1", change.table), [&change.entity_id])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether two row images match in everything but `updatedAt`.
+fn same_content(current: Option<&str>, expected: Option<&str>) -> Result<bool, String> {
+    let content = |image: Option<&str>| -> Result<Option<Map<String, Value>>, String> {
+        let Some(image) = image else {
+            return Ok(None);
+        };
+        let mut image: Map<String, Value> = serde_json::from_str(image).map_err(|e| e.to_string())?;
+        image.remove("updatedAt");
+        Ok(Some(image))
+    };
+    Ok(content(current)? == content(expected)?)
+}
+
+/// Writes a row image, stamping `updatedAt` with `now` on tables that have it, deleted rows
+/// included.
+fn write_image(conn: &Connection, table: &str, image: &str, now: &str) -> Result<(), String> {
+    let mut image: Map<String, Value> = serde_json::from_str(image).map_err(|e| e.to_string())?;
+    if let Some(updated_at) = image.get_mut("updatedAt") {
+        *updated_at = Value::String(now.to_string());
+    }
+    let columns: Vec<&String> = image.keys().collect();
+    let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+    let assignments: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{column} = excluded.{column}", column = column))
+        .collect();
+    let values: Vec<SqlValue> = image.values().map(sql_value).collect();
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} ({columns}) VALUES ({placeholders})
+             ON CONFLICT(id) DO UPDATE SET {assignments}",
+            table = table,
+            columns = columns.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", "),
+            placeholders = placeholders.join(", "),
+            assignments = assignments.join(", ")
+        ),
+        params_from_iter(values),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(flag) => SqlValue::Integer(i64::from(*flag)),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => SqlValue::Integer(integer),
+            None => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(text) => SqlValue::Text(text.clone()),
+        // json_object() only nests JSON for values that are themselves JSON, which plain
+        // columns never are; keep the text form just in case.
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        conn
+    }
+
+    fn task(conn: &Connection) -> (String, String, Option<String>) {
+        conn.query_row("SELECT title, updatedAt, deletedAt FROM tasks WHERE id = 't'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .unwrap()
+    }
+
+    fn edit(conn: &Connection, sql: &str, now: &str) {
+        begin(conn, "edit", now).unwrap();
+        conn.execute(sql, [now]).unwrap();
+        finish(conn, now).unwrap();
+    }
+
+    #[test]
+    fn undo_and_redo_stamp_updated_at() {
+        let conn = connection();
+        conn.execute(
+            "INSERT INTO tasks (id, title, status, createdAt, updatedAt)
+             VALUES ('t', 'Old', 'inbox', '2024-01-01T00:00:00.000Z', '2024-01-01T00:00:00.000Z')",
+            [],
+        )
+        .unwrap();
+        edit(&conn, "UPDATE tasks SET title = 'New', updatedAt = ?1 WHERE id = 't'", "2024-01-02T00:00:00.000Z");
+
+        undo(&conn, "2024-01-03T00:00:00.000Z").unwrap().unwrap();
+        assert_eq!(task(&conn), ("Old".to_string(), "2024-01-03T00:00:00.000Z".to_string(), None));
+        redo(&conn, "2024-01-04T00:00:00.000Z").unwrap().unwrap();
+        assert_eq!(task(&conn), ("New".to_string(), "2024-01-04T00:00:00.000Z".to_string(), None));
+
+        // Tombstones are stamped too.
+        edit(&conn, "UPDATE tasks SET deletedAt = ?1, updatedAt = ?1 WHERE id = 't'", "2024-01-05T00:00:00.000Z");
+        undo(&conn, "2024-01-06T00:00:00.000Z").unwrap().unwrap();
+        redo(&conn, "2024-01-07T00:00:00.000Z").unwrap().unwrap();
+        let (_, updated_at, deleted_at) = task(&conn);
+        assert_eq!(updated_at, "2024-01-07T00:00:00.000Z");
+        assert_eq!(deleted_at.as_deref(), Some("2024-01-05T00:00:00.000Z"));
+
+        // An edit outside the journal still blocks undo.
+        conn.execute("UPDATE tasks SET title = 'Elsewhere' WHERE id = 't'", []).unwrap();
+        assert!(undo(&conn, "2024-01-08T00:00:00.000Z").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::fs::File;
@@ -9,8 +10,8 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tauri::{Emitter, Manager};
 use tauri::menu::{Menu, MenuItem};
 use tauri::path::BaseDirectory;
@@ -19,9 +20,39 @@ use tauri::image::Image;
 use tauri_plugin_global_shortcut::GlobalShortcutExt;
 use rusqlite::{params, Connection, OptionalExtension, params_from_iter, ToSql};
 use keyring::{Entry, Error as KeyringError};
+use model::{parse_entity, AppData, Area, Entity, Project, ProjectStatus, Section, Settings, Task, TaskPriority, TaskStatus};
+use backup::BackupInfo;
+use encryption::StorageKey;
+use fts::FtsConfig;
+use history::HistoryEntry;
+use integrity::{CheckReport, RepairReport};
+use journal::OperationSummary;
+use search_query::{QueryError, SearchQuery, TextIndex};
+use http_client::{ClientOptions, HttpClient};
+use sync_file::FileStamp;
+use tags::{ContextUsage, TagChange, TagUsage};
+use trash::PurgeReport;
+use validation::{Problem, Repair};
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 
+mod app_meta;
+mod backup;
+mod encryption;
+mod fts;
+mod history;
+mod http_client;
+mod integrity;
+mod journal;
+mod migrations;
+mod model;
+mod search_query;
+mod sync;
+mod sync_file;
+mod tags;
+mod trash;
+mod validation;
+
 /// App name used for config directories and files
 const APP_NAME: &str = "mindwtr";
 const CONFIG_FILE_NAME: &str = "config.toml";
@@ -33,168 +64,30 @@ const KEYRING_CLOUD_TOKEN: &str = "cloud_token";
 const KEYRING_AI_OPENAI: &str = "ai_key_openai";
 const KEYRING_AI_ANTHROPIC: &str = "ai_key_anthropic";
 const KEYRING_AI_GEMINI: &str = "ai_key_gemini";
-
-const SQLITE_SCHEMA: &str = r#"
+const KEYRING_STORAGE_KEY: &str = "storage_key";
+/// Holds a new storage key while files are being converted to it.
+const KEYRING_STORAGE_KEY_PENDING: &str = "storage_key_pending";
+
+/// Times a sync write is retried after finding the remote document changed since it was read.
+const SYNC_WRITE_ATTEMPTS: usize = 5;
+/// Upper bound on the configurable retries of a WebDAV request.
+const MAX_HTTP_RETRIES: u32 = 10;
+
+const DEFAULT_SEARCH_LIMIT: i64 = 100;
+const DEFAULT_HIGHLIGHT_START: &str = "<mark>";
+const DEFAULT_HIGHLIGHT_END: &str = "</mark>";
+const DEFAULT_OPERATION_LIST_LIMIT: i64 = 20;
+const SEARCH_SNIPPET_TOKENS: i64 = 12;
+const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Quiet period after the last write before the JSON mirror is refreshed...
+const MIRROR_DEBOUNCE: Duration = Duration::from_millis(1500);
+/// ...unless writes keep coming for this long.
+const MIRROR_MAX_DELAY: Duration = Duration::from_secs(10);
+/// How often the backup schedule checks whether an automatic backup is due.
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const SQLITE_PRAGMAS: &str = r#"
 PRAGMA journal_mode = WAL;
 PRAGMA foreign_keys = ON;
-
-CREATE TABLE IF NOT EXISTS tasks (
-  id TEXT PRIMARY KEY,
-  title TEXT NOT NULL,
-  status TEXT NOT NULL,
-  priority TEXT,
-  taskMode TEXT,
-  startTime TEXT,
-  dueDate TEXT,
-  recurrence TEXT,
-  pushCount INTEGER,
-  tags TEXT,
-  contexts TEXT,
-  checklist TEXT,
-  description TEXT,
-  attachments TEXT,
-  location TEXT,
-  projectId TEXT,
-  sectionId TEXT,
-  areaId TEXT,
-  orderNum INTEGER,
-  isFocusedToday INTEGER,
-  timeEstimate TEXT,
-  reviewAt TEXT,
-  completedAt TEXT,
-  createdAt TEXT NOT NULL,
-  updatedAt TEXT NOT NULL,
-  deletedAt TEXT,
-  purgedAt TEXT
-);
-
-CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
-CREATE INDEX IF NOT EXISTS idx_tasks_project_id ON tasks(projectId);
-CREATE INDEX IF NOT EXISTS idx_tasks_updated_at ON tasks(updatedAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_deleted_at ON tasks(deletedAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_due_date ON tasks(dueDate);
-CREATE INDEX IF NOT EXISTS idx_tasks_start_time ON tasks(startTime);
-CREATE INDEX IF NOT EXISTS idx_tasks_review_at ON tasks(reviewAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_created_at ON tasks(createdAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_status_deleted_at ON tasks(status, deletedAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_project_status_deleted_at ON tasks(projectId, status, deletedAt);
-
-CREATE TABLE IF NOT EXISTS projects (
-  id TEXT PRIMARY KEY,
-  title TEXT NOT NULL,
-  status TEXT NOT NULL,
-  color TEXT NOT NULL,
-  orderNum INTEGER,
-  tagIds TEXT,
-  isSequential INTEGER,
-  isFocused INTEGER,
-  supportNotes TEXT,
-  attachments TEXT,
-  reviewAt TEXT,
-  areaId TEXT,
-  areaTitle TEXT,
-  createdAt TEXT NOT NULL,
-  updatedAt TEXT NOT NULL,
-  deletedAt TEXT
-);
-
-CREATE TABLE IF NOT EXISTS areas (
-  id TEXT PRIMARY KEY,
-  name TEXT NOT NULL,
-  color TEXT,
-  icon TEXT,
-  orderNum INTEGER NOT NULL,
-  createdAt TEXT,
-  updatedAt TEXT
-);
-
-CREATE TABLE IF NOT EXISTS sections (
-  id TEXT PRIMARY KEY,
-  projectId TEXT NOT NULL,
-  title TEXT NOT NULL,
-  description TEXT,
-  orderNum INTEGER,
-  isCollapsed INTEGER,
-  createdAt TEXT NOT NULL,
-  updatedAt TEXT NOT NULL,
-  deletedAt TEXT
-);
-
-CREATE TABLE IF NOT EXISTS settings (
-  id INTEGER PRIMARY KEY CHECK (id = 1),
-  data TEXT NOT NULL
-);
-
-CREATE TABLE IF NOT EXISTS schema_migrations (
-  version INTEGER PRIMARY KEY
-);
-
-INSERT OR IGNORE INTO schema_migrations (version) VALUES (1);
-
-CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
-  id UNINDEXED,
-  title,
-  description,
-  tags,
-  contexts,
-  content=''
-);
-
-CREATE VIRTUAL TABLE IF NOT EXISTS projects_fts USING fts5(
-  id UNINDEXED,
-  title,
-  supportNotes,
-  tagIds,
-  areaTitle,
-  content=''
-);
-
-CREATE TRIGGER IF NOT EXISTS tasks_ai AFTER INSERT ON tasks BEGIN
-  INSERT INTO tasks_fts (id, title, description, tags, contexts)
-  VALUES (new.id, new.title, coalesce(new.description, ''), coalesce(new.tags, ''), coalesce(new.contexts, ''));
-END;
-
-CREATE TRIGGER IF NOT EXISTS tasks_ad AFTER DELETE ON tasks BEGIN
-  INSERT INTO tasks_fts (tasks_fts, id, title, description, tags, contexts)
-  VALUES ('delete', old.id, old.title, coalesce(old.description, ''), coalesce(old.tags, ''), coalesce(old.contexts, ''));
-END;
-
-CREATE TRIGGER IF NOT EXISTS tasks_au AFTER UPDATE ON tasks BEGIN
-  INSERT INTO tasks_fts (tasks_fts, id, title, description, tags, contexts)
-  VALUES ('delete', old.id, old.title, coalesce(old.description, ''), coalesce(old.tags, ''), coalesce(old.contexts, ''));
-  INSERT INTO tasks_fts (id, title, description, tags, contexts)
-  VALUES (new.id, new.title, coalesce(new.description, ''), coalesce(new.tags, ''), coalesce(new.contexts, ''));
-END;
-
-CREATE TRIGGER IF NOT EXISTS projects_ai AFTER INSERT ON projects BEGIN
-  INSERT INTO projects_fts (id, title, supportNotes, tagIds, areaTitle)
-  VALUES (new.id, new.title, coalesce(new.supportNotes, ''), coalesce(new.tagIds, ''), coalesce(new.areaTitle, ''));
-END;
-
-CREATE TRIGGER IF NOT EXISTS projects_ad AFTER DELETE ON projects BEGIN
-  INSERT INTO projects_fts (projects_fts, id, title, supportNotes, tagIds, areaTitle)
-  VALUES ('delete', old.id, old.title, coalesce(old.supportNotes, ''), coalesce(old.tagIds, ''), coalesce(old.areaTitle, ''));
-END;
-
-CREATE TRIGGER IF NOT EXISTS projects_au AFTER UPDATE ON projects BEGIN
-  INSERT INTO projects_fts (projects_fts, id, title, supportNotes, tagIds, areaTitle)
-  VALUES ('delete', old.id, old.title, coalesce(old.supportNotes, ''), coalesce(old.tagIds, ''), coalesce(old.areaTitle, ''));
-  INSERT INTO projects_fts (id, title, supportNotes, tagIds, areaTitle)
-  VALUES (new.id, new.title, coalesce(new.supportNotes, ''), coalesce(new.tagIds, ''), coalesce(new.areaTitle, ''));
-END;
-
-CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
-CREATE INDEX IF NOT EXISTS idx_tasks_projectId ON tasks(projectId);
-CREATE INDEX IF NOT EXISTS idx_tasks_deletedAt ON tasks(deletedAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_dueDate ON tasks(dueDate);
-CREATE INDEX IF NOT EXISTS idx_tasks_startTime ON tasks(startTime);
-CREATE INDEX IF NOT EXISTS idx_tasks_reviewAt ON tasks(reviewAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_createdAt ON tasks(createdAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_updatedAt ON tasks(updatedAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_status_deletedAt ON tasks(status, deletedAt);
-CREATE INDEX IF NOT EXISTS idx_tasks_project_status_deletedAt ON tasks(projectId, status, deletedAt);
-CREATE INDEX IF NOT EXISTS idx_projects_status ON projects(status);
-CREATE INDEX IF NOT EXISTS idx_projects_areaId ON projects(areaId);
 "#;
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -210,8 +103,18 @@ struct AppConfigToml {
     webdav_url: Option<String>,
     webdav_username: Option<String>,
     webdav_password: Option<String>,
+    webdav_ca_certificate_path: Option<String>,
+    webdav_pinned_sha256: Option<String>,
+    webdav_timeout_seconds: Option<String>,
+    webdav_connect_timeout_seconds: Option<String>,
+    webdav_max_retries: Option<String>,
     cloud_url: Option<String>,
     cloud_token: Option<String>,
+    cloud_ca_certificate_path: Option<String>,
+    cloud_pinned_sha256: Option<String>,
+    cloud_timeout_seconds: Option<String>,
+    cloud_connect_timeout_seconds: Option<String>,
+    cloud_max_retries: Option<String>,
     external_calendars: Option<String>,
     ai_key_openai: Option<String>,
     ai_key_anthropic: Option<String>,
@@ -232,18 +135,187 @@ struct LinuxDistroInfo {
     id_like: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct TaskQueryOptions {
     status: Option<String>,
     project_id: Option<String>,
-    exclude_statuses: Option<Vec<String>>,
+    exclude_statuses: Option<Vec<TaskStatus>>,
     include_deleted: Option<bool>,
     include_archived: Option<bool>,
+    area_id: Option<String>,
+    section_id: Option<String>,
+    /// Tasks must carry every listed tag.
+    tags: Option<Vec<String>>,
+    /// Tasks must carry every listed context.
+    contexts: Option<Vec<String>>,
+    priority: Option<TaskPriority>,
+    is_focused_today: Option<bool>,
+    /// Inclusive ISO date/time bounds, compared as strings like the rest of the app does.
+    due_from: Option<String>,
+    due_to: Option<String>,
+    start_from: Option<String>,
+    start_to: Option<String>,
+    /// Tasks whose reviewAt is set and falls at or before this time.
+    review_due_by: Option<String>,
+    completed_since: Option<String>,
+    sort_by: Option<TaskSortKey>,
+    sort_desc: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum TaskSortKey {
+    OrderNum,
+    DueDate,
+    CreatedAt,
+    Priority,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskPage {
+    tasks: Vec<Task>,
+    /// Number of tasks matching the filters, ignoring limit and offset.
+    total: i64,
+    /// Offset of the next page, or None once the last page has been returned.
+    next_offset: Option<i64>,
+}
+
+/// A searchable table and its FTS index. `columns` are the FTS text columns and `weights`
+/// the bm25 weights in FTS column order, starting with the unindexed id.
+#[derive(Clone, Copy)]
+struct SearchTarget {
+    source_table: &'static str,
+    fts_table: &'static str,
+    alias: &'static str,
+    /// Source column shown as the result title when there is no highlight.
+    title_column: &'static str,
+    columns: &'static [&'static str],
+    weights: &'static str,
+    /// Whether the source table has a `deletedAt` column.
+    soft_delete: bool,
+}
+
+const TASK_SEARCH: SearchTarget = SearchTarget {
+    source_table: "tasks",
+    fts_table: "tasks_fts",
+    alias: "t",
+    title_column: "title",
+    columns: &["title", "description", "tags", "contexts", "checklist", "attachments"],
+    weights: "0.0, 10.0, 2.0, 4.0, 4.0, 3.0, 2.0",
+    soft_delete: true,
+};
+
+const PROJECT_SEARCH: SearchTarget = SearchTarget {
+    source_table: "projects",
+    fts_table: "projects_fts",
+    alias: "p",
+    title_column: "title",
+    columns: &["title", "supportNotes", "tagIds", "areaTitle", "attachments"],
+    weights: "0.0, 10.0, 2.0, 4.0, 3.0, 2.0",
+    soft_delete: true,
+};
+
+const SECTION_SEARCH: SearchTarget = SearchTarget {
+    source_table: "sections",
+    fts_table: "sections_fts",
+    alias: "s",
+    title_column: "title",
+    columns: &["title", "description"],
+    weights: "0.0, 10.0, 2.0",
+    soft_delete: true,
+};
+
+const AREA_SEARCH: SearchTarget = SearchTarget {
+    source_table: "areas",
+    fts_table: "areas_fts",
+    alias: "a",
+    title_column: "name",
+    columns: &["name"],
+    weights: "0.0, 10.0",
+    soft_delete: true,
+};
+
+/// Extra SQL conditions for one side of a search, with their parameters in placeholder order.
+#[derive(Default)]
+struct SearchFilters {
+    clauses: Vec<String>,
+    params: Vec<Box<dyn ToSql>>,
+}
+
+impl SearchFilters {
+    fn push(&mut self, clause: &str, mut params: Vec<Box<dyn ToSql>>) {
+        self.clauses.push(clause.to_string());
+        self.params.append(&mut params);
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchOptions {
+    /// Maximum results per kind; defaults to `DEFAULT_SEARCH_LIMIT`.
+    limit: Option<i64>,
+    status: Option<TaskStatus>,
+    project_id: Option<String>,
+    area_id: Option<String>,
+    /// Markers wrapped around matched terms. The text between them is not HTML-escaped.
+    highlight_start: Option<String>,
+    highlight_end: Option<String>,
+}
+
+/// Results are grouped by entity type and ordered best match first. Each `*_matches` list lines
+/// up with its entity list index for index.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResults {
+    tasks: Vec<Task>,
+    projects: Vec<Project>,
+    sections: Vec<Section>,
+    areas: Vec<Area>,
+    task_matches: Vec<SearchMatch>,
+    project_matches: Vec<SearchMatch>,
+    section_matches: Vec<SearchMatch>,
+    area_matches: Vec<SearchMatch>,
+    /// Set instead of failing the command when the query can't be parsed, so the search box can
+    /// point at the offending token while the user is still typing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_error: Option<QueryError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchMatch {
+    id: String,
+    score: f64,
+    title_highlight: String,
+    snippet: String,
 }
 
 struct QuickAddPending(AtomicBool);
 
+#[derive(Default)]
+struct SqliteState(Mutex<Option<Connection>>);
+
+/// Writes not yet reflected in the JSON mirror. See `schedule_json_mirror`.
+#[derive(Default)]
+struct MirrorState {
+    /// Bumped by every write to the database.
+    generation: AtomicU64,
+    /// The generation the mirror was last written at.
+    written: AtomicU64,
+    writer_running: AtomicBool,
+    /// Held while the mirror file is being replaced.
+    writing: Mutex<()>,
+}
+
+/// The `MirrorState` generation the last automatic backup covered, or None before the first
+/// one this session. See `backup_if_due`.
+#[derive(Default)]
+struct BackupState(Mutex<Option<u64>>);
+
 struct AudioRecorderState(Mutex<Option<AudioRecorderHandle>>);
 
 #[derive(Clone, Debug)]
@@ -616,207 +688,48 @@ fn get_db_path(app: &tauri::AppHandle) -> PathBuf {
     get_data_dir(app).join(DB_FILE_NAME)
 }
 
+fn get_backup_dir(app: &tauri::AppHandle) -> PathBuf {
+    get_data_dir(app).join(backup::BACKUP_DIR_NAME)
+}
+
 fn open_sqlite(app: &tauri::AppHandle) -> Result<Connection, String> {
     let db_path = get_db_path(app);
     if let Some(parent) = db_path.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    conn.execute_batch(SQLITE_SCHEMA).map_err(|e| e.to_string())?;
-    ensure_tasks_purged_at_column(&conn)?;
-    ensure_tasks_order_column(&conn)?;
-    ensure_tasks_area_column(&conn)?;
-    ensure_tasks_section_column(&conn)?;
-    ensure_projects_order_column(&conn)?;
-    ensure_projects_area_order_index(&conn)?;
-    ensure_fts_triggers(&conn)?;
-    ensure_fts_populated(&conn, false)?;
+    let keys = if encryption::is_encrypted_database(&db_path) {
+        storage_keys(app)?
+    } else {
+        Vec::new()
+    };
+    let (mut conn, unlocked_with) = encryption::open(&db_path, &keys)?;
+    if let Some(index) = unlocked_with.filter(|index| *index > 0) {
+        // A key change was interrupted after the database was converted; finish it.
+        set_keyring_secret(app, KEYRING_STORAGE_KEY, Some(keys[index].to_hex()))?;
+        set_keyring_secret(app, KEYRING_STORAGE_KEY_PENDING, None)?;
+    }
+    conn.busy_timeout(SQLITE_BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+    conn.execute_batch(SQLITE_PRAGMAS).map_err(|e| e.to_string())?;
+    migrations::migrate(&mut conn)?;
+    fts::ensure_populated(&conn, false)?;
     Ok(conn)
 }
 
-fn ensure_tasks_purged_at_column(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(tasks)")
-        .map_err(|e| e.to_string())?;
-    let columns = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    for col in columns {
-        if col.map_err(|e| e.to_string())? == "purgedAt" {
-            return Ok(());
-        }
-    }
-    conn.execute("ALTER TABLE tasks ADD COLUMN purgedAt TEXT", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-fn ensure_tasks_order_column(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(tasks)")
-        .map_err(|e| e.to_string())?;
-    let columns = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    for col in columns {
-        if col.map_err(|e| e.to_string())? == "orderNum" {
-            return Ok(());
-        }
-    }
-    conn.execute("ALTER TABLE tasks ADD COLUMN orderNum INTEGER", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-fn ensure_tasks_area_column(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(tasks)")
-        .map_err(|e| e.to_string())?;
-    let columns = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    let mut has_area = false;
-    for col in columns {
-        if col.map_err(|e| e.to_string())? == "areaId" {
-            has_area = true;
-            break;
-        }
-    }
-    if !has_area {
-        conn.execute("ALTER TABLE tasks ADD COLUMN areaId TEXT", [])
-            .map_err(|e| e.to_string())?;
-    }
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_tasks_area_id ON tasks(areaId)", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-fn ensure_tasks_section_column(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(tasks)")
-        .map_err(|e| e.to_string())?;
-    let columns = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    let mut has_section = false;
-    for col in columns {
-        if col.map_err(|e| e.to_string())? == "sectionId" {
-            has_section = true;
-            break;
-        }
-    }
-    if !has_section {
-        conn.execute("ALTER TABLE tasks ADD COLUMN sectionId TEXT", [])
-            .map_err(|e| e.to_string())?;
-    }
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_tasks_section_id ON tasks(sectionId)", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-fn ensure_projects_order_column(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(projects)")
-        .map_err(|e| e.to_string())?;
-    let columns = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    for col in columns {
-        if col.map_err(|e| e.to_string())? == "orderNum" {
-            return Ok(());
-        }
-    }
-    conn.execute("ALTER TABLE projects ADD COLUMN orderNum INTEGER", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-fn ensure_projects_area_order_index(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(projects)")
-        .map_err(|e| e.to_string())?;
-    let columns = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    let mut has_order = false;
-    for col in columns {
-        if col.map_err(|e| e.to_string())? == "orderNum" {
-            has_order = true;
-            break;
-        }
-    }
-    if has_order {
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_projects_area_order ON projects(areaId, orderNum)",
-            [],
-        )
-        .map_err(|e| e.to_string())?;
-    }
-    Ok(())
-}
-
-fn ensure_fts_triggers(conn: &Connection) -> Result<(), String> {
-    let has_v2: Option<i64> = conn
-        .query_row(
-            "SELECT version FROM schema_migrations WHERE version = 2 LIMIT 1",
-            [],
-            |row| row.get(0),
-        )
-        .optional()
-        .map_err(|e| e.to_string())?;
-    if has_v2.is_some() {
-        return Ok(());
-    }
-
-    conn.execute("DROP TRIGGER IF EXISTS tasks_ad", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DROP TRIGGER IF EXISTS tasks_au", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DROP TRIGGER IF EXISTS projects_ad", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DROP TRIGGER IF EXISTS projects_au", [])
-        .map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "CREATE TRIGGER tasks_ad AFTER DELETE ON tasks BEGIN
-          INSERT INTO tasks_fts (tasks_fts, id, title, description, tags, contexts)
-          VALUES ('delete', old.id, old.title, coalesce(old.description, ''), coalesce(old.tags, ''), coalesce(old.contexts, ''));
-        END",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
-    conn.execute(
-        "CREATE TRIGGER tasks_au AFTER UPDATE ON tasks BEGIN
-          INSERT INTO tasks_fts (tasks_fts, id, title, description, tags, contexts)
-          VALUES ('delete', old.id, old.title, coalesce(old.description, ''), coalesce(old.tags, ''), coalesce(old.contexts, ''));
-          INSERT INTO tasks_fts (id, title, description, tags, contexts)
-          VALUES (new.id, new.title, coalesce(new.description, ''), coalesce(new.tags, ''), coalesce(new.contexts, ''));
-        END",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
-    conn.execute(
-        "CREATE TRIGGER projects_ad AFTER DELETE ON projects BEGIN
-          INSERT INTO projects_fts (projects_fts, id, title, supportNotes, tagIds, areaTitle)
-          VALUES ('delete', old.id, old.title, coalesce(old.supportNotes, ''), coalesce(old.tagIds, ''), coalesce(old.areaTitle, ''));
-        END",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
-    conn.execute(
-        "CREATE TRIGGER projects_au AFTER UPDATE ON projects BEGIN
-          INSERT INTO projects_fts (projects_fts, id, title, supportNotes, tagIds, areaTitle)
-          VALUES ('delete', old.id, old.title, coalesce(old.supportNotes, ''), coalesce(old.tagIds, ''), coalesce(old.areaTitle, ''));
-          INSERT INTO projects_fts (id, title, supportNotes, tagIds, areaTitle)
-          VALUES (new.id, new.title, coalesce(new.supportNotes, ''), coalesce(new.tagIds, ''), coalesce(new.areaTitle, ''));
-        END",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
-
-    conn.execute("INSERT OR IGNORE INTO schema_migrations (version) VALUES (2)", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
+/// Runs `f` against the shared connection, opening and migrating it on first use. Access is
+/// serialized so commands never interleave statements or transactions; commands that call it run
+/// on a blocking thread, and slow file I/O such as the JSON mirror and backups happens after the
+/// lock is released.
+fn with_sqlite<T, F>(app: &tauri::AppHandle, f: F) -> Result<T, String>
+where
+    F: FnOnce(&mut Connection) -> Result<T, String>,
+{
+    let state = app.state::<SqliteState>();
+    let mut guard = state.inner().0.lock().map_err(|_| "SQLite lock poisoned".to_string())?;
+    if guard.is_none() {
+        *guard = Some(open_sqlite(app)?);
+    }
+    let conn = guard.as_mut().ok_or_else(|| "SQLite connection unavailable".to_string())?;
+    f(conn)
 }
 
 fn sqlite_has_any_data(conn: &Connection) -> Result<bool, String> {
@@ -835,71 +748,12 @@ fn sqlite_has_any_data(conn: &Connection) -> Result<bool, String> {
     Ok(task_count > 0 || project_count > 0 || area_count > 0 || settings_count > 0)
 }
 
-fn ensure_fts_populated(conn: &Connection, force_rebuild: bool) -> Result<(), String> {
-    let tasks_fts_count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM tasks_fts", [], |row| row.get(0))
-        .unwrap_or(0);
-    let missing_tasks: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM tasks WHERE id NOT IN (SELECT id FROM tasks_fts)",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-    let extra_tasks: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM tasks_fts WHERE id NOT IN (SELECT id FROM tasks)",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-    if force_rebuild || tasks_fts_count == 0 || missing_tasks > 0 || extra_tasks > 0 {
-        conn.execute("INSERT INTO tasks_fts(tasks_fts) VALUES('delete-all')", [])
-            .map_err(|e| e.to_string())?;
-        conn.execute(
-            "INSERT INTO tasks_fts (id, title, description, tags, contexts)
-             SELECT id, title, coalesce(description, ''), coalesce(tags, ''), coalesce(contexts, '') FROM tasks",
-            [],
-        )
-        .map_err(|e| e.to_string())?;
-    }
-
-    let projects_fts_count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM projects_fts", [], |row| row.get(0))
-        .unwrap_or(0);
-    let missing_projects: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM projects WHERE id NOT IN (SELECT id FROM projects_fts)",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-    let extra_projects: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM projects_fts WHERE id NOT IN (SELECT id FROM projects)",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-    if force_rebuild || projects_fts_count == 0 || missing_projects > 0 || extra_projects > 0 {
-        conn.execute("INSERT INTO projects_fts(projects_fts) VALUES('delete-all')", [])
-            .map_err(|e| e.to_string())?;
-        conn.execute(
-            "INSERT INTO projects_fts (id, title, supportNotes, tagIds, areaTitle)
-             SELECT id, title, coalesce(supportNotes, ''), coalesce(tagIds, ''), coalesce(areaTitle, '') FROM projects",
-            [],
-        )
-        .map_err(|e| e.to_string())?;
-    }
-    Ok(())
-}
-
 fn json_str(value: Option<&Value>) -> Option<String> {
     value.and_then(|v| serde_json::to_string(v).ok())
 }
 
-fn json_str_or_default(value: Option<&Value>, default: &str) -> String {
-    json_str(value).unwrap_or_else(|| default.to_string())
+fn json_list_str(values: &[String]) -> Result<String, String> {
+    serde_json::to_string(values).map_err(|e| e.to_string())
 }
 
 fn parse_json_value(raw: Option<String>) -> Value {
@@ -911,173 +765,251 @@ fn parse_json_value(raw: Option<String>) -> Value {
     Value::Null
 }
 
-fn parse_json_array(raw: Option<String>) -> Value {
+fn parse_json_column(raw: Option<String>) -> Option<Value> {
     match parse_json_value(raw) {
-        Value::Array(arr) => Value::Array(arr),
-        _ => Value::Array(Vec::new()),
+        Value::Null => None,
+        value => Some(value),
     }
 }
 
-fn build_fts_query(input: &str) -> Option<String> {
-    let mut cleaned = String::new();
-    for ch in input.chars() {
-        if ch.is_alphanumeric() || ch == '#' || ch == '@' {
-            cleaned.push(ch);
-        } else {
-            cleaned.push(' ');
-        }
-    }
-    let tokens: Vec<String> = cleaned
-        .split_whitespace()
-        .filter(|t| !t.is_empty())
-        .map(|t| format!("{}*", t))
-        .collect();
-    if tokens.is_empty() {
+/// Stores an entity's unknown fields, or NULL when there are none.
+fn extra_str(extra: &Map<String, Value>) -> Option<String> {
+    if extra.is_empty() {
         None
     } else {
-        Some(tokens.join(" "))
+        serde_json::to_string(extra).ok()
     }
 }
 
-fn row_to_task_value(row: &rusqlite::Row<'_>) -> Result<Value, rusqlite::Error> {
-    let mut map = serde_json::Map::new();
-    map.insert("id".to_string(), Value::String(row.get::<_, String>("id")?));
-    map.insert("title".to_string(), Value::String(row.get::<_, String>("title")?));
-    map.insert("status".to_string(), Value::String(row.get::<_, String>("status")?));
-    if let Ok(val) = row.get::<_, Option<String>>("priority") {
-        if let Some(v) = val { map.insert("priority".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("taskMode") {
-        if let Some(v) = val { map.insert("taskMode".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("startTime") {
-        if let Some(v) = val { map.insert("startTime".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("dueDate") {
-        if let Some(v) = val { map.insert("dueDate".to_string(), Value::String(v)); }
-    }
-    let recurrence_raw: Option<String> = row.get("recurrence")?;
-    let recurrence_val = parse_json_value(recurrence_raw);
-    if !recurrence_val.is_null() {
-        map.insert("recurrence".to_string(), recurrence_val);
-    }
-    if let Ok(val) = row.get::<_, Option<i64>>("pushCount") {
-        if let Some(v) = val { map.insert("pushCount".to_string(), Value::Number(v.into())); }
-    }
-    let tags_raw: Option<String> = row.get("tags")?;
-    map.insert("tags".to_string(), parse_json_array(tags_raw));
-    let contexts_raw: Option<String> = row.get("contexts")?;
-    map.insert("contexts".to_string(), parse_json_array(contexts_raw));
-    let checklist_raw: Option<String> = row.get("checklist")?;
-    let checklist_val = parse_json_value(checklist_raw);
-    if !checklist_val.is_null() { map.insert("checklist".to_string(), checklist_val); }
-    if let Ok(val) = row.get::<_, Option<String>>("description") {
-        if let Some(v) = val { map.insert("description".to_string(), Value::String(v)); }
-    }
-    let attachments_raw: Option<String> = row.get("attachments")?;
-    let attachments_val = parse_json_value(attachments_raw);
-    if !attachments_val.is_null() { map.insert("attachments".to_string(), attachments_val); }
-    if let Ok(val) = row.get::<_, Option<String>>("location") {
-        if let Some(v) = val { map.insert("location".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("projectId") {
-        if let Some(v) = val { map.insert("projectId".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("sectionId") {
-        if let Some(v) = val { map.insert("sectionId".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("areaId") {
-        if let Some(v) = val { map.insert("areaId".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<i64>>("orderNum") {
-        if let Some(v) = val { map.insert("orderNum".to_string(), Value::Number(v.into())); }
-    }
-    if let Ok(val) = row.get::<_, i64>("isFocusedToday") {
-        if val != 0 { map.insert("isFocusedToday".to_string(), Value::Bool(true)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("timeEstimate") {
-        if let Some(v) = val { map.insert("timeEstimate".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("reviewAt") {
-        if let Some(v) = val { map.insert("reviewAt".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("completedAt") {
-        if let Some(v) = val { map.insert("completedAt".to_string(), Value::String(v)); }
-    }
-    map.insert("createdAt".to_string(), Value::String(row.get::<_, String>("createdAt")?));
-    map.insert("updatedAt".to_string(), Value::String(row.get::<_, String>("updatedAt")?));
-    if let Ok(val) = row.get::<_, Option<String>>("deletedAt") {
-        if let Some(v) = val { map.insert("deletedAt".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("purgedAt") {
-        if let Some(v) = val { map.insert("purgedAt".to_string(), Value::String(v)); }
+fn parse_extra(raw: Option<String>) -> Map<String, Value> {
+    match parse_json_value(raw) {
+        Value::Object(map) => map,
+        _ => Map::new(),
     }
-    Ok(Value::Object(map))
 }
 
-fn row_to_project_value(row: &rusqlite::Row<'_>) -> Result<Value, rusqlite::Error> {
-    let mut map = serde_json::Map::new();
-    map.insert("id".to_string(), Value::String(row.get::<_, String>("id")?));
-    map.insert("title".to_string(), Value::String(row.get::<_, String>("title")?));
-    map.insert("status".to_string(), Value::String(row.get::<_, String>("status")?));
-    map.insert("color".to_string(), Value::String(row.get::<_, String>("color")?));
-    if let Ok(val) = row.get::<_, Option<i64>>("orderNum") {
-        if let Some(v) = val { map.insert("order".to_string(), Value::Number(v.into())); }
-    }
-    let tag_ids_raw: Option<String> = row.get("tagIds")?;
-    map.insert("tagIds".to_string(), parse_json_array(tag_ids_raw));
-    if let Ok(val) = row.get::<_, i64>("isSequential") {
-        if val != 0 { map.insert("isSequential".to_string(), Value::Bool(true)); }
-    }
-    if let Ok(val) = row.get::<_, i64>("isFocused") {
-        if val != 0 { map.insert("isFocused".to_string(), Value::Bool(true)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("supportNotes") {
-        if let Some(v) = val { map.insert("supportNotes".to_string(), Value::String(v)); }
-    }
-    let attachments_raw: Option<String> = row.get("attachments")?;
-    let attachments_val = parse_json_value(attachments_raw);
-    if !attachments_val.is_null() { map.insert("attachments".to_string(), attachments_val); }
-    if let Ok(val) = row.get::<_, Option<String>>("reviewAt") {
-        if let Some(v) = val { map.insert("reviewAt".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("areaId") {
-        if let Some(v) = val { map.insert("areaId".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<String>>("areaTitle") {
-        if let Some(v) = val { map.insert("areaTitle".to_string(), Value::String(v)); }
-    }
-    map.insert("createdAt".to_string(), Value::String(row.get::<_, String>("createdAt")?));
-    map.insert("updatedAt".to_string(), Value::String(row.get::<_, String>("updatedAt")?));
-    if let Ok(val) = row.get::<_, Option<String>>("deletedAt") {
-        if let Some(v) = val { map.insert("deletedAt".to_string(), Value::String(v)); }
-    }
-    Ok(Value::Object(map))
+fn parse_json_string_list(raw: Option<String>) -> Vec<String> {
+    raw.and_then(|text| serde_json::from_str::<Vec<String>>(&text).ok())
+        .unwrap_or_default()
 }
 
-fn row_to_section_value(row: &rusqlite::Row<'_>) -> Result<Value, rusqlite::Error> {
-    let mut map = serde_json::Map::new();
-    map.insert("id".to_string(), Value::String(row.get::<_, String>("id")?));
-    map.insert("projectId".to_string(), Value::String(row.get::<_, String>("projectId")?));
-    map.insert("title".to_string(), Value::String(row.get::<_, String>("title")?));
-    if let Ok(val) = row.get::<_, Option<String>>("description") {
-        if let Some(v) = val { map.insert("description".to_string(), Value::String(v)); }
-    }
-    if let Ok(val) = row.get::<_, Option<i64>>("orderNum") {
-        if let Some(v) = val { map.insert("order".to_string(), Value::Number(v.into())); }
-    }
-    if let Ok(val) = row.get::<_, i64>("isCollapsed") {
-        if val != 0 { map.insert("isCollapsed".to_string(), Value::Bool(true)); }
-    }
-    map.insert("createdAt".to_string(), Value::String(row.get::<_, String>("createdAt")?));
-    map.insert("updatedAt".to_string(), Value::String(row.get::<_, String>("updatedAt")?));
-    if let Ok(val) = row.get::<_, Option<String>>("deletedAt") {
-        if let Some(v) = val { map.insert("deletedAt".to_string(), Value::String(v)); }
-    }
-    Ok(Value::Object(map))
+fn row_to_task(row: &rusqlite::Row<'_>) -> Result<Task, rusqlite::Error> {
+    Ok(Task {
+        id: row.get("id")?,
+        title: row.get("title")?,
+        status: row.get("status")?,
+        priority: row.get("priority")?,
+        task_mode: row.get("taskMode")?,
+        start_time: row.get("startTime")?,
+        due_date: row.get("dueDate")?,
+        recurrence: parse_json_column(row.get("recurrence")?),
+        push_count: row.get("pushCount")?,
+        tags: parse_json_string_list(row.get("tags")?),
+        contexts: parse_json_string_list(row.get("contexts")?),
+        checklist: parse_json_column(row.get("checklist")?),
+        description: row.get("description")?,
+        attachments: parse_json_column(row.get("attachments")?),
+        location: row.get("location")?,
+        project_id: row.get("projectId")?,
+        section_id: row.get("sectionId")?,
+        area_id: row.get("areaId")?,
+        order_num: row.get("orderNum")?,
+        is_focused_today: row.get::<_, Option<i64>>("isFocusedToday")?.unwrap_or(0) != 0,
+        time_estimate: row.get("timeEstimate")?,
+        review_at: row.get("reviewAt")?,
+        completed_at: row.get("completedAt")?,
+        created_at: row.get("createdAt")?,
+        updated_at: row.get("updatedAt")?,
+        deleted_at: row.get("deletedAt")?,
+        purged_at: row.get("purgedAt")?,
+        extra: parse_extra(row.get("extra")?),
+    })
+}
+
+fn row_to_project(row: &rusqlite::Row<'_>) -> Result<Project, rusqlite::Error> {
+    Ok(Project {
+        id: row.get("id")?,
+        title: row.get("title")?,
+        status: row.get("status")?,
+        color: row.get("color")?,
+        order: row.get("orderNum")?,
+        tag_ids: parse_json_string_list(row.get("tagIds")?),
+        is_sequential: row.get::<_, Option<i64>>("isSequential")?.unwrap_or(0) != 0,
+        is_focused: row.get::<_, Option<i64>>("isFocused")?.unwrap_or(0) != 0,
+        support_notes: row.get("supportNotes")?,
+        attachments: parse_json_column(row.get("attachments")?),
+        review_at: row.get("reviewAt")?,
+        area_id: row.get("areaId")?,
+        area_title: row.get("areaTitle")?,
+        created_at: row.get("createdAt")?,
+        updated_at: row.get("updatedAt")?,
+        deleted_at: row.get("deletedAt")?,
+        purged_at: row.get("purgedAt")?,
+        extra: parse_extra(row.get("extra")?),
+    })
+}
+
+fn row_to_section(row: &rusqlite::Row<'_>) -> Result<Section, rusqlite::Error> {
+    Ok(Section {
+        id: row.get("id")?,
+        project_id: row.get("projectId")?,
+        title: row.get("title")?,
+        description: row.get("description")?,
+        order: row.get("orderNum")?,
+        is_collapsed: row.get::<_, Option<i64>>("isCollapsed")?.unwrap_or(0) != 0,
+        created_at: row.get("createdAt")?,
+        updated_at: row.get("updatedAt")?,
+        deleted_at: row.get("deletedAt")?,
+        purged_at: row.get("purgedAt")?,
+        extra: parse_extra(row.get("extra")?),
+    })
+}
+
+fn row_to_area(row: &rusqlite::Row<'_>) -> Result<Area, rusqlite::Error> {
+    Ok(Area {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        color: row.get("color")?,
+        icon: row.get("icon")?,
+        order: row.get("orderNum")?,
+        is_archived: row.get::<_, Option<i64>>("isArchived")?.unwrap_or(0) != 0,
+        created_at: row.get("createdAt")?,
+        updated_at: row.get("updatedAt")?,
+        deleted_at: row.get("deletedAt")?,
+        extra: parse_extra(row.get("extra")?),
+    })
+}
+
+// Purged rows are tombstones: a stale copy arriving from the webview or a sync must not bring
+// their content back, so the upserts leave them untouched.
+const TASK_UPSERT_SQL: &str = "INSERT INTO tasks (id, title, status, priority, taskMode, startTime, dueDate, recurrence, pushCount, tags, contexts, checklist, description, attachments, location, projectId, sectionId, areaId, orderNum, isFocusedToday, timeEstimate, reviewAt, completedAt, createdAt, updatedAt, deletedAt, purgedAt, extra) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)
+ ON CONFLICT(id) DO UPDATE SET title = excluded.title, status = excluded.status, priority = excluded.priority, taskMode = excluded.taskMode, startTime = excluded.startTime, dueDate = excluded.dueDate, recurrence = excluded.recurrence, pushCount = excluded.pushCount, tags = excluded.tags, contexts = excluded.contexts, checklist = excluded.checklist, description = excluded.description, attachments = excluded.attachments, location = excluded.location, projectId = excluded.projectId, sectionId = excluded.sectionId, areaId = excluded.areaId, orderNum = excluded.orderNum, isFocusedToday = excluded.isFocusedToday, timeEstimate = excluded.timeEstimate, reviewAt = excluded.reviewAt, completedAt = excluded.completedAt, createdAt = excluded.createdAt, updatedAt = excluded.updatedAt, deletedAt = excluded.deletedAt, purgedAt = excluded.purgedAt, extra = excluded.extra
+ WHERE tasks.purgedAt IS NULL";
+
+const PROJECT_UPSERT_SQL: &str = "INSERT INTO projects (id, title, status, color, orderNum, tagIds, isSequential, isFocused, supportNotes, attachments, reviewAt, areaId, areaTitle, createdAt, updatedAt, deletedAt, purgedAt, extra) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+ ON CONFLICT(id) DO UPDATE SET title = excluded.title, status = excluded.status, color = excluded.color, orderNum = excluded.orderNum, tagIds = excluded.tagIds, isSequential = excluded.isSequential, isFocused = excluded.isFocused, supportNotes = excluded.supportNotes, attachments = excluded.attachments, reviewAt = excluded.reviewAt, areaId = excluded.areaId, areaTitle = excluded.areaTitle, createdAt = excluded.createdAt, updatedAt = excluded.updatedAt, deletedAt = excluded.deletedAt, purgedAt = excluded.purgedAt, extra = excluded.extra
+ WHERE projects.purgedAt IS NULL";
+
+const AREA_UPSERT_SQL: &str = "INSERT INTO areas (id, name, color, icon, orderNum, isArchived, createdAt, updatedAt, deletedAt, extra) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+ ON CONFLICT(id) DO UPDATE SET name = excluded.name, color = excluded.color, icon = excluded.icon, orderNum = excluded.orderNum, isArchived = excluded.isArchived, createdAt = excluded.createdAt, updatedAt = excluded.updatedAt, deletedAt = excluded.deletedAt, extra = excluded.extra";
+
+const SECTION_UPSERT_SQL: &str = "INSERT INTO sections (id, projectId, title, description, orderNum, isCollapsed, createdAt, updatedAt, deletedAt, purgedAt, extra) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+ ON CONFLICT(id) DO UPDATE SET projectId = excluded.projectId, title = excluded.title, description = excluded.description, orderNum = excluded.orderNum, isCollapsed = excluded.isCollapsed, createdAt = excluded.createdAt, updatedAt = excluded.updatedAt, deletedAt = excluded.deletedAt, purgedAt = excluded.purgedAt, extra = excluded.extra
+ WHERE sections.purgedAt IS NULL";
+
+
+fn upsert_task(conn: &Connection, task: &Task) -> Result<(), String> {
+    conn.execute(
+        TASK_UPSERT_SQL,
+        params![
+            task.id,
+            task.title,
+            task.status,
+            task.priority,
+            task.task_mode,
+            task.start_time,
+            task.due_date,
+            json_str(task.recurrence.as_ref()),
+            task.push_count,
+            json_list_str(&task.tags)?,
+            json_list_str(&task.contexts)?,
+            json_str(task.checklist.as_ref()),
+            task.description,
+            json_str(task.attachments.as_ref()),
+            task.location,
+            task.project_id,
+            task.section_id,
+            task.area_id,
+            task.order_num,
+            task.is_focused_today as i32,
+            task.time_estimate,
+            task.review_at,
+            task.completed_at,
+            task.created_at,
+            task.updated_at,
+            task.deleted_at,
+            task.purged_at,
+            extra_str(&task.extra),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn upsert_project(conn: &Connection, project: &Project) -> Result<(), String> {
+    conn.execute(
+        PROJECT_UPSERT_SQL,
+        params![
+            project.id,
+            project.title,
+            project.status,
+            project.color,
+            project.order,
+            json_list_str(&project.tag_ids)?,
+            project.is_sequential as i32,
+            project.is_focused as i32,
+            project.support_notes,
+            json_str(project.attachments.as_ref()),
+            project.review_at,
+            project.area_id,
+            project.area_title,
+            project.created_at,
+            project.updated_at,
+            project.deleted_at,
+            project.purged_at,
+            extra_str(&project.extra),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn upsert_area(conn: &Connection, area: &Area) -> Result<(), String> {
+    conn.execute(
+        AREA_UPSERT_SQL,
+        params![
+            area.id,
+            area.name,
+            area.color,
+            area.icon,
+            area.order,
+            area.is_archived as i32,
+            area.created_at,
+            area.updated_at,
+            area.deleted_at,
+            extra_str(&area.extra),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn upsert_section(conn: &Connection, section: &Section) -> Result<(), String> {
+    conn.execute(
+        SECTION_UPSERT_SQL,
+        params![
+            section.id,
+            section.project_id,
+            section.title,
+            section.description,
+            section.order,
+            section.is_collapsed as i32,
+            section.created_at,
+            section.updated_at,
+            section.deleted_at,
+            section.purged_at,
+            extra_str(&section.extra),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn settings_json(settings: &Settings) -> Result<String, String> {
+    serde_json::to_string(settings).map_err(|e| e.to_string())
 }
 
-fn migrate_json_to_sqlite(conn: &mut Connection, data: &Value) -> Result<(), String> {
+fn migrate_json_to_sqlite(conn: &mut Connection, data: &AppData) -> Result<(), String> {
     let tx = conn.transaction().map_err(|e| e.to_string())?;
     tx.execute("DELETE FROM tasks", []).map_err(|e| e.to_string())?;
     tx.execute("DELETE FROM projects", []).map_err(|e| e.to_string())?;
@@ -1085,116 +1017,22 @@ fn migrate_json_to_sqlite(conn: &mut Connection, data: &Value) -> Result<(), Str
     tx.execute("DELETE FROM sections", []).map_err(|e| e.to_string())?;
     tx.execute("DELETE FROM settings", []).map_err(|e| e.to_string())?;
 
-    let tasks = data.get("tasks").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-    for task in tasks {
-        let tags_json = json_str_or_default(task.get("tags"), "[]");
-        let contexts_json = json_str_or_default(task.get("contexts"), "[]");
-        let recurrence_json = json_str(task.get("recurrence"));
-        let checklist_json = json_str(task.get("checklist"));
-        let attachments_json = json_str(task.get("attachments"));
-        tx.execute(
-            "INSERT INTO tasks (id, title, status, priority, taskMode, startTime, dueDate, recurrence, pushCount, tags, contexts, checklist, description, attachments, location, projectId, sectionId, areaId, orderNum, isFocusedToday, timeEstimate, reviewAt, completedAt, createdAt, updatedAt, deletedAt, purgedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)",
-            params![
-                task.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
-                task.get("title").and_then(|v| v.as_str()).unwrap_or_default(),
-                task.get("status").and_then(|v| v.as_str()).unwrap_or("inbox"),
-                task.get("priority").and_then(|v| v.as_str()),
-                task.get("taskMode").and_then(|v| v.as_str()),
-                task.get("startTime").and_then(|v| v.as_str()),
-                task.get("dueDate").and_then(|v| v.as_str()),
-                recurrence_json,
-                task.get("pushCount").and_then(|v| v.as_i64()),
-                tags_json,
-                contexts_json,
-                checklist_json,
-                task.get("description").and_then(|v| v.as_str()),
-                attachments_json,
-                task.get("location").and_then(|v| v.as_str()),
-                task.get("projectId").and_then(|v| v.as_str()),
-                task.get("sectionId").and_then(|v| v.as_str()),
-                task.get("areaId").and_then(|v| v.as_str()),
-                task.get("orderNum").and_then(|v| v.as_i64()),
-                task.get("isFocusedToday").and_then(|v| v.as_bool()).unwrap_or(false) as i32,
-                task.get("timeEstimate").and_then(|v| v.as_str()),
-                task.get("reviewAt").and_then(|v| v.as_str()),
-                task.get("completedAt").and_then(|v| v.as_str()),
-                task.get("createdAt").and_then(|v| v.as_str()).unwrap_or_default(),
-                task.get("updatedAt").and_then(|v| v.as_str()).unwrap_or_default(),
-                task.get("deletedAt").and_then(|v| v.as_str()),
-                task.get("purgedAt").and_then(|v| v.as_str()),
-            ],
-        )
-        .map_err(|e| e.to_string())?;
+    for task in &data.tasks {
+        upsert_task(&tx, task)?;
     }
-
-    let projects = data.get("projects").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-    for project in projects {
-        let tag_ids_json = json_str_or_default(project.get("tagIds"), "[]");
-        let attachments_json = json_str(project.get("attachments"));
-        tx.execute(
-            "INSERT INTO projects (id, title, status, color, orderNum, tagIds, isSequential, isFocused, supportNotes, attachments, reviewAt, areaId, areaTitle, createdAt, updatedAt, deletedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
-            params![
-                project.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
-                project.get("title").and_then(|v| v.as_str()).unwrap_or_default(),
-                project.get("status").and_then(|v| v.as_str()).unwrap_or("active"),
-                project.get("color").and_then(|v| v.as_str()).unwrap_or("#6B7280"),
-                project.get("order").and_then(|v| v.as_i64()),
-                tag_ids_json,
-                project.get("isSequential").and_then(|v| v.as_bool()).unwrap_or(false) as i32,
-                project.get("isFocused").and_then(|v| v.as_bool()).unwrap_or(false) as i32,
-                project.get("supportNotes").and_then(|v| v.as_str()),
-                attachments_json,
-                project.get("reviewAt").and_then(|v| v.as_str()),
-                project.get("areaId").and_then(|v| v.as_str()),
-                project.get("areaTitle").and_then(|v| v.as_str()),
-                project.get("createdAt").and_then(|v| v.as_str()).unwrap_or_default(),
-                project.get("updatedAt").and_then(|v| v.as_str()).unwrap_or_default(),
-                project.get("deletedAt").and_then(|v| v.as_str()),
-            ],
-        )
-        .map_err(|e| e.to_string())?;
+    for project in &data.projects {
+        upsert_project(&tx, project)?;
     }
-
-    let areas = data.get("areas").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-    for area in areas {
-        tx.execute(
-            "INSERT INTO areas (id, name, color, icon, orderNum, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                area.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
-                area.get("name").and_then(|v| v.as_str()).unwrap_or_default(),
-                area.get("color").and_then(|v| v.as_str()),
-                area.get("icon").and_then(|v| v.as_str()),
-                area.get("order").and_then(|v| v.as_i64()).unwrap_or(0),
-                area.get("createdAt").and_then(|v| v.as_str()),
-                area.get("updatedAt").and_then(|v| v.as_str()),
-            ],
-        )
-        .map_err(|e| e.to_string())?;
+    for area in &data.areas {
+        upsert_area(&tx, area)?;
     }
-
-    let sections = data.get("sections").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-    for section in sections {
-        tx.execute(
-            "INSERT INTO sections (id, projectId, title, description, orderNum, isCollapsed, createdAt, updatedAt, deletedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![
-                section.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
-                section.get("projectId").and_then(|v| v.as_str()).unwrap_or_default(),
-                section.get("title").and_then(|v| v.as_str()).unwrap_or_default(),
-                section.get("description").and_then(|v| v.as_str()),
-                section.get("order").and_then(|v| v.as_i64()),
-                section.get("isCollapsed").and_then(|v| v.as_bool()).unwrap_or(false) as i32,
-                section.get("createdAt").and_then(|v| v.as_str()).unwrap_or_default(),
-                section.get("updatedAt").and_then(|v| v.as_str()).unwrap_or_default(),
-                section.get("deletedAt").and_then(|v| v.as_str()),
-            ],
-        )
-        .map_err(|e| e.to_string())?;
+    for section in &data.sections {
+        upsert_section(&tx, section)?;
     }
 
-    let settings_json = json_str(data.get("settings"));
     tx.execute(
         "INSERT INTO settings (id, data) VALUES (1, ?1)",
-        params![settings_json.unwrap_or_else(|| "{}".to_string())],
+        params![settings_json(&data.settings)?],
     )
     .map_err(|e| e.to_string())?;
 
@@ -1202,25 +1040,355 @@ fn migrate_json_to_sqlite(conn: &mut Connection, data: &Value) -> Result<(), Str
     Ok(())
 }
 
-fn read_sqlite_data(conn: &Connection) -> Result<Value, String> {
-    let mut tasks_stmt = conn
-        .prepare("SELECT * FROM tasks")
-        .map_err(|e| e.to_string())?;
-    let task_rows = tasks_stmt
-        .query_map([], |row| row_to_task_value(row))
+/// Loads every row of an entity table, by id.
+fn load_rows<T: Entity>(
+    conn: &Connection,
+    table: &str,
+    from_row: fn(&rusqlite::Row<'_>) -> Result<T, rusqlite::Error>,
+) -> Result<HashMap<String, T>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT * FROM {}", table))
         .map_err(|e| e.to_string())?;
-    let mut tasks: Vec<Value> = Vec::new();
-    for row in task_rows {
-        tasks.push(row.map_err(|e| e.to_string())?);
+    let rows = stmt.query_map([], from_row).map_err(|e| e.to_string())?;
+    let mut stored = HashMap::new();
+    for row in rows {
+        let item = row.map_err(|e| e.to_string())?;
+        stored.insert(item.id().to_string(), item);
     }
+    Ok(stored)
+}
 
-    let mut projects_stmt = conn
+/// Upserts only the rows of `table` that are new or differ from the stored row and deletes rows
+/// that are no longer present. Whole rows are compared rather than `updatedAt`, since repairs and
+/// imports can change an entity without touching it.
+fn apply_entity_diff<T: Entity + PartialEq>(
+    conn: &Connection,
+    table: &str,
+    items: &[T],
+    from_row: fn(&rusqlite::Row<'_>) -> Result<T, rusqlite::Error>,
+    upsert: fn(&Connection, &T) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut existing = load_rows(conn, table, from_row)?;
+    for item in items {
+        if existing.remove(item.id()).as_ref() != Some(item) {
+            upsert(conn, item)?;
+        }
+    }
+    if !existing.is_empty() {
+        let mut delete_stmt = conn
+            .prepare(&format!("DELETE FROM {} WHERE id = ?1", table))
+            .map_err(|e| e.to_string())?;
+        for id in existing.keys() {
+            delete_stmt.execute([id]).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Persists `data` by writing only inserted, changed and removed entities, so FTS triggers fire
+/// for just those rows instead of the whole dataset.
+fn apply_data_diff(conn: &mut Connection, data: &AppData, label: &str) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = now_iso();
+    journal::begin(&tx, label, &now)?;
+    apply_entity_diff(&tx, "tasks", &data.tasks, row_to_task, upsert_task)?;
+    apply_entity_diff(&tx, "projects", &data.projects, row_to_project, upsert_project)?;
+    apply_entity_diff(&tx, "areas", &data.areas, row_to_area, upsert_area)?;
+    apply_entity_diff(&tx, "sections", &data.sections, row_to_section, upsert_section)?;
+
+    let settings_json = settings_json(&data.settings)?;
+    let stored_settings: Option<String> = tx
+        .query_row("SELECT data FROM settings WHERE id = 1", [], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if stored_settings.as_deref() != Some(settings_json.as_str()) {
+        tx.execute(
+            "INSERT INTO settings (id, data) VALUES (1, ?1) ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+            params![settings_json],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    journal::finish(&tx, &now)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn now_iso() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn load_entity<T>(
+    conn: &Connection,
+    table: &str,
+    id: &str,
+    from_row: fn(&rusqlite::Row<'_>) -> Result<T, rusqlite::Error>,
+) -> Result<Option<T>, String> {
+    conn.query_row(&format!("SELECT * FROM {} WHERE id = ?1", table), [id], from_row)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+fn load_task(conn: &Connection, id: &str) -> Result<Option<Task>, String> {
+    load_entity(conn, "tasks", id, row_to_task)
+}
+
+fn load_project(conn: &Connection, id: &str) -> Result<Option<Project>, String> {
+    load_entity(conn, "projects", id, row_to_project)
+}
+
+fn load_section(conn: &Connection, id: &str) -> Result<Option<Section>, String> {
+    load_entity(conn, "sections", id, row_to_section)
+}
+
+fn load_area(conn: &Connection, id: &str) -> Result<Option<Area>, String> {
+    load_entity(conn, "areas", id, row_to_area)
+}
+
+fn entity_object(value: Value, label: &str) -> Result<Map<String, Value>, String> {
+    match value {
+        Value::Object(map) => Ok(map),
+        _ => Err(format!("{} must be an object", label)),
+    }
+}
+
+/// Applies a partial update on top of `current` and re-parses the result, so a patch is held
+/// to the same field-level rules as a full entity. Keys set to `null` are removed.
+fn patch_entity<T>(current: &T, patch: Value, label: &str) -> Result<T, String>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    let mut target = entity_object(serde_json::to_value(current).map_err(|e| e.to_string())?, label)?;
+    let patch = entity_object(patch, &format!("{} patch", label))?;
+    for (key, value) in patch {
+        if key == "id" {
+            if value.as_str() != target.get("id").and_then(|v| v.as_str()) {
+                return Err(format!("{} id cannot be changed", label));
+            }
+            continue;
+        }
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            target.insert(key, value);
+        }
+    }
+    target.insert("updatedAt".to_string(), Value::String(now_iso()));
+    parse_entity(Value::Object(target), label)
+}
+
+/// Stamps `createdAt`/`updatedAt` on a new entity and parses it.
+fn new_entity<T>(value: Value, defaults: &[(&str, &str)], label: &str) -> Result<T, String>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut map = entity_object(value, label)?;
+    for (key, default) in defaults {
+        map.entry(*key).or_insert_with(|| Value::String(default.to_string()));
+    }
+    let now = now_iso();
+    map.entry("createdAt").or_insert_with(|| Value::String(now.clone()));
+    map.insert("updatedAt".to_string(), Value::String(now));
+    parse_entity(Value::Object(map), label)
+}
+
+fn require_non_empty(value: &str, field: &str, label: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{}.{} must not be empty", label, field));
+    }
+    Ok(())
+}
+
+fn ensure_live_row(conn: &Connection, table: &str, id: &str, label: &str) -> Result<(), String> {
+    let deleted_at: Option<Option<String>> = conn
+        .query_row(&format!("SELECT deletedAt FROM {} WHERE id = ?1", table), [id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match deleted_at {
+        Some(None) => Ok(()),
+        Some(Some(_)) => Err(format!("{} {} is deleted", label, id)),
+        None => Err(format!("{} {} not found", label, id)),
+    }
+}
+
+/// Checks the invariants serde cannot express. References are only re-checked when they change,
+/// so entities pointing at rows that were trashed since can still be edited.
+fn validate_task(conn: &Connection, task: &Task, previous: Option<&Task>) -> Result<(), String> {
+    require_non_empty(&task.id, "id", "task")?;
+    require_non_empty(&task.title, "title", "task")?;
+    let project_changed = previous.map_or(true, |prev| prev.project_id != task.project_id);
+    if let Some(project_id) = task.project_id.as_deref().filter(|_| project_changed) {
+        ensure_live_row(conn, "projects", project_id, "Project")?;
+    }
+    let section_changed = project_changed || previous.map_or(true, |prev| prev.section_id != task.section_id);
+    if let Some(section_id) = task.section_id.as_deref().filter(|_| section_changed) {
+        let section = load_section(conn, section_id)?
+            .ok_or_else(|| format!("Section {} not found", section_id))?;
+        if section.deleted_at.is_some() {
+            return Err(format!("Section {} is deleted", section_id));
+        }
+        if task.project_id.as_deref() != Some(section.project_id.as_str()) {
+            return Err(format!("Section {} does not belong to the task's project", section_id));
+        }
+    }
+    let area_changed = previous.map_or(true, |prev| prev.area_id != task.area_id);
+    if let Some(area_id) = task.area_id.as_deref().filter(|_| area_changed) {
+        ensure_live_row(conn, "areas", area_id, "Area")?;
+    }
+    Ok(())
+}
+
+fn validate_project(conn: &Connection, project: &Project, previous: Option<&Project>) -> Result<(), String> {
+    require_non_empty(&project.id, "id", "project")?;
+    require_non_empty(&project.title, "title", "project")?;
+    let area_changed = previous.map_or(true, |prev| prev.area_id != project.area_id);
+    if let Some(area_id) = project.area_id.as_deref().filter(|_| area_changed) {
+        ensure_live_row(conn, "areas", area_id, "Area")?;
+    }
+    Ok(())
+}
+
+fn validate_section(conn: &Connection, section: &Section, previous: Option<&Section>) -> Result<(), String> {
+    require_non_empty(&section.id, "id", "section")?;
+    require_non_empty(&section.title, "title", "section")?;
+    if previous.map_or(true, |prev| prev.project_id != section.project_id) {
+        ensure_live_row(conn, "projects", &section.project_id, "Project")?;
+    }
+    Ok(())
+}
+
+fn validate_area(area: &Area) -> Result<(), String> {
+    require_non_empty(&area.id, "id", "area")?;
+    require_non_empty(&area.name, "name", "area")
+}
+
+/// Runs `write` in a single transaction, journaled as one undoable operation named `label`, and
+/// schedules a refresh of the JSON mirror.
+fn with_write_transaction<T, F>(app: &tauri::AppHandle, label: &str, write: F) -> Result<T, String>
+where
+    F: FnOnce(&rusqlite::Transaction<'_>) -> Result<T, String>,
+{
+    ensure_data_file(app)?;
+    let stored = with_sqlite(app, |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let now = now_iso();
+        journal::begin(&tx, label, &now)?;
+        let stored = write(&tx)?;
+        journal::finish(&tx, &now)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(stored)
+    })?;
+    schedule_json_mirror(app);
+    Ok(stored)
+}
+
+fn create_task_in(conn: &Connection, task: Value) -> Result<Task, String> {
+    let task: Task = new_entity(task, &[("status", TaskStatus::Inbox.as_str())], "task")?;
+    if load_task(conn, &task.id)?.is_some() {
+        return Err(format!("Task {} already exists", task.id));
+    }
+    validate_task(conn, &task, None)?;
+    upsert_task(conn, &task)?;
+    load_task(conn, &task.id)?.ok_or_else(|| format!("Task {} not found", task.id))
+}
+
+fn update_task_in(conn: &Connection, id: &str, patch: Value) -> Result<Task, String> {
+    let current = load_task(conn, id)?.ok_or_else(|| format!("Task {} not found", id))?;
+    if current.purged_at.is_some() {
+        return Err(format!("Task {} has been purged", id));
+    }
+    let task = patch_entity(&current, patch, "task")?;
+    validate_task(conn, &task, Some(&current))?;
+    upsert_task(conn, &task)?;
+    load_task(conn, id)?.ok_or_else(|| format!("Task {} not found", id))
+}
+
+fn create_project_in(conn: &Connection, project: Value) -> Result<Project, String> {
+    let project: Project = new_entity(project, &[("status", ProjectStatus::Active.as_str())], "project")?;
+    if load_project(conn, &project.id)?.is_some() {
+        return Err(format!("Project {} already exists", project.id));
+    }
+    validate_project(conn, &project, None)?;
+    upsert_project(conn, &project)?;
+    load_project(conn, &project.id)?.ok_or_else(|| format!("Project {} not found", project.id))
+}
+
+fn update_project_in(conn: &Connection, id: &str, patch: Value) -> Result<Project, String> {
+    let current = load_project(conn, id)?.ok_or_else(|| format!("Project {} not found", id))?;
+    if current.purged_at.is_some() {
+        return Err(format!("Project {} has been purged", id));
+    }
+    let project = patch_entity(&current, patch, "project")?;
+    validate_project(conn, &project, Some(&current))?;
+    upsert_project(conn, &project)?;
+    load_project(conn, id)?.ok_or_else(|| format!("Project {} not found", id))
+}
+
+fn create_section_in(conn: &Connection, section: Value) -> Result<Section, String> {
+    let section: Section = new_entity(section, &[], "section")?;
+    if load_section(conn, &section.id)?.is_some() {
+        return Err(format!("Section {} already exists", section.id));
+    }
+    validate_section(conn, &section, None)?;
+    upsert_section(conn, &section)?;
+    load_section(conn, &section.id)?.ok_or_else(|| format!("Section {} not found", section.id))
+}
+
+fn update_section_in(conn: &Connection, id: &str, patch: Value) -> Result<Section, String> {
+    let current = load_section(conn, id)?.ok_or_else(|| format!("Section {} not found", id))?;
+    if current.purged_at.is_some() {
+        return Err(format!("Section {} has been purged", id));
+    }
+    let section = patch_entity(&current, patch, "section")?;
+    validate_section(conn, &section, Some(&current))?;
+    upsert_section(conn, &section)?;
+    load_section(conn, id)?.ok_or_else(|| format!("Section {} not found", id))
+}
+
+fn create_area_in(conn: &Connection, area: Value) -> Result<Area, String> {
+    let mut area = entity_object(area, "area")?;
+    if !area.contains_key("order") {
+        let next_order: i64 = conn
+            .query_row("SELECT coalesce(MAX(orderNum) + 1, 0) FROM areas", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        area.insert("order".to_string(), Value::Number(next_order.into()));
+    }
+    let area: Area = new_entity(Value::Object(area), &[], "area")?;
+    if load_area(conn, &area.id)?.is_some() {
+        return Err(format!("Area {} already exists", area.id));
+    }
+    validate_area(&area)?;
+    upsert_area(conn, &area)?;
+    load_area(conn, &area.id)?.ok_or_else(|| format!("Area {} not found", area.id))
+}
+
+fn update_area_in(conn: &Connection, id: &str, patch: Value) -> Result<Area, String> {
+    let current = load_area(conn, id)?.ok_or_else(|| format!("Area {} not found", id))?;
+    let area = patch_entity(&current, patch, "area")?;
+    validate_area(&area)?;
+    upsert_area(conn, &area)?;
+    load_area(conn, id)?.ok_or_else(|| format!("Area {} not found", id))
+}
+
+fn read_sqlite_data(conn: &Connection) -> Result<AppData, String> {
+    let mut tasks_stmt = conn
+        .prepare("SELECT * FROM tasks")
+        .map_err(|e| e.to_string())?;
+    let task_rows = tasks_stmt
+        .query_map([], row_to_task)
+        .map_err(|e| e.to_string())?;
+    let mut tasks: Vec<Task> = Vec::new();
+    for row in task_rows {
+        tasks.push(row.map_err(|e| e.to_string())?);
+    }
+
+    let mut projects_stmt = conn
         .prepare("SELECT * FROM projects")
         .map_err(|e| e.to_string())?;
     let project_rows = projects_stmt
-        .query_map([], |row| row_to_project_value(row))
+        .query_map([], row_to_project)
         .map_err(|e| e.to_string())?;
-    let mut projects: Vec<Value> = Vec::new();
+    let mut projects: Vec<Project> = Vec::new();
     for row in project_rows {
         projects.push(row.map_err(|e| e.to_string())?);
     }
@@ -1229,9 +1397,9 @@ fn read_sqlite_data(conn: &Connection) -> Result<Value, String> {
         .prepare("SELECT * FROM sections")
         .map_err(|e| e.to_string())?;
     let section_rows = sections_stmt
-        .query_map([], |row| row_to_section_value(row))
+        .query_map([], row_to_section)
         .map_err(|e| e.to_string())?;
-    let mut sections: Vec<Value> = Vec::new();
+    let mut sections: Vec<Section> = Vec::new();
     for row in section_rows {
         sections.push(row.map_err(|e| e.to_string())?);
     }
@@ -1240,27 +1408,9 @@ fn read_sqlite_data(conn: &Connection) -> Result<Value, String> {
         .prepare("SELECT * FROM areas")
         .map_err(|e| e.to_string())?;
     let area_rows = areas_stmt
-        .query_map([], |row| {
-            let mut map = serde_json::Map::new();
-            map.insert("id".to_string(), Value::String(row.get::<_, String>("id")?));
-            map.insert("name".to_string(), Value::String(row.get::<_, String>("name")?));
-            if let Ok(val) = row.get::<_, Option<String>>("color") {
-                if let Some(v) = val { map.insert("color".to_string(), Value::String(v)); }
-            }
-            if let Ok(val) = row.get::<_, Option<String>>("icon") {
-                if let Some(v) = val { map.insert("icon".to_string(), Value::String(v)); }
-            }
-            map.insert("order".to_string(), Value::Number((row.get::<_, i64>("orderNum")?).into()));
-            if let Ok(val) = row.get::<_, Option<String>>("createdAt") {
-                if let Some(v) = val { map.insert("createdAt".to_string(), Value::String(v)); }
-            }
-            if let Ok(val) = row.get::<_, Option<String>>("updatedAt") {
-                if let Some(v) = val { map.insert("updatedAt".to_string(), Value::String(v)); }
-            }
-            Ok(Value::Object(map))
-        })
+        .query_map([], row_to_area)
         .map_err(|e| e.to_string())?;
-    let mut areas: Vec<Value> = Vec::new();
+    let mut areas: Vec<Area> = Vec::new();
     for row in area_rows {
         areas.push(row.map_err(|e| e.to_string())?);
     }
@@ -1269,20 +1419,15 @@ fn read_sqlite_data(conn: &Connection) -> Result<Value, String> {
         .query_row("SELECT data FROM settings WHERE id = 1", [], |row| row.get(0))
         .optional()
         .map_err(|e| e.to_string())?;
-    let settings_val = parse_json_value(settings_raw).as_object().cloned().unwrap_or_default();
-
-    Ok(Value::Object(
-        serde_json::json!({
-            "tasks": tasks,
-            "projects": projects,
-            "sections": sections,
-            "areas": areas,
-            "settings": Value::Object(settings_val),
-        })
-        .as_object()
-        .unwrap()
-        .clone(),
-    ))
+    let settings = parse_json_value(settings_raw).as_object().cloned().unwrap_or_default();
+
+    Ok(AppData {
+        tasks,
+        projects,
+        sections,
+        areas,
+        settings: Settings(settings),
+    })
 }
 
 fn get_legacy_config_json_path(app: &tauri::AppHandle) -> PathBuf {
@@ -1354,10 +1499,30 @@ fn read_config_toml(path: &Path) -> AppConfigToml {
             config.webdav_username = parse_toml_string_value(value);
         } else if key == "webdav_password" {
             config.webdav_password = parse_toml_string_value(value);
+        } else if key == "webdav_ca_certificate_path" {
+            config.webdav_ca_certificate_path = parse_toml_string_value(value);
+        } else if key == "webdav_pinned_sha256" {
+            config.webdav_pinned_sha256 = parse_toml_string_value(value);
+        } else if key == "webdav_timeout_seconds" {
+            config.webdav_timeout_seconds = parse_toml_string_value(value);
+        } else if key == "webdav_connect_timeout_seconds" {
+            config.webdav_connect_timeout_seconds = parse_toml_string_value(value);
+        } else if key == "webdav_max_retries" {
+            config.webdav_max_retries = parse_toml_string_value(value);
         } else if key == "cloud_url" {
             config.cloud_url = parse_toml_string_value(value);
         } else if key == "cloud_token" {
             config.cloud_token = parse_toml_string_value(value);
+        } else if key == "cloud_ca_certificate_path" {
+            config.cloud_ca_certificate_path = parse_toml_string_value(value);
+        } else if key == "cloud_pinned_sha256" {
+            config.cloud_pinned_sha256 = parse_toml_string_value(value);
+        } else if key == "cloud_timeout_seconds" {
+            config.cloud_timeout_seconds = parse_toml_string_value(value);
+        } else if key == "cloud_connect_timeout_seconds" {
+            config.cloud_connect_timeout_seconds = parse_toml_string_value(value);
+        } else if key == "cloud_max_retries" {
+            config.cloud_max_retries = parse_toml_string_value(value);
         } else if key == "external_calendars" {
             config.external_calendars = parse_toml_string_value(value);
         } else if key == "ai_key_openai" {
@@ -1436,12 +1601,42 @@ fn write_config_toml_with_header(path: &Path, config: &AppConfigToml, header: &s
     if let Some(webdav_password) = &config.webdav_password {
         lines.push(format!("webdav_password = {}", serialize_toml_string_value(webdav_password)));
     }
+    if let Some(webdav_ca_certificate_path) = &config.webdav_ca_certificate_path {
+        lines.push(format!("webdav_ca_certificate_path = {}", serialize_toml_string_value(webdav_ca_certificate_path)));
+    }
+    if let Some(webdav_pinned_sha256) = &config.webdav_pinned_sha256 {
+        lines.push(format!("webdav_pinned_sha256 = {}", serialize_toml_string_value(webdav_pinned_sha256)));
+    }
+    if let Some(webdav_timeout_seconds) = &config.webdav_timeout_seconds {
+        lines.push(format!("webdav_timeout_seconds = {}", serialize_toml_string_value(webdav_timeout_seconds)));
+    }
+    if let Some(webdav_connect_timeout_seconds) = &config.webdav_connect_timeout_seconds {
+        lines.push(format!("webdav_connect_timeout_seconds = {}", serialize_toml_string_value(webdav_connect_timeout_seconds)));
+    }
+    if let Some(webdav_max_retries) = &config.webdav_max_retries {
+        lines.push(format!("webdav_max_retries = {}", serialize_toml_string_value(webdav_max_retries)));
+    }
     if let Some(cloud_url) = &config.cloud_url {
         lines.push(format!("cloud_url = {}", serialize_toml_string_value(cloud_url)));
     }
     if let Some(cloud_token) = &config.cloud_token {
         lines.push(format!("cloud_token = {}", serialize_toml_string_value(cloud_token)));
     }
+    if let Some(cloud_ca_certificate_path) = &config.cloud_ca_certificate_path {
+        lines.push(format!("cloud_ca_certificate_path = {}", serialize_toml_string_value(cloud_ca_certificate_path)));
+    }
+    if let Some(cloud_pinned_sha256) = &config.cloud_pinned_sha256 {
+        lines.push(format!("cloud_pinned_sha256 = {}", serialize_toml_string_value(cloud_pinned_sha256)));
+    }
+    if let Some(cloud_timeout_seconds) = &config.cloud_timeout_seconds {
+        lines.push(format!("cloud_timeout_seconds = {}", serialize_toml_string_value(cloud_timeout_seconds)));
+    }
+    if let Some(cloud_connect_timeout_seconds) = &config.cloud_connect_timeout_seconds {
+        lines.push(format!("cloud_connect_timeout_seconds = {}", serialize_toml_string_value(cloud_connect_timeout_seconds)));
+    }
+    if let Some(cloud_max_retries) = &config.cloud_max_retries {
+        lines.push(format!("cloud_max_retries = {}", serialize_toml_string_value(cloud_max_retries)));
+    }
     if let Some(external_calendars) = &config.external_calendars {
         lines.push(format!("external_calendars = {}", serialize_toml_string_value(external_calendars)));
     }
@@ -1474,12 +1669,42 @@ fn merge_config(base: &mut AppConfigToml, overrides: AppConfigToml) {
     if overrides.webdav_password.is_some() {
         base.webdav_password = overrides.webdav_password;
     }
+    if overrides.webdav_ca_certificate_path.is_some() {
+        base.webdav_ca_certificate_path = overrides.webdav_ca_certificate_path;
+    }
+    if overrides.webdav_pinned_sha256.is_some() {
+        base.webdav_pinned_sha256 = overrides.webdav_pinned_sha256;
+    }
+    if overrides.webdav_timeout_seconds.is_some() {
+        base.webdav_timeout_seconds = overrides.webdav_timeout_seconds;
+    }
+    if overrides.webdav_connect_timeout_seconds.is_some() {
+        base.webdav_connect_timeout_seconds = overrides.webdav_connect_timeout_seconds;
+    }
+    if overrides.webdav_max_retries.is_some() {
+        base.webdav_max_retries = overrides.webdav_max_retries;
+    }
     if overrides.cloud_url.is_some() {
         base.cloud_url = overrides.cloud_url;
     }
     if overrides.cloud_token.is_some() {
         base.cloud_token = overrides.cloud_token;
     }
+    if overrides.cloud_ca_certificate_path.is_some() {
+        base.cloud_ca_certificate_path = overrides.cloud_ca_certificate_path;
+    }
+    if overrides.cloud_pinned_sha256.is_some() {
+        base.cloud_pinned_sha256 = overrides.cloud_pinned_sha256;
+    }
+    if overrides.cloud_timeout_seconds.is_some() {
+        base.cloud_timeout_seconds = overrides.cloud_timeout_seconds;
+    }
+    if overrides.cloud_connect_timeout_seconds.is_some() {
+        base.cloud_connect_timeout_seconds = overrides.cloud_connect_timeout_seconds;
+    }
+    if overrides.cloud_max_retries.is_some() {
+        base.cloud_max_retries = overrides.cloud_max_retries;
+    }
     if overrides.external_calendars.is_some() {
         base.external_calendars = overrides.external_calendars;
     }
@@ -1543,8 +1768,18 @@ fn config_has_values(config: &AppConfigToml) -> bool {
         || config.webdav_url.is_some()
         || config.webdav_username.is_some()
         || config.webdav_password.is_some()
+        || config.webdav_ca_certificate_path.is_some()
+        || config.webdav_pinned_sha256.is_some()
+        || config.webdav_timeout_seconds.is_some()
+        || config.webdav_connect_timeout_seconds.is_some()
+        || config.webdav_max_retries.is_some()
         || config.cloud_url.is_some()
         || config.cloud_token.is_some()
+        || config.cloud_ca_certificate_path.is_some()
+        || config.cloud_pinned_sha256.is_some()
+        || config.cloud_timeout_seconds.is_some()
+        || config.cloud_connect_timeout_seconds.is_some()
+        || config.cloud_max_retries.is_some()
         || config.external_calendars.is_some()
         || config.ai_key_openai.is_some()
         || config.ai_key_anthropic.is_some()
@@ -1632,6 +1867,47 @@ fn set_keyring_secret(app: &tauri::AppHandle, key: &str, value: Option<String>)
     }
 }
 
+/// Storage keys in the keyring: the current one first, then one left pending by an interrupted
+/// key change.
+fn storage_keys(app: &tauri::AppHandle) -> Result<Vec<StorageKey>, String> {
+    let mut keys = Vec::new();
+    for name in [KEYRING_STORAGE_KEY, KEYRING_STORAGE_KEY_PENDING] {
+        if let Some(hex) = get_keyring_secret(app, name)? {
+            keys.push(StorageKey::from_hex(&hex)?);
+        }
+    }
+    Ok(keys)
+}
+
+/// Key new files are written with, or None when storage isn't encrypted: the one that unlocks the
+/// database, which after an interrupted key change is the pending key rather than the current
+/// one. The keyring is only consulted for an encrypted database, so plain setups never depend on
+/// it.
+fn storage_key(app: &tauri::AppHandle) -> Result<Option<StorageKey>, String> {
+    let db_path = get_db_path(app);
+    if !encryption::is_encrypted_database(&db_path) {
+        return Ok(None);
+    }
+    let keys = storage_keys(app)?;
+    if keys.is_empty() {
+        return Err("Storage is encrypted but its key is missing from the system keyring".to_string());
+    }
+    let (_, unlocked_with) = encryption::open(&db_path, &keys)?;
+    Ok(unlocked_with.map(|index| keys[index].clone()))
+}
+
+/// Reads `data.json` or its `.bak`, decrypting it first if needed.
+fn read_mirror_json(app: &tauri::AppHandle, path: &Path) -> Result<Value, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    if !encryption::is_encrypted_file(&bytes) {
+        return read_json_with_retries(path, 2);
+    }
+    let content = encryption::decode(&bytes, &storage_keys(app)?)?;
+    parse_json_relaxed(&content)
+        .map(normalize_sync_value)
+        .map_err(|e| e.to_string())
+}
+
 fn bootstrap_storage_layout(app: &tauri::AppHandle) -> Result<(), String> {
     let config_dir = get_config_dir(app);
     let data_dir = get_data_dir(app);
@@ -1692,51 +1968,127 @@ fn ensure_data_file(app: &tauri::AppHandle) -> Result<(), String> {
     bootstrap_storage_layout(app)
 }
 
+/// Refreshes the JSON mirror in the background once writes pause for `MIRROR_DEBOUNCE`, or after
+/// `MIRROR_MAX_DELAY` of continuous writes, so a write command doesn't reread and rewrite the whole
+/// dataset. The database stays the source of truth; `flush_json_mirror` catches up on exit.
+fn schedule_json_mirror(app: &tauri::AppHandle) {
+    let state = app.state::<MirrorState>();
+    state.generation.fetch_add(1, Ordering::SeqCst);
+    if state.writer_running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state = app.state::<MirrorState>();
+        let mut started = Instant::now();
+        loop {
+            let seen = state.generation.load(Ordering::SeqCst);
+            std::thread::sleep(MIRROR_DEBOUNCE);
+            if state.generation.load(Ordering::SeqCst) != seen && started.elapsed() < MIRROR_MAX_DELAY {
+                continue;
+            }
+            let refreshed = refresh_json_mirror(&app);
+            state.writer_running.store(false, Ordering::SeqCst);
+            if let Err(err) = refreshed {
+                log::warn!("Failed to refresh the JSON mirror: {}", err);
+                break;
+            }
+            // Writes made while the snapshot was taken found the writer still running.
+            let stale = state.written.load(Ordering::SeqCst) < state.generation.load(Ordering::SeqCst);
+            if !stale || state.writer_running.swap(true, Ordering::SeqCst) {
+                break;
+            }
+            started = Instant::now();
+        }
+    });
+}
+
+/// Writes the mirror now if a scheduled refresh hasn't caught up yet.
+fn flush_json_mirror(app: &tauri::AppHandle) {
+    let state = app.state::<MirrorState>();
+    if state.written.load(Ordering::SeqCst) < state.generation.load(Ordering::SeqCst) {
+        if let Err(err) = refresh_json_mirror(app) {
+            log::warn!("Failed to refresh the JSON mirror: {}", err);
+        }
+    }
+}
+
+fn refresh_json_mirror(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<MirrorState>();
+    let _writing = state.writing.lock().map_err(|_| "Mirror lock poisoned".to_string())?;
+    let generation = state.generation.load(Ordering::SeqCst);
+    let snapshot = with_sqlite(app, |conn| read_sqlite_data(conn))?;
+    write_json_mirror(app, &snapshot)?;
+    state.written.fetch_max(generation, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Keeps `data.json` (and its `.bak`) in step with the database for safety/rollbacks.
+fn write_json_mirror(app: &tauri::AppHandle, data: &AppData) -> Result<(), String> {
+    let data_path = get_data_path(app);
+    if let Some(parent) = data_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let backup_path = data_path.with_extension("json.bak");
+    if data_path.exists() {
+        let _ = fs::copy(&data_path, &backup_path);
+    }
+    let tmp_path = data_path.with_extension("json.tmp");
+    let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+    let content = encryption::encode(&content, storage_key(app)?.as_ref())?;
+    {
+        let mut file = File::create(&tmp_path).map_err(|e| e.to_string())?;
+        file.write_all(&content).map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    if cfg!(windows) && data_path.exists() {
+        fs::remove_file(&data_path).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&tmp_path, &data_path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[tauri::command]
-async fn get_data(app: tauri::AppHandle) -> Result<Value, String> {
+async fn get_data(app: tauri::AppHandle) -> Result<AppData, String> {
     tauri::async_runtime::spawn_blocking(move || {
         ensure_data_file(&app)?;
         let data_path = get_data_path(&app);
         let backup_path = data_path.with_extension("json.bak");
-        let mut conn = open_sqlite(&app)?;
 
-        if !sqlite_has_any_data(&conn)? && data_path.exists() {
-            if let Ok(value) = read_json_with_retries(&data_path, 2) {
-                let _ = fs::copy(&data_path, &backup_path);
-                migrate_json_to_sqlite(&mut conn, &value)?;
-                ensure_fts_populated(&conn, true)?;
+        let stored = with_sqlite(&app, |conn| {
+            if !sqlite_has_any_data(conn)? && data_path.exists() {
+                if let Ok(value) = read_mirror_json(&app, &data_path) {
+                    let data = AppData::from_value(value)?;
+                    let _ = fs::copy(&data_path, &backup_path);
+                    migrate_json_to_sqlite(conn, &data)?;
+                    fts::ensure_populated(conn, true)?;
+                }
             }
-        }
+            read_sqlite_data(conn)
+        });
 
-        match read_sqlite_data(&conn) {
-            Ok(mut value) => {
-                let settings_empty = value
-                    .get("settings")
-                    .and_then(|v| v.as_object())
-                    .map(|obj| obj.is_empty())
-                    .unwrap_or(true);
-                if settings_empty && data_path.exists() {
-                    if let Ok(json_value) = read_json_with_retries(&data_path, 2) {
+        match stored {
+            Ok(mut data) => {
+                if data.settings.is_empty() && data_path.exists() {
+                    if let Ok(json_value) = read_mirror_json(&app, &data_path) {
                         if let Some(json_settings) = json_value.get("settings").and_then(|v| v.as_object()) {
                             if !json_settings.is_empty() {
-                                if let Some(map) = value.as_object_mut() {
-                                    map.insert("settings".to_string(), Value::Object(json_settings.clone()));
-                                }
+                                data.settings = Settings(json_settings.clone());
                             }
                         }
                     }
                 }
-                Ok(value)
+                Ok(data)
             }
             Err(primary_err) => {
                 if data_path.exists() {
-                    if let Ok(value) = read_json_with_retries(&data_path, 2) {
-                        return Ok(value);
+                    if let Ok(data) = read_mirror_json(&app, &data_path).and_then(AppData::from_value) {
+                        return Ok(data);
                     }
                 }
                 if backup_path.exists() {
-                    if let Ok(value) = read_json_with_retries(&backup_path, 2) {
-                        return Ok(value);
+                    if let Ok(data) = read_mirror_json(&app, &backup_path).and_then(AppData::from_value) {
+                        return Ok(data);
                     }
                 }
                 Err(primary_err)
@@ -1747,48 +2099,122 @@ async fn get_data(app: tauri::AppHandle) -> Result<Value, String> {
     .map_err(|e| e.to_string())?
 }
 
+/// Saves a whole data document. With `repair` left out the document is rejected if it has any
+/// problems; `"fix"` corrects what it can and rejects the document if anything is left, `"drop"`
+/// drops every entity with a problem. Returns the problems found, with what was done about each.
 #[tauri::command]
-async fn save_data(app: tauri::AppHandle, data: Value) -> Result<bool, String> {
+async fn save_data(app: tauri::AppHandle, data: Value, repair: Option<Repair>) -> Result<Vec<Problem>, SaveDataError> {
     tauri::async_runtime::spawn_blocking(move || {
+        let validated = validation::validate(data, repair.unwrap_or_default(), &now_iso())?;
+        let Some(data) = validated.data else {
+            return Err(SaveDataError::Invalid {
+                message: validation::rejection_message("Data was not saved", &validated.problems),
+                problems: validated.problems,
+            });
+        };
+        if !validated.problems.is_empty() {
+            log::warn!("save_data repaired {} problems in the submitted data", validated.problems.len());
+        }
         ensure_data_file(&app)?;
-        let mut conn = open_sqlite(&app)?;
-        migrate_json_to_sqlite(&mut conn, &data)?;
+        with_sqlite(&app, |conn| apply_data_diff(conn, &data, "save_data"))?;
+        schedule_json_mirror(&app);
+        Ok(validated.problems)
+    })
+    .await
+    .map_err(|e| SaveDataError::from(e.to_string()))?
+}
 
-        // Keep JSON backup updated for safety/rollbacks
-        let data_path = get_data_path(&app);
-        if let Some(parent) = data_path.parent() {
-            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-        }
-        let backup_path = data_path.with_extension("json.bak");
-        if data_path.exists() {
-            let _ = fs::copy(&data_path, &backup_path);
-        }
-        let tmp_path = data_path.with_extension("json.tmp");
-        let content = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
-        {
-            let mut file = File::create(&tmp_path).map_err(|e| e.to_string())?;
-            file.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
-            file.sync_all().map_err(|e| e.to_string())?;
-        }
-        if cfg!(windows) && data_path.exists() {
-            fs::remove_file(&data_path).map_err(|e| e.to_string())?;
-        }
-        fs::rename(&tmp_path, &data_path).map_err(|e| e.to_string())?;
-        Ok(true)
+/// Error of `save_data`, serialized with a `kind` tag. On `invalid` nothing was saved and
+/// `problems` lists why.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum SaveDataError {
+    Invalid { message: String, problems: Vec<Problem> },
+    Failed { message: String },
+}
+
+impl From<String> for SaveDataError {
+    fn from(message: String) -> Self {
+        SaveDataError::Failed { message }
+    }
+}
+
+/// Lists the problems `save_data` would find in `data` without saving anything.
+#[tauri::command]
+fn validate_data(data: Value) -> Result<Vec<Problem>, String> {
+    validation::validate(data, Repair::Reject, &now_iso()).map(|validated| validated.problems)
+}
+
+#[tauri::command]
+async fn query_tasks(app: tauri::AppHandle, options: TaskQueryOptions) -> Result<TaskPage, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| query_tasks_in(conn, &options))
     })
     .await
     .map_err(|e| e.to_string())?
 }
 
-#[tauri::command]
-fn query_tasks(app: tauri::AppHandle, options: TaskQueryOptions) -> Result<Vec<Value>, String> {
-    let conn = open_sqlite(&app)?;
+/// Adds one `EXISTS` clause per value so a task must carry all of them as tags, or as contexts
+/// when `context` is set.
+fn push_label_filter(
+    where_clauses: &mut Vec<String>,
+    params: &mut Vec<Box<dyn ToSql>>,
+    context: bool,
+    values: Option<&Vec<String>>,
+) {
+    for value in values.into_iter().flatten() {
+        where_clauses.push(tags::task_filter("tasks", context, value, false, params));
+    }
+}
+
+fn push_range_filter(
+    where_clauses: &mut Vec<String>,
+    params: &mut Vec<Box<dyn ToSql>>,
+    column: &str,
+    from: Option<&String>,
+    to: Option<&String>,
+) {
+    if let Some(from) = from {
+        where_clauses.push(format!("{} >= ?", column));
+        params.push(Box::new(from.clone()));
+    }
+    if let Some(to) = to {
+        where_clauses.push(format!("{} <= ?", column));
+        params.push(Box::new(to.clone()));
+    }
+}
+
+fn task_order_by(sort_by: Option<TaskSortKey>, descending: bool) -> String {
+    let direction = if descending { "DESC" } else { "ASC" };
+    // Missing values always sort last; createdAt and id keep pages stable between calls.
+    let primary = match sort_by {
+        None => return "createdAt ASC, id ASC".to_string(),
+        Some(TaskSortKey::OrderNum) => format!("orderNum IS NULL, orderNum {}", direction),
+        Some(TaskSortKey::DueDate) => format!("dueDate IS NULL, dueDate {}", direction),
+        Some(TaskSortKey::CreatedAt) => format!("createdAt {}", direction),
+        Some(TaskSortKey::Priority) => format!(
+            "priority IS NULL, CASE priority WHEN 'urgent' THEN 0 WHEN 'high' THEN 1 WHEN 'medium' THEN 2 WHEN 'low' THEN 3 END {}",
+            direction
+        ),
+    };
+    format!("{}, createdAt ASC, id ASC", primary)
+}
+
+fn query_tasks_in(conn: &Connection, options: &TaskQueryOptions) -> Result<TaskPage, String> {
+    if options.limit.is_some_and(|limit| limit < 0) {
+        return Err("options.limit must not be negative".to_string());
+    }
+    if options.offset.is_some_and(|offset| offset < 0) {
+        return Err("options.offset must not be negative".to_string());
+    }
     let mut where_clauses: Vec<String> = Vec::new();
     let mut params: Vec<Box<dyn ToSql>> = Vec::new();
 
     let include_deleted = options.include_deleted.unwrap_or(false);
     let include_archived = options.include_archived.unwrap_or(false);
 
+    // Purged tasks are sync tombstones with no content, so they never show up, even in the trash.
+    where_clauses.push("purgedAt IS NULL".to_string());
     if !include_deleted {
         where_clauses.push("deletedAt IS NULL".to_string());
     }
@@ -1798,8 +2224,10 @@ fn query_tasks(app: tauri::AppHandle, options: TaskQueryOptions) -> Result<Vec<V
 
     if let Some(status) = options.status.as_ref() {
         if status != "all" {
+            let status = TaskStatus::parse(status)
+                .ok_or_else(|| format!("options.status: unknown task status `{}`", status))?;
             where_clauses.push("status = ?".to_string());
-            params.push(Box::new(status.clone()));
+            params.push(Box::new(status));
         }
     }
 
@@ -1808,7 +2236,7 @@ fn query_tasks(app: tauri::AppHandle, options: TaskQueryOptions) -> Result<Vec<V
             let placeholders = vec!["?"; exclude_statuses.len()].join(", ");
             where_clauses.push(format!("status NOT IN ({})", placeholders));
             for status in exclude_statuses {
-                params.push(Box::new(status.clone()));
+                params.push(Box::new(*status));
             }
         }
     }
@@ -1817,59 +2245,919 @@ fn query_tasks(app: tauri::AppHandle, options: TaskQueryOptions) -> Result<Vec<V
         where_clauses.push("projectId = ?".to_string());
         params.push(Box::new(project_id.clone()));
     }
+    if let Some(area_id) = options.area_id.as_ref() {
+        where_clauses.push("areaId = ?".to_string());
+        params.push(Box::new(area_id.clone()));
+    }
+    if let Some(section_id) = options.section_id.as_ref() {
+        where_clauses.push("sectionId = ?".to_string());
+        params.push(Box::new(section_id.clone()));
+    }
+    if let Some(priority) = options.priority {
+        where_clauses.push("priority = ?".to_string());
+        params.push(Box::new(priority));
+    }
+    if let Some(focused) = options.is_focused_today {
+        where_clauses.push(if focused {
+            "coalesce(isFocusedToday, 0) = 1".to_string()
+        } else {
+            "coalesce(isFocusedToday, 0) = 0".to_string()
+        });
+    }
 
-    let sql = if where_clauses.is_empty() {
-        "SELECT * FROM tasks".to_string()
+    push_label_filter(&mut where_clauses, &mut params, false, options.tags.as_ref());
+    push_label_filter(&mut where_clauses, &mut params, true, options.contexts.as_ref());
+    push_range_filter(&mut where_clauses, &mut params, "dueDate", options.due_from.as_ref(), options.due_to.as_ref());
+    push_range_filter(&mut where_clauses, &mut params, "startTime", options.start_from.as_ref(), options.start_to.as_ref());
+    push_range_filter(&mut where_clauses, &mut params, "reviewAt", None, options.review_due_by.as_ref());
+    push_range_filter(&mut where_clauses, &mut params, "completedAt", options.completed_since.as_ref(), None);
+
+    let where_sql = if where_clauses.is_empty() {
+        String::new()
     } else {
-        format!("SELECT * FROM tasks WHERE {}", where_clauses.join(" AND "))
+        format!(" WHERE {}", where_clauses.join(" AND "))
     };
 
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM tasks{}", where_sql),
+            params_from_iter(params.iter().map(|p| p.as_ref())),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let offset = options.offset.unwrap_or(0);
+    let order_by = task_order_by(options.sort_by, options.sort_desc.unwrap_or(false));
+    let sql = format!(
+        "SELECT * FROM tasks{} ORDER BY {} LIMIT ? OFFSET ?",
+        where_sql, order_by
+    );
+    // SQLite treats a negative LIMIT as "no limit".
+    params.push(Box::new(options.limit.unwrap_or(-1)));
+    params.push(Box::new(offset));
+
     let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
     let rows = stmt
-        .query_map(params_from_iter(params.iter().map(|p| p.as_ref())), |row| row_to_task_value(row))
+        .query_map(params_from_iter(params.iter().map(|p| p.as_ref())), row_to_task)
         .map_err(|e| e.to_string())?;
 
-    let mut tasks: Vec<Value> = Vec::new();
+    let mut tasks: Vec<Task> = Vec::new();
     for row in rows {
         tasks.push(row.map_err(|e| e.to_string())?);
     }
-    Ok(tasks)
+    let end = offset + tasks.len() as i64;
+    let next_offset = if end < total { Some(end) } else { None };
+    Ok(TaskPage {
+        tasks,
+        total,
+        next_offset,
+    })
+}
+
+#[tauri::command]
+async fn search_fts(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<SearchResults, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let options = options.unwrap_or_default();
+        with_sqlite(&app, |conn| search_fts_in(conn, &query, &options))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Runs one side of a search. With a rank expression the FTS index drives the query and provides
+/// bm25 scores and snippets; without one (e.g. `status:next` alone) rows are filtered in SQL and
+/// ordered by most recently updated.
+fn run_search<T>(
+    conn: &Connection,
+    query: &SearchQuery,
+    target: &SearchTarget,
+    filters: SearchFilters,
+    options: &SearchOptions,
+    fts_config: &FtsConfig,
+    row_to_item: fn(&rusqlite::Row<'_>) -> Result<T, rusqlite::Error>,
+) -> Result<(Vec<T>, Vec<SearchMatch>), String> {
+    let SearchTarget {
+        source_table,
+        fts_table,
+        alias,
+        title_column,
+        columns,
+        weights,
+        soft_delete,
+    } = *target;
+    let index = TextIndex {
+        fts_table,
+        alias,
+        columns,
+        trigram: fts_config.is_trigram(),
+    };
+    let mark_start = options.highlight_start.as_deref().unwrap_or(DEFAULT_HIGHLIGHT_START);
+    let mark_end = options.highlight_end.as_deref().unwrap_or(DEFAULT_HIGHLIGHT_END);
+    let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+    let SearchFilters {
+        clauses: mut filters,
+        params: mut filter_params,
+    } = filters;
+    if soft_delete {
+        filters.insert(0, format!("{}.deletedAt IS NULL", alias));
+    }
+    if filters.is_empty() {
+        filters.push("1".to_string());
+    }
+
+    let sql = match query.rank_expression(index.trigram) {
+        Some(rank_expression) => {
+            params.push(Box::new(mark_start.to_string()));
+            params.push(Box::new(mark_end.to_string()));
+            params.push(Box::new(mark_start.to_string()));
+            params.push(Box::new(mark_end.to_string()));
+            params.push(Box::new(rank_expression));
+            params.append(&mut filter_params);
+            let predicate = query.predicate(&index, &mut params);
+            format!(
+                "SELECT {alias}.*,
+                        bm25({fts}, {weights}) AS score,
+                        highlight({fts}, 1, ?, ?) AS titleHighlight,
+                        snippet({fts}, -1, ?, ?, '…', {tokens}) AS snippet
+                 FROM {fts} JOIN {source} {alias} ON {fts}.rowid = {alias}.rowid
+                 WHERE {fts} MATCH ? AND {filters} AND {predicate}
+                 ORDER BY score
+                 LIMIT ?",
+                alias = alias,
+                fts = fts_table,
+                source = source_table,
+                weights = weights,
+                tokens = SEARCH_SNIPPET_TOKENS,
+                filters = filters.join(" AND "),
+                predicate = predicate
+            )
+        }
+        None => {
+            params.append(&mut filter_params);
+            let predicate = query.predicate(&index, &mut params);
+            format!(
+                "SELECT {alias}.*, 0.0 AS score, {alias}.{title} AS titleHighlight, '' AS snippet
+                 FROM {source} {alias}
+                 WHERE {filters} AND {predicate}
+                 ORDER BY {alias}.updatedAt DESC
+                 LIMIT ?",
+                alias = alias,
+                title = title_column,
+                source = source_table,
+                filters = filters.join(" AND "),
+                predicate = predicate
+            )
+        }
+    };
+    params.push(Box::new(options.limit.unwrap_or(DEFAULT_SEARCH_LIMIT)));
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params_from_iter(params.iter().map(|p| p.as_ref())), |row| {
+            Ok((row_to_item(row)?, row_to_search_match(row)?))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut items = Vec::new();
+    let mut matches = Vec::new();
+    for row in rows {
+        let (item, search_match) = row.map_err(|e| e.to_string())?;
+        items.push(item);
+        matches.push(search_match);
+    }
+    Ok((items, matches))
+}
+
+fn search_fts_in(conn: &Connection, query: &str, options: &SearchOptions) -> Result<SearchResults, String> {
+    let query = match search_query::parse(query, chrono::Local::now().date_naive()) {
+        Ok(query) => query,
+        Err(error) => {
+            return Ok(SearchResults {
+                query_error: Some(error),
+                ..SearchResults::default()
+            })
+        }
+    };
+    if query.is_empty() {
+        return Ok(SearchResults::default());
+    }
+    if options.limit.is_some_and(|limit| limit < 0) {
+        return Err("options.limit must not be negative".to_string());
+    }
+
+    let fts_config = fts::load_config(conn)?;
+
+    let mut task_filters = SearchFilters::default();
+    if let Some(status) = options.status {
+        task_filters.push("t.status = ?", vec![Box::new(status)]);
+    }
+    if let Some(project_id) = options.project_id.as_ref() {
+        task_filters.push("t.projectId = ?", vec![Box::new(project_id.clone())]);
+    }
+    if let Some(area_id) = options.area_id.as_ref() {
+        // Tasks inherit the area of their project when they don't set one themselves.
+        task_filters.push(
+            "(t.areaId = ? OR (t.areaId IS NULL AND t.projectId IN (SELECT id FROM projects WHERE areaId = ?)))",
+            vec![Box::new(area_id.clone()), Box::new(area_id.clone())],
+        );
+    }
+    let (tasks, task_matches) =
+        run_search(conn, &query, &TASK_SEARCH, task_filters, options, &fts_config, row_to_task)?;
+
+    // Task fields and the status option don't apply to the other entities, so only search them
+    // when the query is plain text.
+    let plain_text = options.status.is_none() && !query.has_filters();
+    let (projects, project_matches) = if plain_text && options.project_id.is_none() {
+        let mut project_filters = SearchFilters::default();
+        if let Some(area_id) = options.area_id.as_ref() {
+            project_filters.push("p.areaId = ?", vec![Box::new(area_id.clone())]);
+        }
+        run_search(conn, &query, &PROJECT_SEARCH, project_filters, options, &fts_config, row_to_project)?
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let (sections, section_matches) = if plain_text {
+        let mut section_filters = SearchFilters::default();
+        if let Some(project_id) = options.project_id.as_ref() {
+            section_filters.push("s.projectId = ?", vec![Box::new(project_id.clone())]);
+        }
+        if let Some(area_id) = options.area_id.as_ref() {
+            section_filters.push(
+                "s.projectId IN (SELECT id FROM projects WHERE areaId = ?)",
+                vec![Box::new(area_id.clone())],
+            );
+        }
+        run_search(conn, &query, &SECTION_SEARCH, section_filters, options, &fts_config, row_to_section)?
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let (areas, area_matches) = if plain_text && options.project_id.is_none() {
+        let mut area_filters = SearchFilters::default();
+        if let Some(area_id) = options.area_id.as_ref() {
+            area_filters.push("a.id = ?", vec![Box::new(area_id.clone())]);
+        }
+        run_search(conn, &query, &AREA_SEARCH, area_filters, options, &fts_config, row_to_area)?
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    Ok(SearchResults {
+        tasks,
+        projects,
+        sections,
+        areas,
+        task_matches,
+        project_matches,
+        section_matches,
+        area_matches,
+        query_error: None,
+    })
+}
+
+fn row_to_search_match(row: &rusqlite::Row<'_>) -> Result<SearchMatch, rusqlite::Error> {
+    let score: f64 = row.get("score")?;
+    Ok(SearchMatch {
+        id: row.get("id")?,
+        // bm25() is lower-is-better; flip it so callers can treat larger as more relevant.
+        score: -score,
+        title_highlight: row.get("titleHighlight")?,
+        snippet: row.get("snippet")?,
+    })
+}
+
+#[tauri::command]
+async fn create_task(app: tauri::AppHandle, task: Value) -> Result<Task, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "create_task", |tx| create_task_in(tx, task))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn update_task(app: tauri::AppHandle, id: String, patch: Value) -> Result<Task, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "update_task", |tx| update_task_in(tx, &id, patch))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn delete_task(app: tauri::AppHandle, id: String) -> Result<Task, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "delete_task", |tx| {
+            let now = now_iso();
+            update_task_in(tx, &id, serde_json::json!({ "deletedAt": now }))
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn move_task(
+    app: tauri::AppHandle,
+    id: String,
+    project_id: Option<String>,
+    section_id: Option<String>,
+    order: Option<i64>,
+) -> Result<Task, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "move_task", |tx| {
+            let mut patch = Map::new();
+            patch.insert("projectId".to_string(), project_id.map(Value::String).unwrap_or(Value::Null));
+            patch.insert("sectionId".to_string(), section_id.map(Value::String).unwrap_or(Value::Null));
+            if let Some(order) = order {
+                patch.insert("orderNum".to_string(), Value::Number(order.into()));
+            }
+            update_task_in(tx, &id, Value::Object(patch))
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn create_project(app: tauri::AppHandle, project: Value) -> Result<Project, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "create_project", |tx| create_project_in(tx, project))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn update_project(app: tauri::AppHandle, id: String, patch: Value) -> Result<Project, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "update_project", |tx| update_project_in(tx, &id, patch))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn delete_project(app: tauri::AppHandle, id: String) -> Result<Project, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "delete_project", |tx| {
+            let now = now_iso();
+            let deleted = update_project_in(tx, &id, serde_json::json!({ "deletedAt": now }))?;
+            // Match the store: the project's sections and tasks go to the trash with it.
+            tx.execute(
+                "UPDATE sections SET deletedAt = ?1, updatedAt = ?1 WHERE projectId = ?2 AND deletedAt IS NULL",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE tasks SET deletedAt = ?1, updatedAt = ?1, sectionId = NULL WHERE projectId = ?2 AND deletedAt IS NULL",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(deleted)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn create_section(app: tauri::AppHandle, section: Value) -> Result<Section, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "create_section", |tx| create_section_in(tx, section))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn update_section(app: tauri::AppHandle, id: String, patch: Value) -> Result<Section, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "update_section", |tx| update_section_in(tx, &id, patch))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn delete_section(app: tauri::AppHandle, id: String) -> Result<Section, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "delete_section", |tx| {
+            let now = now_iso();
+            let deleted = update_section_in(tx, &id, serde_json::json!({ "deletedAt": now }))?;
+            tx.execute(
+                "UPDATE tasks SET sectionId = NULL, updatedAt = ?1 WHERE sectionId = ?2",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(deleted)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn create_area(app: tauri::AppHandle, area: Value) -> Result<Area, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "create_area", |tx| create_area_in(tx, area))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn update_area(app: tauri::AppHandle, id: String, patch: Value) -> Result<Area, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "update_area", |tx| update_area_in(tx, &id, patch))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn delete_area(app: tauri::AppHandle, id: String) -> Result<Area, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "delete_area", |tx| {
+            ensure_live_row(tx, "areas", &id, "Area")?;
+            let now = now_iso();
+            // Kept as a tombstone so the deletion reaches other devices instead of the area
+            // being brought back by their copy.
+            tx.execute(
+                "UPDATE areas SET deletedAt = ?1, updatedAt = ?1 WHERE id = ?2",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE projects SET areaId = NULL, areaTitle = NULL, updatedAt = ?1 WHERE areaId = ?2",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE tasks SET areaId = NULL, updatedAt = ?1 WHERE areaId = ?2",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            load_area(tx, &id)?.ok_or_else(|| format!("Area {} not found", id))
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn list_tags(app: tauri::AppHandle) -> Result<Vec<TagUsage>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| tags::list_tags(conn))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn list_contexts(app: tauri::AppHandle) -> Result<Vec<ContextUsage>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| tags::list_contexts(conn))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Renames `from` to `to` on every task and project that carries it.
+#[tauri::command]
+async fn rename_tag(app: tauri::AppHandle, from: String, to: String) -> Result<TagChange, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "rename_tag", |tx| {
+            tags::replace_tags(tx, &[from], Some(&to), &now_iso())
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Replaces every tag in `sources` with `target`, so entities carrying several of them end up
+/// with `target` once.
+#[tauri::command]
+async fn merge_tags(app: tauri::AppHandle, sources: Vec<String>, target: String) -> Result<TagChange, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "merge_tags", |tx| {
+            tags::replace_tags(tx, &sources, Some(&target), &now_iso())
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Removes `tag` from every task and project.
+#[tauri::command]
+async fn delete_tag(app: tauri::AppHandle, tag: String) -> Result<TagChange, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "delete_tag", |tx| tags::replace_tags(tx, &[tag], None, &now_iso()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn get_fts_config(app: tauri::AppHandle) -> Result<FtsConfig, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| fts::load_config(conn))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Switches the search tokenizer and rebuilds the FTS tables with it.
+#[tauri::command]
+async fn set_fts_config(app: tauri::AppHandle, config: FtsConfig) -> Result<FtsConfig, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            fts::apply_config(&tx, &config)?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(config)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Permanently removes trashed tasks, projects and sections, keeping sync tombstones. With
+/// `older_than_days` only entities deleted at least that many days ago are purged. The history
+/// and undo journal forget the purged content, so a purge can't be undone.
+#[tauri::command]
+async fn purge_trash(app: tauri::AppHandle, older_than_days: Option<u32>) -> Result<PurgeReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "purge_trash", |tx| purge_trash_in(tx, older_than_days))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn get_trash_retention(app: tauri::AppHandle) -> Result<Option<u32>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| trash::load_retention_days(conn))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Sets or clears the automatic purge policy and applies it right away.
+#[tauri::command]
+async fn set_trash_retention(app: tauri::AppHandle, days: Option<u32>) -> Result<PurgeReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_write_transaction(&app, "set_trash_retention", |tx| {
+            trash::save_retention_days(tx, days)?;
+            match days {
+                Some(days) => purge_trash_in(tx, Some(days)),
+                None => Ok(PurgeReport::default()),
+            }
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn purge_trash_in(conn: &Connection, older_than_days: Option<u32>) -> Result<PurgeReport, String> {
+    let now = chrono::Utc::now();
+    let cutoff = older_than_days.map(|days| {
+        (now - chrono::Duration::days(i64::from(days))).to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+    });
+    let report = trash::purge(
+        conn,
+        cutoff.as_deref(),
+        &now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+    )?;
+    history::forget_purged(conn, &report.purged_at)?;
+    journal::forget_purged(conn, &report.purged_at)?;
+    Ok(report)
+}
+
+/// Applies the trash retention policy at startup. The JSON mirror is only rewritten when
+/// something was actually purged.
+fn purge_expired_trash(app: &tauri::AppHandle) -> Result<(), String> {
+    with_sqlite(app, |conn| {
+        if let Some(days) = trash::load_retention_days(conn)? {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            let report = purge_trash_in(&tx, Some(days))?;
+            tx.commit().map_err(|e| e.to_string())?;
+            if report.total() > 0 {
+                schedule_json_mirror(app);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Reverts the most recent write. The webview should reload its data afterwards.
+#[tauri::command]
+async fn undo(app: tauri::AppHandle) -> Result<Option<OperationSummary>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_journal_transaction(&app, |tx| journal::undo(tx, &now_iso()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Re-applies the most recently undone write. The webview should reload its data afterwards.
+#[tauri::command]
+async fn redo(app: tauri::AppHandle) -> Result<Option<OperationSummary>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_journal_transaction(&app, |tx| journal::redo(tx, &now_iso()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn list_recent_operations(app: tauri::AppHandle, limit: Option<i64>) -> Result<Vec<OperationSummary>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let limit = limit.unwrap_or(DEFAULT_OPERATION_LIST_LIMIT);
+        if limit < 0 {
+            return Err("limit must not be negative".to_string());
+        }
+        with_sqlite(&app, |conn| journal::list_recent(conn, limit))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Like `with_write_transaction`, but for undo and redo, which must not be journaled themselves.
+fn with_journal_transaction<F>(app: &tauri::AppHandle, step: F) -> Result<Option<OperationSummary>, String>
+where
+    F: FnOnce(&rusqlite::Transaction<'_>) -> Result<Option<OperationSummary>, String>,
+{
+    ensure_data_file(app)?;
+    let operation = with_sqlite(app, |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let operation = step(&tx)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(operation)
+    })?;
+    if operation.is_some() {
+        schedule_json_mirror(app);
+    }
+    Ok(operation)
+}
+
+/// Checks for a due automatic backup at startup and then every `BACKUP_CHECK_INTERVAL`.
+fn start_backup_schedule(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        backup_if_due(&app);
+        std::thread::sleep(BACKUP_CHECK_INTERVAL);
+    });
+}
+
+/// Takes an automatic backup when the last one is over an hour old and the database has been
+/// written since the previous automatic backup of this session, then rotates old ones. The first
+/// check of a session always counts as changed, so writes from earlier sessions are covered. A
+/// failed backup is logged and retried on the next check.
+fn backup_if_due(app: &tauri::AppHandle) {
+    let generation = app.state::<MirrorState>().generation.load(Ordering::SeqCst);
+    let state = app.state::<BackupState>();
+    let mut covered = match state.0.lock() {
+        Ok(covered) => covered,
+        Err(_) => return,
+    };
+    if *covered == Some(generation) {
+        return;
+    }
+    let dir = get_backup_dir(app);
+    let now = chrono::Utc::now();
+    let result = backup::is_due(&dir, now).and_then(|due| {
+        if due {
+            // A connection of its own, so commands waiting on the shared one aren't held up while
+            // the database is copied.
+            let db_path = get_db_path(app);
+            let keys = if encryption::is_encrypted_database(&db_path) {
+                storage_keys(app)?
+            } else {
+                Vec::new()
+            };
+            let (conn, unlocked_with) = encryption::open(&db_path, &keys)?;
+            conn.busy_timeout(SQLITE_BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+            let key = unlocked_with.map(|index| &keys[index]);
+            backup::create(&dir, &conn, key, &get_data_path(app), backup::AUTO_REASON, now)?;
+            backup::prune(&dir)?;
+            *covered = Some(generation);
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        log::warn!("Automatic backup failed: {}", err);
+    }
+}
+
+/// Backups on disk, newest first.
+#[tauri::command]
+fn list_backups(app: tauri::AppHandle) -> Result<Vec<BackupInfo>, String> {
+    backup::list(&get_backup_dir(&app))
+}
+
+#[tauri::command]
+async fn create_backup_now(app: tauri::AppHandle) -> Result<BackupInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        ensure_data_file(&app)?;
+        with_sqlite(&app, |conn| {
+            let dir = get_backup_dir(&app);
+            let key = storage_key(&app)?;
+            let info = backup::create(&dir, conn, key.as_ref(), &get_data_path(&app), "manual", chrono::Utc::now())?;
+            backup::prune(&dir)?;
+            Ok(info)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Replaces the live database with backup `id`. The backup is checked, upgraded to the current
+/// schema and fully read on a scratch copy first, and the current data is backed up before it is
+/// overwritten. Returns the safety backup. The webview should reload its data afterwards.
+#[tauri::command]
+async fn restore_backup(app: tauri::AppHandle, id: String) -> Result<BackupInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        ensure_data_file(&app)?;
+        let dir = get_backup_dir(&app);
+        let scratch_path = get_data_dir(&app).join(format!("{}.restore", DB_FILE_NAME));
+        let result = with_sqlite(&app, |conn| {
+            let key = storage_key(&app)?;
+            let keys: Vec<StorageKey> = key.iter().cloned().collect();
+            let candidate = backup::open_for_restore(&dir, &id, &scratch_path, &keys, key.as_ref())?;
+            read_sqlite_data(&candidate).map_err(|e| format!("Backup {} is unreadable: {}", id, e))?;
+            drop(candidate);
+            let safety =
+                backup::create(&dir, conn, key.as_ref(), &get_data_path(&app), "pre-restore", chrono::Utc::now())?;
+            backup::restore_into(conn, &scratch_path, key.as_ref())?;
+            fts::ensure_populated(conn, true)?;
+            let snapshot = read_sqlite_data(conn)?;
+            write_json_mirror(&app, &snapshot)?;
+            Ok(safety)
+        });
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", scratch_path.to_string_lossy(), suffix));
+        }
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Re-encrypts the database, the JSON mirror and every backup under `next`, or decrypts them
+/// when `next` is None. The new key is parked in the keyring before anything is converted, so an
+/// interrupted run leaves every file readable with one of the stored keys.
+fn change_storage_key(app: &tauri::AppHandle, next: Option<StorageKey>) -> Result<(), String> {
+    ensure_data_file(app)?;
+    let state = app.state::<SqliteState>();
+    let mut guard = state.inner().0.lock().map_err(|_| "SQLite lock poisoned".to_string())?;
+    if let Some(conn) = guard.take() {
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| e.to_string())?;
+        conn.close().map_err(|(_, e)| e.to_string())?;
+    }
+
+    let mut keys = storage_keys(app)?;
+    if let Some(next) = &next {
+        set_keyring_secret(app, KEYRING_STORAGE_KEY_PENDING, Some(next.to_hex()))?;
+        keys.push(next.clone());
+    }
+    let data_path = get_data_path(app);
+    let backup_dir = get_backup_dir(app);
+    let mut databases = vec![get_db_path(app)];
+    let mut files = vec![data_path.clone(), data_path.with_extension("json.bak")];
+    for info in backup::list(&backup_dir)? {
+        databases.push(backup::database_path(&backup_dir, &info.id));
+        files.push(backup::data_path(&backup_dir, &info.id));
+    }
+    for path in databases.iter().filter(|path| path.exists()) {
+        encryption::convert_database(path, &keys, next.as_ref())?;
+    }
+    for path in files.iter().filter(|path| path.exists()) {
+        encryption::convert_file(path, &keys, next.as_ref())?;
+    }
+
+    set_keyring_secret(app, KEYRING_STORAGE_KEY, next.map(|key| key.to_hex()))?;
+    set_keyring_secret(app, KEYRING_STORAGE_KEY_PENDING, None)
+}
+
+#[tauri::command]
+fn is_storage_encrypted(app: tauri::AppHandle) -> bool {
+    encryption::is_encrypted_database(&get_db_path(&app))
+}
+
+/// Encrypts the database, the JSON mirror and backups with a new key kept in the OS keyring.
+#[tauri::command]
+async fn enable_storage_encryption(app: tauri::AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if encryption::is_encrypted_database(&get_db_path(&app)) {
+            return Err("Storage is already encrypted".to_string());
+        }
+        change_storage_key(&app, Some(StorageKey::generate()?))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Stores everything in plain text again and removes the key from the keyring.
+#[tauri::command]
+async fn disable_storage_encryption(app: tauri::AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if !encryption::is_encrypted_database(&get_db_path(&app)) {
+            return Err("Storage is not encrypted".to_string());
+        }
+        change_storage_key(&app, None)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Re-encrypts everything under a freshly generated key.
+#[tauri::command]
+async fn rotate_storage_key(app: tauri::AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if !encryption::is_encrypted_database(&get_db_path(&app)) {
+            return Err("Storage is not encrypted".to_string());
+        }
+        change_storage_key(&app, Some(StorageKey::generate()?))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Field-level change timeline of a task or project, oldest first.
+#[tauri::command]
+async fn get_entity_history(app: tauri::AppHandle, id: String) -> Result<Vec<HistoryEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| history::entity_history(conn, &id))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// This database's device id, as recorded in history entries.
+#[tauri::command]
+async fn get_device_id(app: tauri::AppHandle) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| history::device_id(conn))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Checks the database for corruption, FTS drift and orphaned references. `full` runs
+/// `integrity_check` instead of the faster `quick_check`.
+#[tauri::command]
+async fn check_database(app: tauri::AppHandle, full: Option<bool>) -> Result<CheckReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| integrity::check(conn, full.unwrap_or(false)))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Repairs what `check_database` finds: rebuilds damaged indexes, fixes orphaned references as
+/// one undoable operation, then vacuums. The report ends with a fresh full check, so anything
+/// still listed there needs a backup restore. The webview should reload its data afterwards.
+#[tauri::command]
+async fn repair_database(app: tauri::AppHandle) -> Result<RepairReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let before = with_sqlite(&app, |conn| integrity::check(conn, true))?;
+        let reindexed = !before.errors.is_empty();
+        if reindexed {
+            with_sqlite(&app, |conn| conn.execute_batch("REINDEX").map_err(|e| e.to_string()))?;
+        }
+        let (orphans_fixed, fts_rebuilt) = with_write_transaction(&app, "repair_database", |tx| {
+            let orphans_fixed = integrity::fix_orphans(tx, &now_iso())?;
+            let fts_rebuilt = fts::inconsistent_indexes(tx)?;
+            if !fts_rebuilt.is_empty() {
+                fts::apply_config(tx, &fts::load_config(tx)?)?;
+            }
+            Ok((orphans_fixed, fts_rebuilt))
+        })?;
+        with_sqlite(&app, |conn| {
+            conn.execute_batch("VACUUM").map_err(|e| e.to_string())?;
+            Ok(RepairReport {
+                after: integrity::check(conn, true)?,
+                before,
+                fts_rebuilt,
+                orphans_fixed,
+                reindexed,
+            })
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-fn search_fts(app: tauri::AppHandle, query: String) -> Result<Value, String> {
-    let conn = open_sqlite(&app)?;
-    let Some(fts_query) = build_fts_query(&query) else {
-        return Ok(serde_json::json!({ "tasks": [], "projects": [] }));
-    };
-
-    let mut tasks: Vec<Value> = Vec::new();
-    let mut projects: Vec<Value> = Vec::new();
-
-    let mut task_stmt = conn
-        .prepare("SELECT t.* FROM tasks_fts f JOIN tasks t ON f.id = t.id WHERE tasks_fts MATCH ? AND t.deletedAt IS NULL")
-        .map_err(|e| e.to_string())?;
-    let task_rows = task_stmt
-        .query_map([fts_query.clone()], |row| row_to_task_value(row))
-        .map_err(|e| e.to_string())?;
-    for row in task_rows {
-        tasks.push(row.map_err(|e| e.to_string())?);
-    }
-
-    let mut project_stmt = conn
-        .prepare("SELECT p.* FROM projects_fts f JOIN projects p ON f.id = p.id WHERE projects_fts MATCH ? AND p.deletedAt IS NULL")
-        .map_err(|e| e.to_string())?;
-    let project_rows = project_stmt
-        .query_map([fts_query], |row| row_to_project_value(row))
-        .map_err(|e| e.to_string())?;
-    for row in project_rows {
-        projects.push(row.map_err(|e| e.to_string())?);
-    }
-
-    Ok(serde_json::json!({
-        "tasks": tasks,
-        "projects": projects
-    }))
+async fn get_schema_version(app: tauri::AppHandle) -> Result<i64, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        with_sqlite(&app, |conn| migrations::current_version(conn))
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -2067,13 +3355,140 @@ fn get_webdav_config(app: tauri::AppHandle) -> Result<Value, String> {
             password = Some(legacy);
         }
     }
+    let client = ClientSettings::of(&config, RemoteBackend::WebDav);
     Ok(serde_json::json!({
         "url": config.webdav_url.unwrap_or_default(),
         "username": config.webdav_username.unwrap_or_default(),
-        "hasPassword": password.is_some()
+        "hasPassword": password.is_some(),
+        "client": client
     }))
 }
 
+/// The remote sync backends, each with its own HTTP client settings.
+#[derive(Debug, Clone, Copy)]
+enum RemoteBackend {
+    WebDav,
+    Cloud,
+}
+
+/// Connection settings of the HTTP client of a remote sync backend. Empty fields take the
+/// defaults in `http_client`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientSettings {
+    ca_certificate_path: Option<String>,
+    pinned_sha256: Option<String>,
+    timeout_seconds: Option<u64>,
+    connect_timeout_seconds: Option<u64>,
+    max_retries: Option<u32>,
+}
+
+impl ClientSettings {
+    fn of(config: &AppConfigToml, backend: RemoteBackend) -> ClientSettings {
+        let (ca_certificate_path, pinned_sha256, timeout, connect_timeout, max_retries) = match backend {
+            RemoteBackend::WebDav => (
+                &config.webdav_ca_certificate_path,
+                &config.webdav_pinned_sha256,
+                &config.webdav_timeout_seconds,
+                &config.webdav_connect_timeout_seconds,
+                &config.webdav_max_retries,
+            ),
+            RemoteBackend::Cloud => (
+                &config.cloud_ca_certificate_path,
+                &config.cloud_pinned_sha256,
+                &config.cloud_timeout_seconds,
+                &config.cloud_connect_timeout_seconds,
+                &config.cloud_max_retries,
+            ),
+        };
+        let number = |value: &Option<String>| value.as_deref().and_then(|raw| raw.trim().parse().ok());
+        ClientSettings {
+            ca_certificate_path: ca_certificate_path.clone(),
+            pinned_sha256: pinned_sha256.clone(),
+            timeout_seconds: number(timeout),
+            connect_timeout_seconds: number(connect_timeout),
+            max_retries: number(max_retries).and_then(|retries: u64| u32::try_from(retries).ok()),
+        }
+    }
+
+    fn store(self, config: &mut AppConfigToml, backend: RemoteBackend) {
+        let (ca_certificate_path, pinned_sha256, timeout, connect_timeout, max_retries) = match backend {
+            RemoteBackend::WebDav => (
+                &mut config.webdav_ca_certificate_path,
+                &mut config.webdav_pinned_sha256,
+                &mut config.webdav_timeout_seconds,
+                &mut config.webdav_connect_timeout_seconds,
+                &mut config.webdav_max_retries,
+            ),
+            RemoteBackend::Cloud => (
+                &mut config.cloud_ca_certificate_path,
+                &mut config.cloud_pinned_sha256,
+                &mut config.cloud_timeout_seconds,
+                &mut config.cloud_connect_timeout_seconds,
+                &mut config.cloud_max_retries,
+            ),
+        };
+        *ca_certificate_path = self.ca_certificate_path;
+        *pinned_sha256 = self.pinned_sha256;
+        *timeout = self.timeout_seconds.map(|value| value.to_string());
+        *connect_timeout = self.connect_timeout_seconds.map(|value| value.to_string());
+        *max_retries = self.max_retries.map(|value| value.to_string());
+    }
+
+    fn options(&self) -> ClientOptions {
+        let defaults = ClientOptions::default();
+        ClientOptions {
+            connect_timeout: self
+                .connect_timeout_seconds
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.connect_timeout),
+            timeout: self.timeout_seconds.map(Duration::from_secs).unwrap_or(defaults.timeout),
+            max_retries: self.max_retries.unwrap_or(defaults.max_retries),
+            ca_certificate_path: self.ca_certificate_path.as_ref().map(PathBuf::from),
+            pinned_sha256: self.pinned_sha256.clone(),
+        }
+    }
+}
+
+/// Saves the connection settings of `backend` after checking that a client can be built from
+/// them, so a missing CA file or malformed fingerprint is reported here rather than on the next
+/// sync.
+fn set_client_options(app: &tauri::AppHandle, backend: RemoteBackend, options: ClientSettings) -> Result<bool, String> {
+    let non_empty = |value: Option<String>| value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+    let mut options = ClientSettings {
+        ca_certificate_path: non_empty(options.ca_certificate_path),
+        pinned_sha256: non_empty(options.pinned_sha256),
+        ..options
+    };
+    if let Some(pin) = &options.pinned_sha256 {
+        options.pinned_sha256 = Some(http_client::normalize_fingerprint(pin)?);
+    }
+    if options.timeout_seconds == Some(0) || options.connect_timeout_seconds == Some(0) {
+        return Err("Timeouts must be at least one second".to_string());
+    }
+    if options.max_retries.is_some_and(|retries| retries > MAX_HTTP_RETRIES) {
+        return Err(format!("At most {} retries are allowed", MAX_HTTP_RETRIES));
+    }
+    HttpClient::new(&options.options())?;
+
+    let mut config = read_config(app);
+    options.store(&mut config, backend);
+    write_config_files(&get_config_path(app), &get_secrets_path(app), &config)?;
+    Ok(true)
+}
+
+/// Saves the WebDAV connection settings; see `set_client_options`.
+#[tauri::command]
+fn set_webdav_client_options(app: tauri::AppHandle, options: ClientSettings) -> Result<bool, String> {
+    set_client_options(&app, RemoteBackend::WebDav, options)
+}
+
+/// Saves the self-hosted cloud connection settings; see `set_client_options`.
+#[tauri::command]
+fn set_cloud_client_options(app: tauri::AppHandle, options: ClientSettings) -> Result<bool, String> {
+    set_client_options(&app, RemoteBackend::Cloud, options)
+}
+
 #[tauri::command]
 fn set_webdav_config(app: tauri::AppHandle, url: String, username: String, password: String) -> Result<bool, String> {
     let url = url.trim().to_string();
@@ -2098,6 +3513,31 @@ fn set_webdav_config(app: tauri::AppHandle, url: String, username: String, passw
     Ok(true)
 }
 
+fn normalize_cloud_url(raw: &str) -> String {
+    let trimmed = raw.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        return String::new();
+    }
+    if trimmed.to_lowercase().ends_with("/data") {
+        trimmed.to_string()
+    } else {
+        format!("{}/data", trimmed)
+    }
+}
+
+/// The bearer token travels with every request, so plain HTTP is only accepted for a server on
+/// this machine, as in the frontend cloud client.
+fn is_secure_cloud_url(url: &str) -> bool {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    match parsed.scheme() {
+        "https" => true,
+        "http" => matches!(parsed.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")),
+        _ => false,
+    }
+}
+
 fn normalize_webdav_url(raw: &str) -> String {
     let trimmed = raw.trim().trim_end_matches('/');
     if trimmed.is_empty() {
@@ -2110,56 +3550,85 @@ fn normalize_webdav_url(raw: &str) -> String {
     }
 }
 
+/// Fetches the WebDAV document, remembering its `ETag` and `Last-Modified` for
+/// `webdav_put_json`.
 #[tauri::command]
-fn webdav_get_json(app: tauri::AppHandle) -> Result<Value, String> {
-    let config = read_config(&app);
-    let url = normalize_webdav_url(&config.webdav_url.unwrap_or_default());
-    if url.trim().is_empty() {
-        return Err("WebDAV URL not configured".to_string());
-    }
-    let username = config.webdav_username.unwrap_or_default();
-    let password = get_keyring_secret(&app, KEYRING_WEB_DAV_PASSWORD)?
-        .ok_or_else(|| "WebDAV password not configured".to_string())?;
-
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .get(url)
-        .basic_auth(username, Some(password))
-        .send()
-        .map_err(|e| format!("WebDAV request failed: {e}"))?;
+async fn webdav_get_json(app: tauri::AppHandle) -> Result<Value, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = SyncTarget::webdav(&app)?;
+        read_remote_json(&target, &app.state::<RemoteVersions>())?
+            .ok_or_else(|| format!("WebDAV error: {}", reqwest::StatusCode::NOT_FOUND))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    if !response.status().is_success() {
-        return Err(format!("WebDAV error: {}", response.status()));
-    }
+/// Replaces the WebDAV document, provided it is still the one `webdav_get_json` returned.
+#[tauri::command]
+async fn webdav_put_json(app: tauri::AppHandle, data: Value) -> Result<bool, RemoteWriteError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = SyncTarget::webdav(&app)?;
+        write_remote_json(&target, &app.state::<RemoteVersions>(), &data)
+    })
+    .await
+    .map_err(|e| RemoteWriteError::from(e.to_string()))?
+}
 
-    response
-        .json::<Value>()
-        .map_err(|e| format!("Invalid WebDAV response: {e}"))
+/// Fetches the cloud document, or null when there is none yet, remembering its `ETag` for
+/// `cloud_put_json`.
+#[tauri::command]
+async fn cloud_get_json(app: tauri::AppHandle) -> Result<Option<Value>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = SyncTarget::cloud(&app)?;
+        read_remote_json(&target, &app.state::<RemoteVersions>())
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
+/// Replaces the cloud document, provided it is still the one `cloud_get_json` returned. Servers
+/// that don't send an `ETag` are written unconditionally.
 #[tauri::command]
-fn webdav_put_json(app: tauri::AppHandle, data: Value) -> Result<bool, String> {
-    let config = read_config(&app);
-    let url = normalize_webdav_url(&config.webdav_url.unwrap_or_default());
-    if url.trim().is_empty() {
-        return Err("WebDAV URL not configured".to_string());
+async fn cloud_put_json(app: tauri::AppHandle, data: Value) -> Result<bool, RemoteWriteError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = SyncTarget::cloud(&app)?;
+        write_remote_json(&target, &app.state::<RemoteVersions>(), &data)
+    })
+    .await
+    .map_err(|e| RemoteWriteError::from(e.to_string()))?
+}
+
+fn read_remote_json(target: &SyncTarget, versions: &RemoteVersions) -> Result<Option<Value>, String> {
+    let (value, version) = target.read()?;
+    versions.record(target, version);
+    Ok(value)
+}
+
+fn write_remote_json(target: &SyncTarget, versions: &RemoteVersions, data: &Value) -> Result<bool, RemoteWriteError> {
+    match target.write(data, &versions.recorded(target))? {
+        WriteOutcome::Written(version) => {
+            versions.record(target, version);
+            Ok(true)
+        }
+        WriteOutcome::RemoteChanged => Err(RemoteWriteError::RemoteChanged {
+            message: format!("The {} document changed since it was read", target.name()),
+        }),
     }
-    let username = config.webdav_username.unwrap_or_default();
-    let password = get_keyring_secret(&app, KEYRING_WEB_DAV_PASSWORD)?
-        .ok_or_else(|| "WebDAV password not configured".to_string())?;
+}
 
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .put(url)
-        .basic_auth(username, Some(password))
-        .json(&data)
-        .send()
-        .map_err(|e| format!("WebDAV request failed: {e}"))?;
+/// Error of the commands that write a whole sync document, serialized with a `kind` tag. On
+/// `remoteChanged` nothing was written: read the document again, merge and retry.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum RemoteWriteError {
+    RemoteChanged { message: String },
+    Failed { message: String },
+}
 
-    if !response.status().is_success() {
-        return Err(format!("WebDAV error: {}", response.status()));
+impl From<String> for RemoteWriteError {
+    fn from(message: String) -> Self {
+        RemoteWriteError::Failed { message }
     }
-    Ok(true)
 }
 
 #[tauri::command]
@@ -2174,9 +3643,11 @@ fn get_cloud_config(app: tauri::AppHandle) -> Result<Value, String> {
             token = Some(legacy);
         }
     }
+    let client = ClientSettings::of(&config, RemoteBackend::Cloud);
     Ok(serde_json::json!({
         "url": config.cloud_url.unwrap_or_default(),
-        "token": token.unwrap_or_default()
+        "token": token.unwrap_or_default(),
+        "client": client
     }))
 }
 
@@ -2264,36 +3735,34 @@ fn open_path(path: String) -> Result<bool, String> {
 }
 
 
-#[tauri::command]
-fn read_sync_file(app: tauri::AppHandle) -> Result<serde_json::Value, String> {
-    let sync_path_str = get_sync_path(app)?;
-    let sync_file = PathBuf::from(&sync_path_str).join(DATA_FILE_NAME);
-    let backup_file = PathBuf::from(&sync_path_str).join(format!("{}.bak", DATA_FILE_NAME));
-    
+/// Reads `data.json` from the sync folder, falling back to the legacy file name and then to the
+/// last backup. Returns None when the folder has no data file yet, along with the stamp of
+/// `data.json` a later write must find unchanged.
+fn read_sync_dir(dir: &Path) -> Result<(Option<Value>, Option<FileStamp>), String> {
+    let sync_file = dir.join(DATA_FILE_NAME);
+    let backup_file = dir.join(format!("{}.bak", DATA_FILE_NAME));
+    // Stamped before reading: if the file is replaced in between, the next write sees a mismatch
+    // and merges again rather than overwriting what it never read.
+    let stamp = sync_file::stamp(&sync_file)?;
+
     if !sync_file.exists() {
-        let legacy_sync_file = PathBuf::from(&sync_path_str).join(format!("{}-sync.json", APP_NAME));
+        let legacy_sync_file = dir.join(format!("{}-sync.json", APP_NAME));
         if legacy_sync_file.exists() {
             let content = fs::read_to_string(&legacy_sync_file).map_err(|e| e.to_string())?;
             return parse_json_relaxed(&content)
-                .map(normalize_sync_value)
+                .map(|value| (Some(normalize_sync_value(value)), stamp))
                 .map_err(|e| e.to_string());
         }
-        // Return empty app data structure if file doesn't exist
-        return Ok(serde_json::json!({
-            "tasks": [],
-            "projects": [],
-            "areas": [],
-            "settings": {}
-        }));
+        return Ok((None, stamp));
     }
 
     match read_json_with_retries(&sync_file, 5) {
-        Ok(value) => Ok(value),
+        Ok(value) => Ok((Some(value), stamp)),
         Err(primary_err) => {
             // Fallback to last known good backup if available.
             if backup_file.exists() {
                 if let Ok(value) = read_json_with_retries(&backup_file, 2) {
-                    return Ok(value);
+                    return Ok((Some(value), stamp));
                 }
             }
             Err(primary_err)
@@ -2301,24 +3770,19 @@ fn read_sync_file(app: tauri::AppHandle) -> Result<serde_json::Value, String> {
     }
 }
 
-
-#[tauri::command]
-fn write_sync_file(app: tauri::AppHandle, data: Value) -> Result<bool, String> {
-    let sync_path_str = get_sync_path(app)?;
-    let sync_file = PathBuf::from(&sync_path_str).join(DATA_FILE_NAME);
-    let backup_file = PathBuf::from(&sync_path_str).join(format!("{}.bak", DATA_FILE_NAME));
-    let tmp_file = PathBuf::from(&sync_path_str).join(format!("{}.tmp", DATA_FILE_NAME));
+/// Writes `data` as the sync folder's `data.json`, provided the file still matches `expected`.
+/// Returns the stamp of the written file.
+fn write_sync_dir(dir: &Path, data: &Value, expected: &Option<FileStamp>) -> Result<WriteOutcome<FileStamp>, String> {
+    let sync_file = dir.join(DATA_FILE_NAME);
+    let backup_file = dir.join(format!("{}.bak", DATA_FILE_NAME));
+    let tmp_file = dir.join(format!("{}.tmp", DATA_FILE_NAME));
 
     if let Some(parent) = sync_file.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
+    let _lock = sync_file::SyncLock::acquire(dir)?;
 
-    // Best-effort backup for recovery.
-    if sync_file.exists() {
-        let _ = fs::copy(&sync_file, &backup_file);
-    }
-
-    let content = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
+    let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
 
     // Atomic-ish write: write to tmp then rename over the target.
     {
@@ -2327,13 +3791,372 @@ fn write_sync_file(app: tauri::AppHandle, data: Value) -> Result<bool, String> {
         file.sync_all().map_err(|e| e.to_string())?;
     }
 
+    if sync_file::stamp(&sync_file)? != *expected {
+        let _ = fs::remove_file(&tmp_file);
+        return Ok(WriteOutcome::RemoteChanged);
+    }
+
+    // Best-effort backup for recovery.
+    if sync_file.exists() {
+        let _ = fs::copy(&sync_file, &backup_file);
+    }
+
     if cfg!(windows) && sync_file.exists() {
         // Windows doesn't allow renaming over an existing file.
         fs::remove_file(&sync_file).map_err(|e| e.to_string())?;
     }
     fs::rename(&tmp_file, &sync_file).map_err(|e| e.to_string())?;
-    
-    Ok(true)
+
+    let written = sync_file::stamp(&sync_file)?.ok_or_else(|| "Sync file disappeared after writing".to_string())?;
+    Ok(WriteOutcome::Written(written))
+}
+
+#[tauri::command]
+async fn read_sync_file(app: tauri::AppHandle) -> Result<serde_json::Value, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = SyncTarget::File(PathBuf::from(get_sync_path(app.clone())?));
+        let (value, version) = target.read()?;
+        app.state::<RemoteVersions>().record(&target, version);
+        // Return empty app data structure if file doesn't exist
+        Ok(value.unwrap_or_else(|| {
+            serde_json::json!({
+                "tasks": [],
+                "projects": [],
+                "areas": [],
+                "settings": {}
+            })
+        }))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Writes the sync file. If another device replaced it since `read_sync_file`, the two are merged
+/// against the sync base, the file as this device last wrote it, and the merge is written instead.
+/// An entity on either side that can't be repaired stops the write rather than being left out of
+/// the merge.
+#[tauri::command]
+async fn write_sync_file(app: tauri::AppHandle, data: Value) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = SyncTarget::File(PathBuf::from(get_sync_path(app.clone())?));
+        let key = target.key();
+        let versions = app.state::<RemoteVersions>();
+        let base = with_sqlite(&app, |conn| sync::load_base(conn, &key))?;
+        let (written, version) = write_merging(&target, data, versions.recorded(&target), base.as_ref())?;
+        versions.record(&target, version);
+        with_sqlite(&app, |conn| sync::store_base(conn, &sync::Base::of(&key, &written)))?;
+        Ok(true)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Writes `data` to `target` unless it changed since it was read as `expected`; then the two are
+/// merged entity by entity against `base`, and the merge is written instead. Returns what was
+/// written and its version.
+fn write_merging(
+    target: &SyncTarget,
+    data: Value,
+    expected: RemoteVersion,
+    base: Option<&sync::Base>,
+) -> Result<(AppData, RemoteVersion), String> {
+    let mut local = parse_sync_data(data.clone(), "The sync file was not written", &now_iso())?;
+    let mut data = data;
+    let mut expected = expected;
+    for _ in 0..SYNC_WRITE_ATTEMPTS {
+        match target.write(&data, &expected)? {
+            WriteOutcome::Written(version) => return Ok((local, version)),
+            WriteOutcome::RemoteChanged => {
+                log::info!("Sync file changed since it was read; merging before writing");
+                let (current, version) = target.read()?;
+                // A missing document says nothing about what was removed from it.
+                if let Some(current) = current {
+                    let remote = parse_sync_data(current, "The sync file can't be merged", &now_iso())?;
+                    let merged = sync::merge(base, &local, &remote);
+                    if !merged.summary.conflicts.is_empty() {
+                        log::info!("Settled {} sync conflicts by updatedAt", merged.summary.conflicts.len());
+                    }
+                    local = merged.data;
+                    data = serde_json::to_value(&local).map_err(|e| e.to_string())?;
+                }
+                expected = version;
+            }
+        }
+    }
+    Err("The sync file kept changing while writing; try again shortly".to_string())
+}
+
+/// The version of each sync document as last read or written by the commands that move whole
+/// documents for the frontend, so a write can tell whether another device replaced the document
+/// since. Keyed by `SyncTarget::key`.
+#[derive(Default)]
+struct RemoteVersions(Mutex<HashMap<String, RemoteVersion>>);
+
+impl RemoteVersions {
+    fn record(&self, target: &SyncTarget, version: RemoteVersion) {
+        if let Ok(mut versions) = self.0.lock() {
+            versions.insert(target.key(), version);
+        }
+    }
+
+    /// The recorded version, or `Unversioned` when the document wasn't read first.
+    fn recorded(&self, target: &SyncTarget) -> RemoteVersion {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|versions| versions.get(&target.key()).cloned())
+            .unwrap_or(RemoteVersion::Unversioned)
+    }
+}
+
+/// The HTTP client of the remote sync backends, kept between commands so connections are reused
+/// and rebuilt when its options change.
+#[derive(Default)]
+struct SyncHttpClient(Mutex<Option<(ClientOptions, HttpClient)>>);
+
+impl SyncHttpClient {
+    fn get(&self, options: ClientOptions) -> Result<HttpClient, String> {
+        let mut cached = self.0.lock().map_err(|_| "HTTP client lock poisoned".to_string())?;
+        if let Some((cached_options, client)) = cached.as_ref() {
+            if *cached_options == options {
+                return Ok(client.clone());
+            }
+        }
+        let client = HttpClient::new(&options)?;
+        *cached = Some((options, client.clone()));
+        Ok(client)
+    }
+}
+
+/// The result of a conditional write of a sync document.
+enum WriteOutcome<V> {
+    /// Written; `V` identifies the new version.
+    Written(V),
+    /// The document changed since it was read; nothing was written.
+    RemoteChanged,
+}
+
+/// What a sync document looked like when read, for the conditional write that follows.
+#[derive(Debug, Clone)]
+enum RemoteVersion {
+    /// Nothing known: the next write is unconditional.
+    Unversioned,
+    File(Option<FileStamp>),
+    /// Validators from an HTTP response, or None when the document didn't exist.
+    Http(Option<HttpValidators>),
+}
+
+#[derive(Debug, Clone)]
+struct HttpValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl HttpValidators {
+    fn of(response: &reqwest::blocking::Response) -> HttpValidators {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        HttpValidators {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// Adds the precondition for replacing the document read as `expected`: `If-Match` with its
+/// ETag, else `If-Unmodified-Since` with its modification date, or `If-None-Match: *` when there
+/// was no document. Weak ETags never satisfy `If-Match`, so they fall back to the date.
+fn with_precondition(
+    request: reqwest::blocking::RequestBuilder,
+    expected: &RemoteVersion,
+) -> reqwest::blocking::RequestBuilder {
+    match expected {
+        RemoteVersion::Http(None) => request.header(reqwest::header::IF_NONE_MATCH, "*"),
+        RemoteVersion::Http(Some(validators)) => {
+            match (validators.etag.as_deref(), validators.last_modified.as_deref()) {
+                (Some(etag), _) if !etag.starts_with("W/") => request.header(reqwest::header::IF_MATCH, etag),
+                (_, Some(date)) => request.header(reqwest::header::IF_UNMODIFIED_SINCE, date),
+                _ => request,
+            }
+        }
+        RemoteVersion::Unversioned | RemoteVersion::File(_) => request,
+    }
+}
+
+/// The shared document of each sync backend.
+enum SyncTarget {
+    File(PathBuf),
+    WebDav {
+        client: HttpClient,
+        url: String,
+        username: String,
+        password: String,
+    },
+    Cloud {
+        client: HttpClient,
+        url: String,
+        token: String,
+    },
+}
+
+impl SyncTarget {
+    fn webdav(app: &tauri::AppHandle) -> Result<SyncTarget, String> {
+        let config = read_config(app);
+        let url = normalize_webdav_url(config.webdav_url.as_deref().unwrap_or_default());
+        if url.trim().is_empty() {
+            return Err("WebDAV URL not configured".to_string());
+        }
+        let password = get_keyring_secret(app, KEYRING_WEB_DAV_PASSWORD)?
+            .ok_or_else(|| "WebDAV password not configured".to_string())?;
+        let client = app
+            .state::<SyncHttpClient>()
+            .get(ClientSettings::of(&config, RemoteBackend::WebDav).options())?;
+        Ok(SyncTarget::WebDav {
+            client,
+            url,
+            username: config.webdav_username.unwrap_or_default(),
+            password,
+        })
+    }
+
+    fn cloud(app: &tauri::AppHandle) -> Result<SyncTarget, String> {
+        let config = read_config(app);
+        let url = normalize_cloud_url(config.cloud_url.as_deref().unwrap_or_default());
+        if url.is_empty() {
+            return Err("Cloud URL not configured".to_string());
+        }
+        if !is_secure_cloud_url(&url) {
+            return Err("Cloud sync requires HTTPS (except localhost).".to_string());
+        }
+        let token = get_keyring_secret(app, KEYRING_CLOUD_TOKEN)?
+            .filter(|token| !token.trim().is_empty())
+            .ok_or_else(|| "Cloud token not configured".to_string())?;
+        let client = app
+            .state::<SyncHttpClient>()
+            .get(ClientSettings::of(&config, RemoteBackend::Cloud).options())?;
+        Ok(SyncTarget::Cloud { client, url, token })
+    }
+
+    /// Identifies the remote document, so a sync base recorded against one is never used with
+    /// another.
+    fn key(&self) -> String {
+        match self {
+            SyncTarget::File(dir) => format!("file:{}", dir.join(DATA_FILE_NAME).display()),
+            SyncTarget::WebDav { url, username, .. } => format!("webdav:{}@{}", username, url),
+            SyncTarget::Cloud { url, .. } => format!("cloud:{}", url),
+        }
+    }
+
+    /// Fetches the document, or None when there isn't one yet, with the version a conditional
+    /// write expects.
+    fn read(&self) -> Result<(Option<Value>, RemoteVersion), String> {
+        let (client, url) = match self {
+            SyncTarget::File(dir) => {
+                let (value, stamp) = read_sync_dir(dir)?;
+                return Ok((value, RemoteVersion::File(stamp)));
+            }
+            SyncTarget::WebDav { client, url, .. } | SyncTarget::Cloud { client, url, .. } => (client, url),
+        };
+        let response = client
+            .send(self.authorize(client.request(reqwest::Method::GET, url)))
+            .map_err(|e| format!("{} request failed: {e}", self.name()))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok((None, RemoteVersion::Http(None)));
+        }
+        let name = self.name();
+        if !response.status().is_success() {
+            return Err(format!("{} error: {}", name, response.status()));
+        }
+        let validators = HttpValidators::of(&response);
+        let value = response
+            .json::<Value>()
+            .map_err(|e| format!("Invalid {} response: {e}", name))?;
+        Ok((Some(value), RemoteVersion::Http(Some(validators))))
+    }
+
+    /// Writes the document unless it changed since it was read as `expected`, returning the
+    /// version written.
+    fn write(&self, data: &Value, expected: &RemoteVersion) -> Result<WriteOutcome<RemoteVersion>, String> {
+        let (client, url) = match self {
+            SyncTarget::File(dir) => {
+                let stamp = match expected {
+                    RemoteVersion::File(stamp) => stamp.clone(),
+                    _ => sync_file::stamp(&dir.join(DATA_FILE_NAME))?,
+                };
+                return write_sync_dir(dir, data, &stamp).map(|outcome| match outcome {
+                    WriteOutcome::Written(stamp) => WriteOutcome::Written(RemoteVersion::File(Some(stamp))),
+                    WriteOutcome::RemoteChanged => WriteOutcome::RemoteChanged,
+                });
+            }
+            SyncTarget::WebDav { client, url, .. } | SyncTarget::Cloud { client, url, .. } => (client, url),
+        };
+        let put = || {
+            let request = with_precondition(self.authorize(client.request(reqwest::Method::PUT, url)), expected);
+            client
+                .send(request.json(data))
+                .map_err(|e| format!("{} request failed: {e}", self.name()))
+        };
+        let mut response = put()?;
+        // The first upload to a WebDAV folder that doesn't exist yet.
+        let missing_folder = matches!(
+            response.status(),
+            reqwest::StatusCode::CONFLICT | reqwest::StatusCode::NOT_FOUND
+        );
+        if missing_folder && matches!(self, SyncTarget::WebDav { .. }) {
+            let folder = reqwest::Url::parse(url)
+                .and_then(|url| url.join("."))
+                .map_err(|e| format!("Invalid WebDAV URL: {e}"))?;
+            client.ensure_collection(&folder, &|request| self.authorize(request))?;
+            response = put()?;
+        }
+        if response.status() == reqwest::StatusCode::PRECONDITION_FAILED {
+            return Ok(WriteOutcome::RemoteChanged);
+        }
+        if !response.status().is_success() {
+            return Err(format!("{} error: {}", self.name(), response.status()));
+        }
+        // Without new validators the next write can't be conditional, so it has to read first.
+        let validators = HttpValidators::of(&response);
+        if validators.etag.is_none() && validators.last_modified.is_none() {
+            return Ok(WriteOutcome::Written(RemoteVersion::Unversioned));
+        }
+        Ok(WriteOutcome::Written(RemoteVersion::Http(Some(validators))))
+    }
+
+    fn authorize(&self, request: reqwest::blocking::RequestBuilder) -> reqwest::blocking::RequestBuilder {
+        match self {
+            SyncTarget::File(_) => request,
+            SyncTarget::WebDav { username, password, .. } => request.basic_auth(username, Some(password)),
+            SyncTarget::Cloud { token, .. } => request.bearer_auth(token),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SyncTarget::File(_) => "Sync file",
+            SyncTarget::WebDav { .. } => "WebDAV",
+            SyncTarget::Cloud { .. } => "Cloud",
+        }
+    }
+}
+
+/// Parses a document taking part in a sync merge, repairing what `save_data` would repair. An
+/// entity that can't be repaired stops the sync, with `outcome` saying what didn't happen: leaving
+/// it out of the merge would read as a deletion and remove it everywhere.
+fn parse_sync_data(value: Value, outcome: &str, now: &str) -> Result<AppData, String> {
+    let validated = validation::validate(value, Repair::Fix, now)?;
+    let Some(data) = validated.data else {
+        return Err(validation::rejection_message(outcome, &validated.problems));
+    };
+    if !validated.problems.is_empty() {
+        log::warn!("Repaired {} problems in data being synced", validated.problems.len());
+    }
+    Ok(data)
 }
 
 #[tauri::command]
@@ -2461,6 +4284,10 @@ pub fn run() {
         .setup(|app| {
             // Ensure data file exists on startup
             ensure_data_file(&app.handle()).ok();
+            // Open and migrate the shared database up front so the first command is fast.
+            with_sqlite(app.handle(), |_| Ok(())).ok();
+            purge_expired_trash(app.handle()).ok();
+            start_backup_schedule(app.handle().clone());
             let diagnostics_enabled = diagnostics_enabled();
             if let Some(window) = app.get_webview_window("main") {
                 if cfg!(target_os = "linux") && is_niri_session() {
@@ -2529,11 +4356,55 @@ pub fn run() {
             Ok(())
         })
         .manage(AudioRecorderState(Mutex::new(None)))
+        .manage(SqliteState::default())
+        .manage(MirrorState::default())
+        .manage(BackupState::default())
+        .manage(RemoteVersions::default())
+        .manage(SyncHttpClient::default())
         .invoke_handler(tauri::generate_handler![
             get_data,
             save_data,
+            validate_data,
             query_tasks,
             search_fts,
+            get_schema_version,
+            get_fts_config,
+            set_fts_config,
+            purge_trash,
+            get_trash_retention,
+            set_trash_retention,
+            undo,
+            redo,
+            list_recent_operations,
+            get_entity_history,
+            get_device_id,
+            list_backups,
+            create_backup_now,
+            restore_backup,
+            check_database,
+            repair_database,
+            is_storage_encrypted,
+            enable_storage_encryption,
+            disable_storage_encryption,
+            rotate_storage_key,
+            create_task,
+            update_task,
+            delete_task,
+            move_task,
+            create_project,
+            update_project,
+            delete_project,
+            create_section,
+            update_section,
+            delete_section,
+            create_area,
+            update_area,
+            delete_area,
+            list_tags,
+            list_contexts,
+            rename_tag,
+            merge_tags,
+            delete_tag,
             get_data_path_cmd,
             get_db_path_cmd,
             get_config_path_cmd,
@@ -2545,8 +4416,12 @@ pub fn run() {
             set_sync_backend,
             get_webdav_config,
             set_webdav_config,
+            set_webdav_client_options,
+            set_cloud_client_options,
             webdav_get_json,
             webdav_put_json,
+            cloud_get_json,
+            cloud_put_json,
             get_cloud_config,
             set_cloud_config,
             get_external_calendars,
@@ -2565,8 +4440,15 @@ pub fn run() {
             consume_quick_add_pending,
             quit_app
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // However the app ends (last window closed, Cmd-Q, OS shutdown, updater restart),
+            // catch the JSON mirror up with writes still waiting for the debounce.
+            if let tauri::RunEvent::Exit = event {
+                flush_json_mirror(app);
+            }
+        });
 }
 
 fn show_main(app: &tauri::AppHandle) {
@@ -2586,3 +4468,390 @@ fn show_main_and_emit(app: &tauri::AppHandle) {
         let _ = app.emit("quick-add", ());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::{TcpListener, TcpStream};
+
+    fn memory_connection() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SQLITE_PRAGMAS).unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        fts::ensure_populated(&conn, false).unwrap();
+        conn
+    }
+
+    fn task(id: &str, title: &str) -> Value {
+        json!({
+            "id": id,
+            "title": title,
+            "status": "inbox",
+            "tags": [],
+            "contexts": [],
+            "createdAt": "2024-01-01T00:00:00.000Z",
+            "updatedAt": "2024-01-01T00:00:00.000Z"
+        })
+    }
+
+    fn data(tasks: Vec<Value>) -> AppData {
+        AppData::from_value(json!({ "tasks": tasks, "projects": [] })).unwrap()
+    }
+
+    fn task_titles(conn: &Connection) -> Vec<(String, String)> {
+        let mut titles: Vec<_> = read_sqlite_data(conn)
+            .unwrap()
+            .tasks
+            .into_iter()
+            .map(|task| (task.id, task.title))
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn apply_data_diff_writes_only_changed_rows() {
+        let mut conn = memory_connection();
+        apply_data_diff(&mut conn, &data(vec![task("a", "A"), task("b", "B")]), "save_data").unwrap();
+        assert_eq!(journal::list_recent(&conn, 10).unwrap().len(), 1);
+
+        // Saving the same data again changes no row, so no operation is journaled.
+        apply_data_diff(&mut conn, &data(vec![task("a", "A"), task("b", "B")]), "save_data").unwrap();
+        assert_eq!(journal::list_recent(&conn, 10).unwrap().len(), 1);
+
+        // A content change with the same updatedAt is written; a missing task is deleted.
+        apply_data_diff(&mut conn, &data(vec![task("a", "A repaired")]), "save_data").unwrap();
+        assert_eq!(task_titles(&conn), vec![("a".to_string(), "A repaired".to_string())]);
+        let latest = &journal::list_recent(&conn, 10).unwrap()[0];
+        assert_eq!(latest.changes, 2);
+    }
+
+    #[test]
+    fn sync_data_that_cant_be_repaired_stops_the_sync() {
+        let now = "2024-01-02T00:00:00.000Z";
+        let mut repairable = task("a", "A");
+        repairable["status"] = json!("unknown");
+        let outcome = "Remote data can't be synced";
+        let remote = parse_sync_data(json!({ "tasks": [repairable.clone()] }), outcome, now).unwrap();
+        assert_eq!(remote.tasks[0].id, "a");
+
+        // Leaving the section out would delete it on the next merge.
+        let section = json!({ "id": "s", "title": "S", "projectId": "gone", "createdAt": now, "updatedAt": now });
+        let error = parse_sync_data(json!({ "tasks": [repairable], "sections": [section] }), outcome, now).unwrap_err();
+        assert!(error.starts_with("Remote data can't be synced because it has 1 problem: sections[0]"), "{error}");
+    }
+
+    #[test]
+    fn purged_tasks_leave_nothing_in_the_journal() {
+        let mut conn = memory_connection();
+        let mut secret = task("a", "Secret plan");
+        secret["description"] = json!("The safe code is 1234");
+        apply_data_diff(&mut conn, &data(vec![secret.clone(), task("b", "Keep")]), "save_data").unwrap();
+        secret["description"] = json!("The safe code is 1234, moved to the attic");
+        apply_data_diff(&mut conn, &data(vec![secret.clone(), task("b", "Keep")]), "update_task").unwrap();
+        secret["deletedAt"] = json!("2024-01-02T00:00:00.000Z");
+        apply_data_diff(&mut conn, &data(vec![secret, task("b", "Keep edited")]), "delete_task").unwrap();
+
+        let tx = conn.transaction().unwrap();
+        let now = now_iso();
+        journal::begin(&tx, "purge_trash", &now).unwrap();
+        let report = purge_trash_in(&tx, None).unwrap();
+        journal::finish(&tx, &now).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(report.tasks, 1);
+
+        let leaks: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM operation_changes
+                 WHERE coalesce(before, '') || coalesce(after, '') LIKE '%Secret plan%'
+                    OR coalesce(before, '') || coalesce(after, '') LIKE '%1234%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leaks, 0);
+        // Operations that only touched the purged task are gone, and the purge itself can't be
+        // undone; undo moves on to the edit of the task that is left.
+        let labels: Vec<String> = journal::list_recent(&conn, 10)
+            .unwrap()
+            .into_iter()
+            .map(|operation| operation.label)
+            .collect();
+        assert_eq!(labels, ["delete_task", "save_data"]);
+        journal::undo(&conn, &now_iso()).unwrap().unwrap();
+        assert_eq!(
+            task_titles(&conn),
+            vec![("a".to_string(), String::new()), ("b".to_string(), "Keep".to_string())]
+        );
+    }
+
+    /// Method, `Authorization`, `If-Match` and `If-None-Match` of a request.
+    type Request = (String, Option<String>, Option<String>, Option<String>);
+    /// Method, `If-Match` and `If-None-Match` of a request.
+    type Precondition = (String, Option<String>, Option<String>);
+
+    /// Stands in for a WebDAV server holding one document, whose ETag counts its
+    /// versions. Honours `If-Match` and `If-None-Match: *` on PUT and records every request.
+    struct DocumentServer {
+        url: String,
+        document: Arc<Mutex<Option<(u32, String)>>>,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    impl DocumentServer {
+        fn start() -> DocumentServer {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/dav/data.json", listener.local_addr().unwrap());
+            let document = Arc::new(Mutex::new(None));
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let (served, log) = (document.clone(), requests.clone());
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    serve(stream, &served, &log);
+                }
+            });
+            DocumentServer { url, document, requests }
+        }
+
+        /// Replaces the document as another device would.
+        fn replace(&self, data: &Value) {
+            let mut document = self.document.lock().unwrap();
+            let version = document.as_ref().map_or(1, |(version, _)| version + 1);
+            *document = Some((version, data.to_string()));
+        }
+
+        /// Method and preconditions of each request so far.
+        fn preconditions(&self) -> Vec<Precondition> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .map(|(method, _, if_match, if_none_match)| (method.clone(), if_match.clone(), if_none_match.clone()))
+                .collect()
+        }
+    }
+
+    fn serve(mut stream: TcpStream, document: &Mutex<Option<(u32, String)>>, requests: &Mutex<Vec<Request>>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let method = line.split_whitespace().next().unwrap_or_default().to_string();
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let Some((name, value)) = header.trim_end().split_once(':') else {
+                break;
+            };
+            headers.insert(name.to_lowercase(), value.trim().to_string());
+        }
+        let length = headers.get("content-length").map_or(0, |length| length.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let (if_match, if_none_match) = (headers.remove("if-match"), headers.remove("if-none-match"));
+        requests.lock().unwrap().push((
+            method.clone(),
+            headers.remove("authorization"),
+            if_match.clone(),
+            if_none_match.clone(),
+        ));
+
+        let mut document = document.lock().unwrap();
+        let current = document.as_ref().map(|(version, _)| format!("\"{}\"", version));
+        let (status, etag, content) = match (method.as_str(), document.as_ref()) {
+            ("GET", Some((_, content))) => ("200 OK", current, content.clone()),
+            ("GET", None) => ("404 Not Found", None, String::new()),
+            ("PUT", _) => {
+                let stale = match (&if_match, &if_none_match) {
+                    (Some(etag), _) => current.as_ref() != Some(etag),
+                    (None, Some(_)) => current.is_some(),
+                    (None, None) => false,
+                };
+                if stale {
+                    ("412 Precondition Failed", None, String::new())
+                } else {
+                    let version = document.as_ref().map_or(1, |(version, _)| version + 1);
+                    *document = Some((version, String::from_utf8(body).unwrap()));
+                    ("201 Created", Some(format!("\"{}\"", version)), String::new())
+                }
+            }
+            _ => ("405 Method Not Allowed", None, String::new()),
+        };
+        let etag = etag.map(|etag| format!("ETag: {}\r\n", etag)).unwrap_or_default();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            etag,
+            content.len(),
+            content
+        )
+        .unwrap();
+    }
+
+    fn precondition(method: &str, if_match: Option<&str>, if_none_match: Option<&str>) -> Precondition {
+        (method.to_string(), if_match.map(str::to_string), if_none_match.map(str::to_string))
+    }
+
+    #[test]
+    fn webdav_writes_only_replace_the_document_that_was_read() {
+        let server = DocumentServer::start();
+        let target = SyncTarget::WebDav {
+            client: HttpClient::new(&ClientOptions::default()).unwrap(),
+            url: server.url.clone(),
+            username: "me".to_string(),
+            password: "secret".to_string(),
+        };
+        let versions = RemoteVersions::default();
+        let data = json!({ "tasks": [task("a", "A")] });
+
+        // Read while missing, then created by another device: the write must not replace it.
+        assert_eq!(read_remote_json(&target, &versions).unwrap(), None);
+        server.replace(&json!({ "tasks": [] }));
+        let error = write_remote_json(&target, &versions, &data).unwrap_err();
+        assert!(matches!(error, RemoteWriteError::RemoteChanged { .. }), "{error:?}");
+
+        // Read again, the ETag is kept and sent with the next writes.
+        assert_eq!(read_remote_json(&target, &versions).unwrap(), Some(json!({ "tasks": [] })));
+        let RemoteVersion::Http(Some(validators)) = versions.recorded(&target) else {
+            panic!("no validators recorded");
+        };
+        assert_eq!(validators.etag.as_deref(), Some("\"1\""));
+        assert!(write_remote_json(&target, &versions, &data).unwrap());
+        assert!(write_remote_json(&target, &versions, &data).unwrap());
+
+        // Replaced by another device since the last write.
+        server.replace(&json!({ "tasks": [] }));
+        let error = write_remote_json(&target, &versions, &data).unwrap_err();
+        assert!(matches!(error, RemoteWriteError::RemoteChanged { .. }), "{error:?}");
+
+        assert_eq!(
+            server.preconditions(),
+            [
+                precondition("GET", None, None),
+                precondition("PUT", None, Some("*")),
+                precondition("GET", None, None),
+                precondition("PUT", Some("\"1\""), None),
+                precondition("PUT", Some("\"2\""), None),
+                precondition("PUT", Some("\"3\""), None),
+            ]
+        );
+        let requests = server.requests.lock().unwrap();
+        let basic = "Basic bWU6c2VjcmV0".to_string();
+        assert!(requests.iter().all(|request| request.1.as_ref() == Some(&basic)));
+    }
+
+    /// The cloud server from `apps/cloud`, run with Bun on a free port and stopped when dropped.
+    struct CloudServer {
+        url: String,
+        process: std::process::Child,
+        data_dir: PathBuf,
+    }
+
+    impl CloudServer {
+        /// Starts the server, or returns None when Bun isn't installed.
+        fn start(name: &str) -> Option<CloudServer> {
+            let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            let data_dir = std::env::temp_dir().join(format!("mindwtr-cloud-{}-{}", name, std::process::id()));
+            let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../cloud/src/server.ts");
+            let spawned = std::process::Command::new("bun")
+                .arg("run")
+                .arg(&script)
+                .args(["--port", &port.to_string(), "--host", "127.0.0.1"])
+                .env("MINDWTR_CLOUD_DATA_DIR", &data_dir)
+                .stdout(std::process::Stdio::null())
+                .spawn();
+            let process = match spawned {
+                Ok(process) => process,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    eprintln!("Skipping the cloud server test: bun is not installed");
+                    return None;
+                }
+                Err(e) => panic!("Failed to start the cloud server: {e}"),
+            };
+            let server = CloudServer {
+                url: format!("http://127.0.0.1:{}", port),
+                process,
+                data_dir,
+            };
+            let health = format!("{}/health", server.url);
+            for _ in 0..100 {
+                if reqwest::blocking::get(&health).is_ok_and(|response| response.status().is_success()) {
+                    return Some(server);
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            panic!("The cloud server did not start");
+        }
+
+        /// Replaces the data of `token` as another device would.
+        fn replace(&self, token: &str, data: &Value) {
+            let response = reqwest::blocking::Client::new()
+                .put(format!("{}/v1/data", self.url))
+                .bearer_auth(token)
+                .json(data)
+                .send()
+                .unwrap();
+            assert!(response.status().is_success(), "{}", response.status());
+        }
+    }
+
+    impl Drop for CloudServer {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+            let _ = fs::remove_dir_all(&self.data_dir);
+        }
+    }
+
+    #[test]
+    fn cloud_sync_uses_its_client_settings_and_conditional_writes() {
+        let config = AppConfigToml {
+            cloud_timeout_seconds: Some("5".to_string()),
+            cloud_max_retries: Some("0".to_string()),
+            webdav_max_retries: Some("7".to_string()),
+            ..AppConfigToml::default()
+        };
+        let options = ClientSettings::of(&config, RemoteBackend::Cloud).options();
+        assert_eq!((options.timeout, options.max_retries), (Duration::from_secs(5), 0));
+
+        let Some(server) = CloudServer::start("conditional-writes") else {
+            return;
+        };
+        let target = SyncTarget::Cloud {
+            client: HttpClient::new(&options).unwrap(),
+            url: format!("{}/v1/data", server.url),
+            token: "token".to_string(),
+        };
+        let versions = RemoteVersions::default();
+        let data = json!({ "tasks": [task("a", "A")], "projects": [] });
+        let theirs = json!({ "tasks": [task("b", "B")], "projects": [] });
+
+        // Read while missing, then created by another device: the write must not replace it.
+        assert_eq!(read_remote_json(&target, &versions).unwrap(), None);
+        server.replace("token", &theirs);
+        let error = write_remote_json(&target, &versions, &data).unwrap_err();
+        assert!(matches!(error, RemoteWriteError::RemoteChanged { .. }), "{error:?}");
+
+        // Read again, each write sends the ETag of the one before.
+        assert_eq!(read_remote_json(&target, &versions).unwrap(), Some(theirs.clone()));
+        assert!(write_remote_json(&target, &versions, &data).unwrap());
+        assert!(write_remote_json(&target, &versions, &data).unwrap());
+        assert_eq!(read_remote_json(&target, &versions).unwrap(), Some(data.clone()));
+
+        // Replaced by another device since the last write.
+        server.replace("token", &theirs);
+        let error = write_remote_json(&target, &versions, &data).unwrap_err();
+        assert!(matches!(error, RemoteWriteError::RemoteChanged { .. }), "{error:?}");
+        assert_eq!(read_remote_json(&target, &versions).unwrap(), Some(theirs));
+
+        // Another token has data of its own.
+        let other = SyncTarget::Cloud {
+            client: HttpClient::new(&options).unwrap(),
+            url: format!("{}/v1/data", server.url),
+            token: "other".to_string(),
+        };
+        assert_eq!(read_remote_json(&other, &RemoteVersions::default()).unwrap(), None);
+    }
+}
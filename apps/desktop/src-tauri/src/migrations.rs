@@ -0,0 +1,573 @@
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::fts::{self, FtsConfig};
+use crate::history;
+use crate::journal;
+use crate::model::UNKNOWN_TIMESTAMP;
+use crate::tags;
+
+/// A numbered schema change. Migrations run in order, each inside its own transaction, and are
+/// recorded in `schema_migrations` so they are applied exactly once.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Connection) -> Result<(), String>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base schema",
+        apply: base_schema,
+    },
+    Migration {
+        version: 2,
+        description: "id-keyed FTS triggers (superseded by 3)",
+        apply: noop,
+    },
+    Migration {
+        version: 3,
+        description: "rowid-keyed FTS triggers",
+        apply: rowid_fts_triggers,
+    },
+    Migration {
+        version: 4,
+        description: "task order/area/section/purge columns and project ordering",
+        apply: task_and_project_columns,
+    },
+    Migration {
+        version: 5,
+        description: "external-content FTS tables (superseded by 7)",
+        apply: noop,
+    },
+    Migration {
+        version: 6,
+        description: "per-database FTS tokenizer",
+        apply: fts_tokenizer_config,
+    },
+    Migration {
+        version: 7,
+        description: "FTS for sections, areas, checklists and attachments",
+        apply: fts_extended_indexes,
+    },
+    Migration {
+        version: 8,
+        description: "purge tombstones for projects and sections",
+        apply: project_and_section_purge_columns,
+    },
+    Migration {
+        version: 9,
+        description: "undo/redo journal",
+        apply: operation_journal,
+    },
+    Migration {
+        version: 10,
+        description: "task and project change history",
+        apply: entity_history,
+    },
+    Migration {
+        version: 11,
+        description: "extra column for fields this build doesn't know",
+        apply: entity_extra_columns,
+    },
+    Migration {
+        version: 12,
+        description: "area tombstones, archiving and required timestamps",
+        apply: area_lifecycle_columns,
+    },
+    Migration {
+        version: 13,
+        description: "tag and context join tables",
+        apply: tags::install,
+    },
+];
+
+const BASE_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS tasks (
+  id TEXT PRIMARY KEY,
+  title TEXT NOT NULL,
+  status TEXT NOT NULL,
+  priority TEXT,
+  taskMode TEXT,
+  startTime TEXT,
+  dueDate TEXT,
+  recurrence TEXT,
+  pushCount INTEGER,
+  tags TEXT,
+  contexts TEXT,
+  checklist TEXT,
+  description TEXT,
+  attachments TEXT,
+  location TEXT,
+  projectId TEXT,
+  sectionId TEXT,
+  areaId TEXT,
+  orderNum INTEGER,
+  isFocusedToday INTEGER,
+  timeEstimate TEXT,
+  reviewAt TEXT,
+  completedAt TEXT,
+  createdAt TEXT NOT NULL,
+  updatedAt TEXT NOT NULL,
+  deletedAt TEXT,
+  purgedAt TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
+CREATE INDEX IF NOT EXISTS idx_tasks_project_id ON tasks(projectId);
+CREATE INDEX IF NOT EXISTS idx_tasks_updated_at ON tasks(updatedAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_deleted_at ON tasks(deletedAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_due_date ON tasks(dueDate);
+CREATE INDEX IF NOT EXISTS idx_tasks_start_time ON tasks(startTime);
+CREATE INDEX IF NOT EXISTS idx_tasks_review_at ON tasks(reviewAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_created_at ON tasks(createdAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_status_deleted_at ON tasks(status, deletedAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_project_status_deleted_at ON tasks(projectId, status, deletedAt);
+
+CREATE TABLE IF NOT EXISTS projects (
+  id TEXT PRIMARY KEY,
+  title TEXT NOT NULL,
+  status TEXT NOT NULL,
+  color TEXT NOT NULL,
+  orderNum INTEGER,
+  tagIds TEXT,
+  isSequential INTEGER,
+  isFocused INTEGER,
+  supportNotes TEXT,
+  attachments TEXT,
+  reviewAt TEXT,
+  areaId TEXT,
+  areaTitle TEXT,
+  createdAt TEXT NOT NULL,
+  updatedAt TEXT NOT NULL,
+  deletedAt TEXT
+);
+
+CREATE TABLE IF NOT EXISTS areas (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  color TEXT,
+  icon TEXT,
+  orderNum INTEGER NOT NULL,
+  createdAt TEXT,
+  updatedAt TEXT
+);
+
+CREATE TABLE IF NOT EXISTS sections (
+  id TEXT PRIMARY KEY,
+  projectId TEXT NOT NULL,
+  title TEXT NOT NULL,
+  description TEXT,
+  orderNum INTEGER,
+  isCollapsed INTEGER,
+  createdAt TEXT NOT NULL,
+  updatedAt TEXT NOT NULL,
+  deletedAt TEXT
+);
+
+CREATE TABLE IF NOT EXISTS settings (
+  id INTEGER PRIMARY KEY CHECK (id = 1),
+  data TEXT NOT NULL
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
+  id UNINDEXED,
+  title,
+  description,
+  tags,
+  contexts,
+  content=''
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS projects_fts USING fts5(
+  id UNINDEXED,
+  title,
+  supportNotes,
+  tagIds,
+  areaTitle,
+  content=''
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
+CREATE INDEX IF NOT EXISTS idx_tasks_projectId ON tasks(projectId);
+CREATE INDEX IF NOT EXISTS idx_tasks_deletedAt ON tasks(deletedAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_dueDate ON tasks(dueDate);
+CREATE INDEX IF NOT EXISTS idx_tasks_startTime ON tasks(startTime);
+CREATE INDEX IF NOT EXISTS idx_tasks_reviewAt ON tasks(reviewAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_createdAt ON tasks(createdAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_updatedAt ON tasks(updatedAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_status_deletedAt ON tasks(status, deletedAt);
+CREATE INDEX IF NOT EXISTS idx_tasks_project_status_deletedAt ON tasks(projectId, status, deletedAt);
+CREATE INDEX IF NOT EXISTS idx_projects_status ON projects(status);
+CREATE INDEX IF NOT EXISTS idx_projects_areaId ON projects(areaId);
+"#;
+
+const FTS_TRIGGERS: &str = r#"
+DROP TRIGGER IF EXISTS tasks_ai;
+DROP TRIGGER IF EXISTS tasks_ad;
+DROP TRIGGER IF EXISTS tasks_au;
+DROP TRIGGER IF EXISTS projects_ai;
+DROP TRIGGER IF EXISTS projects_ad;
+DROP TRIGGER IF EXISTS projects_au;
+
+CREATE TRIGGER tasks_ai AFTER INSERT ON tasks BEGIN
+  INSERT INTO tasks_fts (rowid, id, title, description, tags, contexts)
+  VALUES (new.rowid, new.id, new.title, coalesce(new.description, ''), coalesce(new.tags, ''), coalesce(new.contexts, ''));
+END;
+
+CREATE TRIGGER tasks_ad AFTER DELETE ON tasks BEGIN
+  INSERT INTO tasks_fts (tasks_fts, rowid, id, title, description, tags, contexts)
+  VALUES ('delete', old.rowid, old.id, old.title, coalesce(old.description, ''), coalesce(old.tags, ''), coalesce(old.contexts, ''));
+END;
+
+CREATE TRIGGER tasks_au AFTER UPDATE ON tasks BEGIN
+  INSERT INTO tasks_fts (tasks_fts, rowid, id, title, description, tags, contexts)
+  VALUES ('delete', old.rowid, old.id, old.title, coalesce(old.description, ''), coalesce(old.tags, ''), coalesce(old.contexts, ''));
+  INSERT INTO tasks_fts (rowid, id, title, description, tags, contexts)
+  VALUES (new.rowid, new.id, new.title, coalesce(new.description, ''), coalesce(new.tags, ''), coalesce(new.contexts, ''));
+END;
+
+CREATE TRIGGER projects_ai AFTER INSERT ON projects BEGIN
+  INSERT INTO projects_fts (rowid, id, title, supportNotes, tagIds, areaTitle)
+  VALUES (new.rowid, new.id, new.title, coalesce(new.supportNotes, ''), coalesce(new.tagIds, ''), coalesce(new.areaTitle, ''));
+END;
+
+CREATE TRIGGER projects_ad AFTER DELETE ON projects BEGIN
+  INSERT INTO projects_fts (projects_fts, rowid, id, title, supportNotes, tagIds, areaTitle)
+  VALUES ('delete', old.rowid, old.id, old.title, coalesce(old.supportNotes, ''), coalesce(old.tagIds, ''), coalesce(old.areaTitle, ''));
+END;
+
+CREATE TRIGGER projects_au AFTER UPDATE ON projects BEGIN
+  INSERT INTO projects_fts (projects_fts, rowid, id, title, supportNotes, tagIds, areaTitle)
+  VALUES ('delete', old.rowid, old.id, old.title, coalesce(old.supportNotes, ''), coalesce(old.tagIds, ''), coalesce(old.areaTitle, ''));
+  INSERT INTO projects_fts (rowid, id, title, supportNotes, tagIds, areaTitle)
+  VALUES (new.rowid, new.id, new.title, coalesce(new.supportNotes, ''), coalesce(new.tagIds, ''), coalesce(new.areaTitle, ''));
+END;
+"#;
+
+/// The newest schema version this build knows how to read and write.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Highest version recorded in `schema_migrations`, or 0 for a fresh database.
+pub fn current_version(conn: &Connection) -> Result<i64, String> {
+    conn.query_row("SELECT coalesce(MAX(version), 0) FROM schema_migrations", [], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Brings the database up to `latest_version()`, refusing to touch a database that was written by
+/// a newer build since its schema may not be understood here. FTS tables marked stale along the
+/// way are rebuilt once, and the journal and history triggers regenerated, against the final
+/// schema.
+pub fn migrate(conn: &mut Connection) -> Result<(), String> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY);")
+        .map_err(|e| e.to_string())?;
+
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this version of Mindwtr supports ({}). Please update the app.",
+            current, latest
+        ));
+    }
+
+    let mut applied_any = false;
+    for migration in MIGRATIONS {
+        let applied: Option<i64> = conn
+            .query_row(
+                "SELECT version FROM schema_migrations WHERE version = ?1",
+                [migration.version],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if applied.is_some() {
+            continue;
+        }
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        (migration.apply)(&tx).map_err(|e| {
+            format!("Migration {} ({}) failed: {}", migration.version, migration.description, e)
+        })?;
+        tx.execute("INSERT INTO schema_migrations (version) VALUES (?1)", [migration.version])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        applied_any = true;
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    fts::rebuild_if_stale(&tx)?;
+    if applied_any {
+        // Journal and history triggers list every column, so they follow the schema.
+        journal::install_triggers(&tx)?;
+        history::install_triggers(&tx)?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn noop(_conn: &Connection) -> Result<(), String> {
+    Ok(())
+}
+
+fn base_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(BASE_SCHEMA).map_err(|e| e.to_string())
+}
+
+fn rowid_fts_triggers(conn: &Connection) -> Result<(), String> {
+    // The FTS tables are contentless, so their `id` column always reads back as NULL and
+    // 'delete' commands must carry the rowid of the entry they remove. Entries written by the
+    // old id-keyed triggers cannot be removed individually, so the index is rebuilt.
+    conn.execute_batch(FTS_TRIGGERS).map_err(|e| e.to_string())?;
+    conn.execute_batch(
+        "INSERT INTO tasks_fts(tasks_fts) VALUES('delete-all');
+         INSERT INTO tasks_fts (rowid, id, title, description, tags, contexts)
+         SELECT rowid, id, title, coalesce(description, ''), coalesce(tags, ''), coalesce(contexts, '') FROM tasks;
+         INSERT INTO projects_fts(projects_fts) VALUES('delete-all');
+         INSERT INTO projects_fts (rowid, id, title, supportNotes, tagIds, areaTitle)
+         SELECT rowid, id, title, coalesce(supportNotes, ''), coalesce(tagIds, ''), coalesce(areaTitle, '') FROM projects;",
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn fts_tokenizer_config(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS app_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);")
+        .map_err(|e| e.to_string())?;
+    let language: Option<String> = conn
+        .query_row(
+            "SELECT CASE WHEN json_valid(data) AND json_type(data, '$.language') = 'text'
+                    THEN json_extract(data, '$.language') END
+             FROM settings WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    fts::save_config(conn, &FtsConfig::for_language(language.as_deref()))?;
+    fts::mark_stale(conn)
+}
+
+fn fts_extended_indexes(conn: &Connection) -> Result<(), String> {
+    // The indexes now keep their own derived text, so they are recreated from scratch.
+    fts::mark_stale(conn)
+}
+
+fn project_and_section_purge_columns(conn: &Connection) -> Result<(), String> {
+    add_column_if_missing(conn, "projects", "purgedAt", "TEXT")?;
+    add_column_if_missing(conn, "sections", "purgedAt", "TEXT")?;
+    // Purged rows drop out of the FTS tables, which the index triggers need to know about.
+    fts::mark_stale(conn)
+}
+
+fn operation_journal(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS operations (
+           id INTEGER PRIMARY KEY AUTOINCREMENT,
+           label TEXT NOT NULL,
+           createdAt TEXT NOT NULL,
+           undoneAt TEXT
+         );
+         CREATE TABLE IF NOT EXISTS operation_changes (
+           id INTEGER PRIMARY KEY AUTOINCREMENT,
+           operationId INTEGER NOT NULL REFERENCES operations(id) ON DELETE CASCADE,
+           entityType TEXT NOT NULL,
+           entityId TEXT NOT NULL,
+           before TEXT,
+           after TEXT
+         );
+         CREATE INDEX IF NOT EXISTS idx_operation_changes_operation ON operation_changes(operationId);
+         CREATE TABLE IF NOT EXISTS journal_cursor (
+           id INTEGER PRIMARY KEY CHECK (id = 1),
+           operationId INTEGER
+         );",
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn entity_history(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS entity_history (
+           id INTEGER PRIMARY KEY AUTOINCREMENT,
+           entityType TEXT NOT NULL,
+           entityId TEXT NOT NULL,
+           kind TEXT NOT NULL,
+           changedAt TEXT,
+           recordedAt TEXT NOT NULL,
+           deviceId TEXT,
+           source TEXT,
+           changes TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_entity_history_entity ON entity_history(entityId);",
+    )
+    .map_err(|e| e.to_string())?;
+    history::device_id(conn).map(|_| ())
+}
+
+fn entity_extra_columns(conn: &Connection) -> Result<(), String> {
+    for table in ["tasks", "projects", "sections", "areas"] {
+        add_column_if_missing(conn, table, "extra", "TEXT")?;
+    }
+    Ok(())
+}
+
+fn area_lifecycle_columns(conn: &Connection) -> Result<(), String> {
+    // SQLite can't tighten a column to NOT NULL in place, so the table is rebuilt. Rows keep
+    // their rowid, which the FTS index is keyed on; dropping the old table takes its triggers
+    // with it, which the FTS rebuild and trigger regeneration after migrating put back.
+    conn.execute_batch(&format!(
+        "CREATE TABLE areas_new (
+           id TEXT PRIMARY KEY,
+           name TEXT NOT NULL,
+           color TEXT,
+           icon TEXT,
+           orderNum INTEGER NOT NULL,
+           isArchived INTEGER NOT NULL DEFAULT 0,
+           createdAt TEXT NOT NULL,
+           updatedAt TEXT NOT NULL,
+           deletedAt TEXT,
+           extra TEXT
+         );
+         INSERT INTO areas_new (rowid, id, name, color, icon, orderNum, createdAt, updatedAt, extra)
+         SELECT rowid, id, name, color, icon, orderNum,
+                coalesce(createdAt, updatedAt, '{unknown}'), coalesce(updatedAt, createdAt, '{unknown}'), extra
+         FROM areas;
+         DROP TABLE areas;
+         ALTER TABLE areas_new RENAME TO areas;",
+        unknown = UNKNOWN_TIMESTAMP
+    ))
+    .map_err(|e| e.to_string())?;
+    fts::mark_stale(conn)
+}
+
+fn task_and_project_columns(conn: &Connection) -> Result<(), String> {
+    // Databases created before these columns joined the base schema gain them here; newer ones
+    // already have them and only pick up the indexes.
+    add_column_if_missing(conn, "tasks", "purgedAt", "TEXT")?;
+    add_column_if_missing(conn, "tasks", "orderNum", "INTEGER")?;
+    add_column_if_missing(conn, "tasks", "areaId", "TEXT")?;
+    add_column_if_missing(conn, "tasks", "sectionId", "TEXT")?;
+    add_column_if_missing(conn, "projects", "orderNum", "INTEGER")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_tasks_area_id ON tasks(areaId);
+         CREATE INDEX IF NOT EXISTS idx_tasks_section_id ON tasks(sectionId);
+         CREATE INDEX IF NOT EXISTS idx_projects_area_order ON projects(areaId, orderNum);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?;
+    for col in columns {
+        if col.map_err(|e| e.to_string())? == column {
+            return Ok(());
+        }
+    }
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database as a build that stopped at version 2 left it: the base tables and nothing later.
+    fn v2_database() -> Connection {
+        let conn = Connection::open_in_memory().expect("open");
+        conn.execute_batch(BASE_SCHEMA).expect("base schema");
+        conn.execute_batch(
+            "CREATE TABLE schema_migrations (version INTEGER PRIMARY KEY);
+             INSERT INTO schema_migrations (version) VALUES (1), (2);
+             INSERT INTO settings (id, data) VALUES (1, '{\"language\":\"en\"}');
+             INSERT INTO areas (id, name, orderNum) VALUES ('a1', 'Home', 0);
+             INSERT INTO projects (id, title, status, color, areaId, createdAt, updatedAt)
+             VALUES ('p1', 'Kitchen', 'active', '#000000', 'a1',
+                     '2024-01-01T00:00:00.000Z', '2024-01-01T00:00:00.000Z');
+             INSERT INTO tasks (id, title, status, description, projectId, createdAt, updatedAt)
+             VALUES ('t1', 'Buy groceries', 'next', 'milk and eggs', 'p1',
+                     '2024-01-01T00:00:00.000Z', '2024-01-02T00:00:00.000Z');",
+        )
+        .expect("seed");
+        conn
+    }
+
+    fn task_row(conn: &Connection) -> (String, String, Option<String>, String) {
+        conn.query_row(
+            "SELECT title, status, description, updatedAt FROM tasks WHERE id = 't1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .expect("task")
+    }
+
+    #[test]
+    fn upgrades_a_v2_database_keeping_its_data() {
+        let mut conn = v2_database();
+        let before = task_row(&conn);
+
+        migrate(&mut conn).expect("migrate");
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(task_row(&conn), before);
+        let project: (String, Option<String>) = conn
+            .query_row("SELECT title, areaId FROM projects WHERE id = 'p1'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(project, ("Kitchen".to_string(), Some("a1".to_string())));
+        let area: (String, String) = conn
+            .query_row("SELECT name, createdAt FROM areas WHERE id = 'a1'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(area, ("Home".to_string(), UNKNOWN_TIMESTAMP.to_string()));
+        let matches: i64 = conn
+            .query_row("SELECT count(*) FROM tasks_fts WHERE tasks_fts MATCH 'groceries'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(matches, 1, "the FTS index is rebuilt from the existing rows");
+    }
+
+    #[test]
+    fn migrating_at_head_changes_nothing() {
+        let mut conn = v2_database();
+        migrate(&mut conn).expect("first migrate");
+        let versions: i64 = conn
+            .query_row("SELECT count(*) FROM schema_migrations", [], |row| row.get(0))
+            .unwrap();
+        let schema = |conn: &Connection| -> Vec<String> {
+            let mut stmt = conn
+                .prepare("SELECT coalesce(sql, name) FROM sqlite_master ORDER BY type, name")
+                .unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<Result<_, _>>().unwrap()
+        };
+        let before = (schema(&conn), task_row(&conn));
+
+        migrate(&mut conn).expect("second migrate");
+
+        assert_eq!((schema(&conn), task_row(&conn)), before);
+        let versions_after: i64 = conn
+            .query_row("SELECT count(*) FROM schema_migrations", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(versions_after, versions);
+    }
+
+    #[test]
+    fn refuses_a_database_from_a_newer_build() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).expect("migrate");
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (?1)", [latest_version() + 1])
+            .unwrap();
+
+        let error = migrate(&mut conn).unwrap_err();
+
+        assert!(error.contains("newer than this version"), "{}", error);
+        assert_eq!(current_version(&conn).unwrap(), latest_version() + 1);
+    }
+}
@@ -0,0 +1,369 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+macro_rules! string_enum {
+    ($name:ident { $($variant:ident => $text:literal),+ $(,)? }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum $name {
+            $(#[serde(rename = $text)] $variant,)+
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text,)+
+                }
+            }
+
+            pub fn parse(raw: &str) -> Option<Self> {
+                match raw {
+                    $($text => Some($name::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+
+        impl ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.as_str()))
+            }
+        }
+
+        impl FromSql for $name {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                let raw = value.as_str()?;
+                $name::parse(raw).ok_or_else(|| {
+                    FromSqlError::Other(format!("unknown {} `{}`", stringify!($name), raw).into())
+                })
+            }
+        }
+    };
+}
+
+string_enum!(TaskStatus {
+    Inbox => "inbox",
+    Next => "next",
+    Waiting => "waiting",
+    Someday => "someday",
+    Reference => "reference",
+    Done => "done",
+    Archived => "archived",
+});
+
+string_enum!(TaskPriority {
+    Low => "low",
+    Medium => "medium",
+    High => "high",
+    Urgent => "urgent",
+});
+
+string_enum!(TaskMode {
+    Task => "task",
+    List => "list",
+});
+
+string_enum!(ProjectStatus {
+    Active => "active",
+    Someday => "someday",
+    Waiting => "waiting",
+    Archived => "archived",
+});
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Stands in for timestamps that older data never recorded. It sorts before any real time, so
+/// a copy with a known timestamp wins last-write-wins merges.
+pub const UNKNOWN_TIMESTAMP: &str = "1970-01-01T00:00:00.000Z";
+
+fn unknown_timestamp() -> String {
+    UNKNOWN_TIMESTAMP.to_string()
+}
+
+/// A task as stored by the desktop backend. Recurrence, checklist and attachments are kept as
+/// opaque JSON because their shape is owned by the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub id: String,
+    pub title: String,
+    pub status: TaskStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<TaskPriority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_mode: Option<TaskMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push_count: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub contexts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checklist: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_num: Option<i64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_focused_today: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_estimate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<String>,
+    /// Fields this build doesn't know, stored in the `extra` column so data written by newer
+    /// clients survives a round trip through this backend. A migration that promotes one of them
+    /// to a real column must also move it out of `extra`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub id: String,
+    pub title: String,
+    pub status: ProjectStatus,
+    pub color: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    #[serde(default)]
+    pub tag_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_sequential: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_focused: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub support_notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area_title: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<String>,
+    /// See `Task::extra`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Section {
+    pub id: String,
+    pub project_id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_collapsed: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<String>,
+    /// See `Task::extra`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Area {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    pub order: i64,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_archived: bool,
+    #[serde(default = "unknown_timestamp")]
+    pub created_at: String,
+    #[serde(default = "unknown_timestamp")]
+    pub updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// See `Task::extra`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Common accessors used when diffing stored rows against incoming entities.
+pub trait Entity {
+    fn id(&self) -> &str;
+    fn updated_at(&self) -> Option<&str>;
+    fn deleted_at(&self) -> Option<&str>;
+}
+
+impl Entity for Task {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        Some(&self.updated_at)
+    }
+    fn deleted_at(&self) -> Option<&str> {
+        self.deleted_at.as_deref()
+    }
+}
+
+impl Entity for Project {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        Some(&self.updated_at)
+    }
+    fn deleted_at(&self) -> Option<&str> {
+        self.deleted_at.as_deref()
+    }
+}
+
+impl Entity for Section {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        Some(&self.updated_at)
+    }
+    fn deleted_at(&self) -> Option<&str> {
+        self.deleted_at.as_deref()
+    }
+}
+
+impl Area {
+    /// Copies whichever of `createdAt`/`updatedAt` was recorded into the one that wasn't, as the
+    /// schema migration does for stored areas.
+    fn fill_unknown_timestamps(&mut self) {
+        if self.updated_at == UNKNOWN_TIMESTAMP {
+            self.updated_at = self.created_at.clone();
+        } else if self.created_at == UNKNOWN_TIMESTAMP {
+            self.created_at = self.updated_at.clone();
+        }
+    }
+}
+
+impl Entity for Area {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        Some(&self.updated_at)
+    }
+    fn deleted_at(&self) -> Option<&str> {
+        self.deleted_at.as_deref()
+    }
+}
+
+/// User settings are an open document owned by the frontend and stored verbatim.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Settings(pub Map<String, Value>);
+
+impl Settings {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppData {
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub sections: Vec<Section>,
+    #[serde(default)]
+    pub areas: Vec<Area>,
+    #[serde(default)]
+    pub settings: Settings,
+}
+
+/// Deserializes one entity, prefixing errors with where it sits in the document so a bad field
+/// can be found, e.g. `tasks[3] (id "abc"): missing field `createdAt``.
+pub fn parse_entity<T: for<'de> Deserialize<'de>>(value: Value, location: &str) -> Result<T, String> {
+    let id = value
+        .get("id")
+        .and_then(|v| v.as_str())
+        .map(|id| format!(" (id \"{}\")", id))
+        .unwrap_or_default();
+    serde_json::from_value(value).map_err(|e| format!("{}{}: {}", location, id, e))
+}
+
+fn parse_entity_list<T: for<'de> Deserialize<'de>>(map: &mut Map<String, Value>, key: &str) -> Result<Vec<T>, String> {
+    match map.remove(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| parse_entity(item, &format!("{}[{}]", key, index)))
+            .collect(),
+        Some(_) => Err(format!("{}: expected an array", key)),
+    }
+}
+
+impl AppData {
+    /// Parses a whole data document with per-entity error locations.
+    pub fn from_value(value: Value) -> Result<AppData, String> {
+        let Value::Object(mut map) = value else {
+            return Err("data: expected an object".to_string());
+        };
+        let tasks = parse_entity_list(&mut map, "tasks")?;
+        let projects = parse_entity_list(&mut map, "projects")?;
+        let sections = parse_entity_list(&mut map, "sections")?;
+        let mut areas: Vec<Area> = parse_entity_list(&mut map, "areas")?;
+        for area in &mut areas {
+            area.fill_unknown_timestamps();
+        }
+        let settings = match map.remove("settings") {
+            None | Some(Value::Null) => Settings::default(),
+            Some(Value::Object(settings)) => Settings(settings),
+            Some(_) => return Err("settings: expected an object".to_string()),
+        };
+        Ok(AppData {
+            tasks,
+            projects,
+            sections,
+            areas,
+            settings,
+        })
+    }
+}
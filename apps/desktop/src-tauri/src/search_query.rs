@@ -0,0 +1,641 @@
+//! Search query language shared by `search_fts`.
+//!
+//! The grammar follows the core `parseSearchQuery`: whitespace separated terms are ANDed, `OR`
+//! splits the query into alternatives, and a leading `-` negates a term. On top of plain words
+//! (prefix matched) it understands:
+//!
+//! - `"exact phrase"`
+//! - `tag:name` / `#name` and `context:name` / `@name`, matching nested values like `#work/client`
+//! - `project:name` (project id or part of its title)
+//! - `status:next`
+//! - `due:<7d`, `start:>=2024-05-01`, `review:today`, `created:<=1w` with `<`, `<=`, `>`, `>=`, `=`
+//!   against `today`, `tomorrow`, `N[d|w|m|y]` from today or an ISO date, compared by day
+//! - `is:focused`
+//!
+//! Text terms compile to FTS5 MATCH expressions, everything else to SQL predicates. With the
+//! trigram tokenizer, terms shorter than three characters fall back to LIKE.
+
+use chrono::{Days, Months, NaiveDate};
+use rusqlite::ToSql;
+use serde::Serialize;
+
+use crate::fts::TRIGRAM_MIN_CHARS;
+use crate::model::TaskStatus;
+use crate::tags;
+
+/// Points at the part of the query that could not be understood. Offsets are in characters.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryError {
+    pub message: String,
+    pub token: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Tag(String),
+    Context(String),
+    Project(String),
+    Status(TaskStatus),
+    Date {
+        column: &'static str,
+        comparator: &'static str,
+        day: String,
+    },
+    Focused,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TermKind {
+    /// A word (prefix matched) or a quoted phrase.
+    Text { text: String, prefix: bool },
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    negated: bool,
+    kind: TermKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Alternatives separated by `OR`; the terms inside each one are ANDed.
+    clauses: Vec<Vec<Term>>,
+}
+
+struct Token {
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> QueryError {
+        QueryError {
+            message: message.into(),
+            token: self.text.clone(),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        if chars[index].is_whitespace() {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        let mut in_quotes = false;
+        let mut quote_start = 0;
+        while index < chars.len() && (in_quotes || !chars[index].is_whitespace()) {
+            if chars[index] == '"' {
+                in_quotes = !in_quotes;
+                quote_start = index;
+            }
+            index += 1;
+        }
+        if in_quotes {
+            return Err(QueryError {
+                message: "Unterminated quote".to_string(),
+                token: chars[quote_start..].iter().collect(),
+                start: quote_start,
+                end: chars.len(),
+            });
+        }
+        tokens.push(Token {
+            text: chars[start..index].iter().collect(),
+            start,
+            end: index,
+        });
+    }
+    Ok(tokens)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn fts_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn has_searchable_text(text: &str) -> bool {
+    text.chars().any(|ch| ch.is_alphanumeric())
+}
+
+fn with_prefix(value: &str, prefix: char) -> String {
+    if value.starts_with(prefix) {
+        value.to_string()
+    } else {
+        format!("{}{}", prefix, value)
+    }
+}
+
+fn resolve_day(raw: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let raw = raw.trim().to_lowercase();
+    match raw.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return today.checked_add_days(Days::new(1)),
+        _ => {}
+    }
+    if let Some(date) = raw.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) {
+        return Some(date);
+    }
+    let digits_end = raw.find(|ch: char| !ch.is_ascii_digit())?;
+    let amount: u32 = raw[..digits_end].parse().ok()?;
+    match raw[digits_end..].trim() {
+        "d" | "day" | "days" => today.checked_add_days(Days::new(amount as u64)),
+        "w" | "week" | "weeks" => today.checked_add_days(Days::new(amount as u64 * 7)),
+        "m" | "month" | "months" => today.checked_add_months(Months::new(amount)),
+        "y" | "year" | "years" => today.checked_add_months(Months::new(amount.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+fn parse_date_filter(column: &'static str, value: &str, token: &Token, today: NaiveDate) -> Result<Filter, QueryError> {
+    let (comparator, rest) = ["<=", ">=", "<", ">", "="]
+        .iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("=", value));
+    let day = resolve_day(rest, today).ok_or_else(|| {
+        token.error(format!(
+            "Unknown date `{}`; use today, tomorrow, a count like 7d/2w/3m/1y or YYYY-MM-DD",
+            rest
+        ))
+    })?;
+    Ok(Filter::Date {
+        column,
+        comparator,
+        day: day.format("%Y-%m-%d").to_string(),
+    })
+}
+
+/// Parses one term. Unknown `field:` prefixes are searched as text, like the core filter does.
+fn parse_term(token: &Token, today: NaiveDate) -> Result<Option<Term>, QueryError> {
+    let (negated, body) = match token.text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token.text.as_str()),
+    };
+    if body.is_empty() {
+        return Err(token.error("`-` must be followed by a term to exclude"));
+    }
+
+    let filter = if let Some(tag) = body.strip_prefix('#') {
+        Some(("tag", tag))
+    } else if let Some(context) = body.strip_prefix('@') {
+        Some(("context", context))
+    } else if !body.starts_with('"') {
+        body.split_once(':').filter(|(field, _)| !field.is_empty())
+    } else {
+        None
+    };
+
+    if let Some((field, raw_value)) = filter {
+        let value = unquote(raw_value);
+        let field = field.to_lowercase();
+        let known = matches!(
+            field.as_str(),
+            "tag" | "tags" | "context" | "contexts" | "project" | "status" | "due" | "start" | "review" | "created" | "is"
+        );
+        if known {
+            if value.trim().is_empty() {
+                return Err(token.error(format!("`{}:` needs a value", field)));
+            }
+            let filter = match field.as_str() {
+                "tag" | "tags" => Filter::Tag(with_prefix(value, '#')),
+                "context" | "contexts" => Filter::Context(with_prefix(value, '@')),
+                "project" => Filter::Project(value.to_string()),
+                "status" => Filter::Status(
+                    TaskStatus::parse(&value.to_lowercase())
+                        .ok_or_else(|| token.error(format!("Unknown status `{}`", value)))?,
+                ),
+                "due" => parse_date_filter("dueDate", value, token, today)?,
+                "start" => parse_date_filter("startTime", value, token, today)?,
+                "review" => parse_date_filter("reviewAt", value, token, today)?,
+                "created" => parse_date_filter("createdAt", value, token, today)?,
+                _ => match value.to_lowercase().as_str() {
+                    "focused" => Filter::Focused,
+                    _ => return Err(token.error(format!("Unknown `is:{}`; supported: is:focused", value))),
+                },
+            };
+            return Ok(Some(Term {
+                negated,
+                kind: TermKind::Filter(filter),
+            }));
+        }
+    }
+
+    let (text, prefix) = if body.starts_with('"') {
+        (unquote(body), false)
+    } else {
+        (body, true)
+    };
+    if !has_searchable_text(text) {
+        return Ok(None);
+    }
+    Ok(Some(Term {
+        negated,
+        kind: TermKind::Text {
+            text: text.to_string(),
+            prefix,
+        },
+    }))
+}
+
+/// Parses a query, resolving relative dates against `today`.
+pub fn parse(input: &str, today: NaiveDate) -> Result<SearchQuery, QueryError> {
+    let tokens = tokenize(input)?;
+    let mut clauses: Vec<Vec<Term>> = Vec::new();
+    let mut current: Vec<Term> = Vec::new();
+    let mut pending_or: Option<&Token> = None;
+
+    for token in &tokens {
+        if matches!(token.text.as_str(), "OR" | "|" | "||") {
+            if current.is_empty() {
+                return Err(token.error("`OR` needs a search term on both sides"));
+            }
+            clauses.push(std::mem::take(&mut current));
+            pending_or = Some(token);
+            continue;
+        }
+        if let Some(term) = parse_term(token, today)? {
+            current.push(term);
+            pending_or = None;
+        }
+    }
+    if let Some(token) = pending_or {
+        return Err(token.error("`OR` needs a search term on both sides"));
+    }
+    if !current.is_empty() {
+        clauses.push(current);
+    }
+    Ok(SearchQuery { clauses })
+}
+
+impl SearchQuery {
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// True when the query uses task fields, which rules out project results.
+    pub fn has_filters(&self) -> bool {
+        self.clauses
+            .iter()
+            .flatten()
+            .any(|term| matches!(term.kind, TermKind::Filter(_)))
+    }
+
+    /// A single FTS expression covering every alternative, used to rank results and build
+    /// snippets. It only exists when each alternative has at least one positive text term the
+    /// index can answer; otherwise some matches would not come from the FTS index at all.
+    pub fn rank_expression(&self, trigram: bool) -> Option<String> {
+        let mut alternatives = Vec::new();
+        for clause in &self.clauses {
+            let positives: Vec<String> = clause
+                .iter()
+                .filter(|term| !term.negated)
+                .filter_map(|term| match &term.kind {
+                    TermKind::Text { text, prefix } => fts_expression(text, *prefix, trigram),
+                    TermKind::Filter(_) => None,
+                })
+                .collect();
+            if positives.is_empty() {
+                return None;
+            }
+            alternatives.push(format!("({})", positives.join(" AND ")));
+        }
+        if alternatives.is_empty() {
+            None
+        } else {
+            Some(alternatives.join(" OR "))
+        }
+    }
+
+    /// SQL predicate for the whole query against `index`. Parameters are appended to `params`
+    /// in placeholder order.
+    pub fn predicate(&self, index: &TextIndex<'_>, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        let clauses: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                let terms: Vec<String> = clause
+                    .iter()
+                    .map(|term| term_predicate(term, index, params))
+                    .collect();
+                format!("({})", terms.join(" AND "))
+            })
+            .collect();
+        format!("({})", clauses.join(" OR "))
+    }
+}
+
+/// An FTS index and the table it covers, as seen by a search query.
+pub struct TextIndex<'a> {
+    pub fts_table: &'a str,
+    /// Alias of the source table in the surrounding query.
+    pub alias: &'a str,
+    /// Text columns of the FTS table.
+    pub columns: &'a [&'a str],
+    pub trigram: bool,
+}
+
+/// The FTS5 expression for a word or phrase, or None when the index can't match it. Trigram
+/// matches substrings anywhere, so words need no prefix marker there, but terms shorter than
+/// three characters produce no trigrams at all.
+fn fts_expression(text: &str, prefix: bool, trigram: bool) -> Option<String> {
+    if trigram {
+        if text.chars().count() < TRIGRAM_MIN_CHARS {
+            None
+        } else {
+            Some(fts_string(text))
+        }
+    } else if prefix {
+        Some(format!("{}*", fts_string(text)))
+    } else {
+        Some(fts_string(text))
+    }
+}
+
+/// Terms the trigram index can't answer are matched with LIKE over the indexed text.
+fn like_predicate(text: &str, index: &TextIndex<'_>, negated: bool, params: &mut Vec<Box<dyn ToSql>>) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let pattern = format!("%{}%", escaped);
+    let columns: Vec<String> = index
+        .columns
+        .iter()
+        .map(|column| {
+            params.push(Box::new(pattern.clone()));
+            format!("{} LIKE ? ESCAPE '\\'", column)
+        })
+        .collect();
+    format!(
+        "{alias}.rowid {not}IN (SELECT rowid FROM {fts} WHERE {columns})",
+        alias = index.alias,
+        not = if negated { "NOT " } else { "" },
+        fts = index.fts_table,
+        columns = columns.join(" OR ")
+    )
+}
+
+fn term_predicate(term: &Term, index: &TextIndex<'_>, params: &mut Vec<Box<dyn ToSql>>) -> String {
+    let alias = index.alias;
+    match &term.kind {
+        TermKind::Text { text, prefix } => match fts_expression(text, *prefix, index.trigram) {
+            Some(expression) => {
+                params.push(Box::new(expression));
+                format!(
+                    "{alias}.rowid {not}IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?)",
+                    alias = alias,
+                    not = if term.negated { "NOT " } else { "" },
+                    fts = index.fts_table
+                )
+            }
+            None => like_predicate(text, index, term.negated, params),
+        },
+        TermKind::Filter(filter) => {
+            let predicate = match filter {
+                Filter::Tag(tag) => tags::task_filter(alias, false, tag, true, params),
+                Filter::Context(context) => tags::task_filter(alias, true, context, true, params),
+                Filter::Project(project) => {
+                    params.push(Box::new(project.clone()));
+                    params.push(Box::new(project.clone()));
+                    format!(
+                        "{alias}.projectId = ? OR {alias}.projectId IN \
+                         (SELECT id FROM projects WHERE instr(lower(title), lower(?)) > 0)",
+                        alias = alias
+                    )
+                }
+                Filter::Status(status) => {
+                    params.push(Box::new(*status));
+                    format!("{}.status = ?", alias)
+                }
+                Filter::Date { column, comparator, day } => {
+                    params.push(Box::new(day.clone()));
+                    format!("substr({}.{}, 1, 10) {} ?", alias, column, comparator)
+                }
+                Filter::Focused => format!("{}.isFocusedToday = 1", alias),
+            };
+            // Missing values compare as NULL; treat them as "no match" so negation includes them.
+            if term.negated {
+                format!("NOT coalesce(({}), 0)", predicate)
+            } else {
+                format!("coalesce(({}), 0)", predicate)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params_from_iter, Connection};
+
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 10).unwrap()
+    }
+
+    fn clauses(input: &str) -> Vec<Vec<Term>> {
+        parse(input, today()).expect("query parses").clauses
+    }
+
+    fn error(input: &str) -> QueryError {
+        parse(input, today()).expect_err("query is rejected")
+    }
+
+    fn text(text: &str, prefix: bool) -> Term {
+        Term {
+            negated: false,
+            kind: TermKind::Text {
+                text: text.to_string(),
+                prefix,
+            },
+        }
+    }
+
+    fn filter(filter: Filter) -> Term {
+        Term {
+            negated: false,
+            kind: TermKind::Filter(filter),
+        }
+    }
+
+    fn date(column: &'static str, comparator: &'static str, day: &str) -> Term {
+        filter(Filter::Date {
+            column,
+            comparator,
+            day: day.to_string(),
+        })
+    }
+
+    fn negated(term: Term) -> Term {
+        Term { negated: true, ..term }
+    }
+
+    #[test]
+    fn words_are_prefix_matched_and_phrases_exact() {
+        assert_eq!(
+            clauses(r#"buy "milk carton""#),
+            vec![vec![text("buy", true), text("milk carton", false)]]
+        );
+        // Terms without letters or digits match nothing in the index and are dropped.
+        assert_eq!(clauses("  -- ... !!"), Vec::<Vec<Term>>::new());
+    }
+
+    #[test]
+    fn minus_negates_and_or_splits_alternatives() {
+        assert_eq!(
+            clauses(r#"report -draft OR invoice | -"old copy""#),
+            vec![
+                vec![text("report", true), negated(text("draft", true))],
+                vec![text("invoice", true)],
+                vec![negated(text("old copy", false))],
+            ]
+        );
+    }
+
+    #[test]
+    fn field_filters() {
+        assert_eq!(
+            clauses("tag:work #home/garden context:phone @office"),
+            vec![vec![
+                filter(Filter::Tag("#work".to_string())),
+                filter(Filter::Tag("#home/garden".to_string())),
+                filter(Filter::Context("@phone".to_string())),
+                filter(Filter::Context("@office".to_string())),
+            ]]
+        );
+        assert_eq!(
+            clauses(r#"project:"Big Move" status:Next is:focused -is:FOCUSED"#),
+            vec![vec![
+                filter(Filter::Project("Big Move".to_string())),
+                filter(Filter::Status(TaskStatus::Next)),
+                filter(Filter::Focused),
+                negated(filter(Filter::Focused)),
+            ]]
+        );
+        // Unknown fields are searched as text, like the core filter does.
+        assert_eq!(clauses("foo:bar"), vec![vec![text("foo:bar", true)]]);
+    }
+
+    #[test]
+    fn date_filters_resolve_against_today() {
+        assert_eq!(
+            clauses("due:<7d start:>=2024-05-01 review:today created:<=1w due:>tomorrow created:=2m start:1y"),
+            vec![vec![
+                date("dueDate", "<", "2024-05-17"),
+                date("startTime", ">=", "2024-05-01"),
+                date("reviewAt", "=", "2024-05-10"),
+                date("createdAt", "<=", "2024-05-17"),
+                date("dueDate", ">", "2024-05-11"),
+                date("createdAt", "=", "2024-07-10"),
+                date("startTime", "=", "2025-05-10"),
+            ]]
+        );
+    }
+
+    #[test]
+    fn unclosed_quote_points_at_the_quote() {
+        assert_eq!(
+            error(r#"café "milk carton"#),
+            QueryError {
+                message: "Unterminated quote".to_string(),
+                token: r#""milk carton"#.to_string(),
+                start: 5,
+                end: 17,
+            }
+        );
+    }
+
+    #[test]
+    fn dangling_or_is_rejected() {
+        let trailing = error("milk OR");
+        assert_eq!((trailing.token.as_str(), trailing.start, trailing.end), ("OR", 5, 7));
+        let leading = error("OR milk");
+        assert_eq!((leading.token.as_str(), leading.start, leading.end), ("OR", 0, 2));
+        let doubled = error("milk OR || eggs");
+        assert_eq!((doubled.token.as_str(), doubled.start, doubled.end), ("||", 8, 10));
+    }
+
+    #[test]
+    fn bad_filter_values_are_rejected_with_their_position() {
+        let bad_date = error("milk due:<soon");
+        assert!(bad_date.message.contains("Unknown date `soon`"), "{}", bad_date.message);
+        assert_eq!((bad_date.token.as_str(), bad_date.start, bad_date.end), ("due:<soon", 5, 14));
+
+        assert!(error("due:2024-13-01").message.contains("Unknown date"));
+        assert!(error("status:later").message.contains("Unknown status `later`"));
+        assert!(error("is:starred").message.contains("supported: is:focused"));
+        assert_eq!(error("tag:").message, "`tag:` needs a value");
+        let bare_minus = error("milk -");
+        assert_eq!((bare_minus.start, bare_minus.end), (5, 6));
+    }
+
+    fn connection() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, title, status, color, createdAt, updatedAt)
+             VALUES ('p1', 'Big Move', 'active', '#000', '2024-01-01', '2024-01-01');
+             INSERT INTO tasks (id, title, status, description, tags, contexts, projectId, dueDate,
+                                isFocusedToday, createdAt, updatedAt) VALUES
+               ('t1', 'Pack kitchen boxes', 'next', 'fragile plates', '[\"#home/kitchen\"]', '[\"@home\"]',
+                'p1', '2024-05-12', 1, '2024-05-01T08:00:00.000Z', '2024-05-01T08:00:00.000Z'),
+               ('t2', 'Call the landlord', 'waiting', NULL, '[\"#work\"]', '[\"@phone\"]',
+                NULL, '2024-06-01', 0, '2024-04-01T08:00:00.000Z', '2024-04-01T08:00:00.000Z'),
+               ('t3', 'Packing list draft', 'inbox', 'old copy', '[]', '[]',
+                NULL, NULL, NULL, '2024-03-01T08:00:00.000Z', '2024-03-01T08:00:00.000Z');",
+        )
+        .unwrap();
+        conn
+    }
+
+    /// Ids of the tasks `input` selects, in id order.
+    fn matching(conn: &Connection, input: &str) -> Vec<String> {
+        let query = parse(input, today()).unwrap();
+        let index = TextIndex {
+            fts_table: "tasks_fts",
+            alias: "t",
+            columns: &["title", "description", "tags", "contexts", "checklist", "attachments"],
+            trigram: false,
+        };
+        let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+        let predicate = query.predicate(&index, &mut params);
+        let mut stmt = conn
+            .prepare(&format!("SELECT t.id FROM tasks t WHERE {} ORDER BY t.id", predicate))
+            .unwrap();
+        let rows = stmt
+            .query_map(params_from_iter(params.iter().map(|p| p.as_ref())), |row| row.get(0))
+            .unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn predicates_select_the_matching_tasks() {
+        let conn = connection();
+        assert_eq!(matching(&conn, "pack"), ["t1", "t3"]);
+        assert_eq!(matching(&conn, r#""fragile plates""#), ["t1"]);
+        assert_eq!(matching(&conn, "pack -draft"), ["t1"]);
+        assert_eq!(matching(&conn, "landlord OR draft"), ["t2", "t3"]);
+        assert_eq!(matching(&conn, "#home"), ["t1"]);
+        assert_eq!(matching(&conn, "@phone"), ["t2"]);
+        assert_eq!(matching(&conn, "project:move"), ["t1"]);
+        assert_eq!(matching(&conn, "status:waiting"), ["t2"]);
+        assert_eq!(matching(&conn, "due:<=7d"), ["t1"]);
+        assert_eq!(matching(&conn, "-due:<=7d"), ["t2", "t3"]);
+        assert_eq!(matching(&conn, "created:>=2024-04-01"), ["t1", "t2"]);
+        assert_eq!(matching(&conn, "is:focused"), ["t1"]);
+        assert_eq!(matching(&conn, "-is:focused"), ["t2", "t3"]);
+    }
+}
@@ -0,0 +1,471 @@
+//! Three-way merge of a sync document being written with the copy another device wrote since it
+//! was read.
+//!
+//! Each entity is compared with its version in the document as this device last wrote it (the
+//! base), so an entity changed on only one side takes that side's version, and one missing from a
+//! side was either added by the other side or removed there. Only entities changed on both sides
+//! are conflicts, settled by `updatedAt` with the same tombstone rule as the frontend merge in
+//! `packages/core`. Versions are `updatedAt` values, as in `apply_entity_diff`.
+
+use chrono::DateTime;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::app_meta;
+use crate::model::{AppData, Area, Entity, Project, Section, Task};
+
+const BASE_KEY: &str = "sync_base";
+
+/// Differences in `updatedAt` within which a deletion beats an edit made before it, as
+/// `CLOCK_SKEW_THRESHOLD_MS` in the frontend merge.
+const CLOCK_SKEW_MS: i64 = 5 * 60 * 1000;
+
+/// Entity versions at the end of the last successful sync with `target`, by list and id.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Base {
+    pub target: String,
+    #[serde(default)]
+    pub versions: HashMap<String, HashMap<String, String>>,
+}
+
+impl Base {
+    /// The versions of the entities in `data`.
+    pub fn of(target: &str, data: &AppData) -> Base {
+        let versions = [
+            ("tasks", versions_of(&data.tasks)),
+            ("projects", versions_of(&data.projects)),
+            ("sections", versions_of(&data.sections)),
+            ("areas", versions_of(&data.areas)),
+        ];
+        Base {
+            target: target.to_string(),
+            versions: versions
+                .into_iter()
+                .map(|(key, versions)| (key.to_string(), versions))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// Entities a sync added to, changed on or deleted from one side. Moving an entity to the trash
+/// counts as a deletion.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.updated == 0 && self.deleted == 0
+    }
+}
+
+/// An entity changed on both sides since the last sync, and the side whose version was kept.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub entity_type: &'static str,
+    pub id: String,
+    pub kept: Side,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSummary {
+    pub local: Changes,
+    pub remote: Changes,
+    pub conflicts: Vec<Conflict>,
+    /// Whether the merge used a base from an earlier sync with the same target. Without one,
+    /// nothing is treated as removed and every difference is settled by `updatedAt`.
+    pub three_way: bool,
+}
+
+pub struct Merged {
+    pub data: AppData,
+    pub summary: SyncSummary,
+}
+
+/// Lets an entity keep device-specific state from its local copy when the remote copy wins.
+trait Merge: Entity + Clone + PartialEq {
+    fn keep_local_state(&mut self, _local: &Self) {}
+}
+
+impl Merge for Task {
+    fn keep_local_state(&mut self, local: &Self) {
+        keep_local_attachment_paths(&mut self.attachments, &local.attachments);
+    }
+}
+
+impl Merge for Project {
+    fn keep_local_state(&mut self, local: &Self) {
+        keep_local_attachment_paths(&mut self.attachments, &local.attachments);
+    }
+}
+
+impl Merge for Section {}
+
+impl Merge for Area {}
+
+/// File attachments point at a path on this device; keep it, and its download status, for
+/// attachments the local copy already has.
+fn keep_local_attachment_paths(winner: &mut Option<Value>, local: &Option<Value>) {
+    let (Some(Value::Array(winner)), Some(Value::Array(local))) = (winner, local) else {
+        return;
+    };
+    for attachment in winner.iter_mut() {
+        let Some(id) = attachment.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        let Some(local) = local.iter().find(|item| item.get("id").and_then(Value::as_str) == Some(id)) else {
+            continue;
+        };
+        if attachment.get("kind").and_then(Value::as_str) != Some("file")
+            || local.get("kind").and_then(Value::as_str) != Some("file")
+        {
+            continue;
+        }
+        let Some(attachment) = attachment.as_object_mut() else {
+            continue;
+        };
+        for key in ["uri", "localStatus"] {
+            match local.get(key) {
+                Some(value) => attachment.insert(key.to_string(), value.clone()),
+                None => attachment.remove(key),
+            };
+        }
+    }
+}
+
+fn version<T: Entity>(item: &T) -> Option<&str> {
+    item.updated_at()
+}
+
+fn millis(raw: Option<&str>) -> i64 {
+    raw.and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+        .map(|parsed| parsed.timestamp_millis())
+        .unwrap_or(0)
+}
+
+/// Picks between two copies changed since the last sync: the newer `updatedAt` wins, except that
+/// when the two are close and only one copy is deleted, the deletion wins unless the other copy
+/// was edited after it. Ties go to the remote copy.
+fn newer<T: Entity>(local: &T, remote: &T) -> Side {
+    let local_time = millis(local.updated_at());
+    let remote_time = millis(remote.updated_at());
+    let local_deleted = local.deleted_at().is_some();
+    if (remote_time - local_time).abs() <= CLOCK_SKEW_MS && local_deleted != remote.deleted_at().is_some() {
+        let (deleted, live) = if local_deleted { (local, remote) } else { (remote, local) };
+        let deletion_wins = millis(deleted.deleted_at()) >= millis(live.updated_at());
+        return if deletion_wins == local_deleted { Side::Local } else { Side::Remote };
+    }
+    if local_time > remote_time {
+        Side::Local
+    } else {
+        Side::Remote
+    }
+}
+
+fn tally<T: Entity + PartialEq>(changes: &mut Changes, before: Option<&T>, after: Option<&T>) {
+    match (before, after) {
+        (None, Some(_)) => changes.added += 1,
+        (Some(_), None) => changes.deleted += 1,
+        (Some(before), Some(after)) if before != after => {
+            if after.deleted_at().is_some() && before.deleted_at().is_none() {
+                changes.deleted += 1;
+            } else {
+                changes.updated += 1;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn merge_list<T: Merge>(
+    entity_type: &'static str,
+    base: Option<&HashMap<String, String>>,
+    local: &[T],
+    remote: &[T],
+    summary: &mut SyncSummary,
+) -> Vec<T> {
+    let local_by_id: HashMap<&str, &T> = local.iter().map(|item| (item.id(), item)).collect();
+    let remote_by_id: HashMap<&str, &T> = remote.iter().map(|item| (item.id(), item)).collect();
+    // Local order first, then entities only the remote has.
+    let remote_only = remote.iter().filter(|item| !local_by_id.contains_key(item.id()));
+    let ids: Vec<&str> = local.iter().chain(remote_only).map(|item| item.id()).collect();
+
+    let mut merged = Vec::with_capacity(local.len().max(remote.len()));
+    for id in ids {
+        let local = local_by_id.get(id).copied();
+        let remote = remote_by_id.get(id).copied();
+        let base_version = base.and_then(|versions| versions.get(id)).map(String::as_str);
+        let unchanged = |item: &T| base_version.is_some() && version(item) == base_version;
+
+        let kept = match (local, remote) {
+            (Some(local), Some(remote)) => {
+                if version(local) == version(remote) || unchanged(remote) {
+                    Some(Side::Local)
+                } else if unchanged(local) {
+                    Some(Side::Remote)
+                } else {
+                    let side = newer(local, remote);
+                    summary.conflicts.push(Conflict {
+                        entity_type,
+                        id: id.to_string(),
+                        kept: side,
+                    });
+                    Some(side)
+                }
+            }
+            // Present in the base but gone from the other side: removed there, unless edited here
+            // since, in which case the edit is kept.
+            (Some(item), None) | (None, Some(item)) if base_version.is_some() => {
+                let side = if local.is_some() { Side::Local } else { Side::Remote };
+                if unchanged(item) {
+                    None
+                } else {
+                    summary.conflicts.push(Conflict {
+                        entity_type,
+                        id: id.to_string(),
+                        kept: side,
+                    });
+                    Some(side)
+                }
+            }
+            (Some(_), None) => Some(Side::Local),
+            (None, Some(_)) => Some(Side::Remote),
+            (None, None) => None,
+        };
+
+        let result = match kept {
+            Some(Side::Local) => local.cloned(),
+            Some(Side::Remote) => remote.map(|remote| {
+                let mut winner = remote.clone();
+                if let Some(local) = local {
+                    winner.keep_local_state(local);
+                }
+                winner
+            }),
+            None => None,
+        };
+        tally(&mut summary.local, local, result.as_ref());
+        tally(&mut summary.remote, remote, result.as_ref());
+        merged.extend(result);
+    }
+    merged
+}
+
+/// Merges `local` with `remote`, using `base` when it was recorded for the same target. Settings
+/// are device preferences and always come from `local`.
+pub fn merge(base: Option<&Base>, local: &AppData, remote: &AppData) -> Merged {
+    let mut summary = SyncSummary {
+        three_way: base.is_some(),
+        ..SyncSummary::default()
+    };
+    let versions = |key: &str| base.and_then(|base| base.versions.get(key));
+    let tasks = merge_list("task", versions("tasks"), &local.tasks, &remote.tasks, &mut summary);
+    let projects = merge_list("project", versions("projects"), &local.projects, &remote.projects, &mut summary);
+    let sections = merge_list("section", versions("sections"), &local.sections, &remote.sections, &mut summary);
+    let mut areas = merge_list("area", versions("areas"), &local.areas, &remote.areas, &mut summary);
+    areas.sort_by_key(|area| area.order);
+
+    Merged {
+        data: AppData {
+            tasks,
+            projects,
+            sections,
+            areas,
+            settings: local.settings.clone(),
+        },
+        summary,
+    }
+}
+
+fn versions_of<T: Entity>(items: &[T]) -> HashMap<String, String> {
+    items
+        .iter()
+        .filter_map(|item| Some((item.id().to_string(), version(item)?.to_string())))
+        .collect()
+}
+
+/// The base recorded by the last sync with `target`, if that was the last target synced with.
+pub fn load_base(conn: &Connection, target: &str) -> Result<Option<Base>, String> {
+    let Some(raw) = app_meta::get(conn, BASE_KEY)? else {
+        return Ok(None);
+    };
+    match serde_json::from_str::<Base>(&raw) {
+        Ok(base) if base.target == target => Ok(Some(base)),
+        Ok(_) => Ok(None),
+        Err(e) => {
+            log::warn!("Ignoring unreadable sync base: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Records `base` for the next sync with its target.
+pub fn store_base(conn: &Connection, base: &Base) -> Result<(), String> {
+    let raw = serde_json::to_string(base).map_err(|e| e.to_string())?;
+    app_meta::set(conn, BASE_KEY, &raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const T0: &str = "2024-01-01T00:00:00.000Z";
+    const T1: &str = "2024-01-01T01:00:00.000Z";
+    const T2: &str = "2024-01-01T02:00:00.000Z";
+
+    fn task(id: &str, updated_at: &str) -> Value {
+        json!({ "id": id, "title": id, "status": "inbox", "createdAt": T0, "updatedAt": updated_at })
+    }
+
+    fn data(tasks: Vec<Value>) -> AppData {
+        AppData::from_value(json!({ "tasks": tasks })).unwrap()
+    }
+
+    fn versions(data: &AppData) -> Vec<(&str, &str)> {
+        let mut versions: Vec<_> = data
+            .tasks
+            .iter()
+            .map(|task| (task.id.as_str(), task.updated_at.as_str()))
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    fn conflicts(summary: &SyncSummary) -> Vec<(&str, Side)> {
+        summary.conflicts.iter().map(|conflict| (conflict.id.as_str(), conflict.kept)).collect()
+    }
+
+    #[test]
+    fn without_base_nothing_is_removed() {
+        let local = data(vec![task("a", T1), task("b", T0)]);
+        let remote = data(vec![task("a", T2), task("c", T0)]);
+        let merged = merge(None, &local, &remote);
+
+        assert_eq!(versions(&merged.data), [("a", T2), ("b", T0), ("c", T0)]);
+        assert!(!merged.summary.three_way);
+        assert_eq!(conflicts(&merged.summary), [("a", Side::Remote)]);
+        assert_eq!((merged.summary.local.added, merged.summary.local.updated), (1, 1));
+        assert_eq!(merged.summary.remote.added, 1);
+        assert_eq!(merged.summary.local.deleted + merged.summary.remote.deleted, 0);
+    }
+
+    #[test]
+    fn base_tells_removals_from_additions() {
+        let base = Base::of("target", &data(vec![task("a", T0), task("b", T0), task("c", T0), task("d", T0)]));
+        // a: removed remotely. b: removed remotely but edited here. c: edited remotely, removed
+        // here. d: removed here. e and f: added on one side each.
+        let local = data(vec![task("a", T0), task("b", T1), task("e", T0)]);
+        let remote = data(vec![task("c", T1), task("d", T0), task("f", T0)]);
+        let merged = merge(Some(&base), &local, &remote);
+
+        assert_eq!(versions(&merged.data), [("b", T1), ("c", T1), ("e", T0), ("f", T0)]);
+        assert!(merged.summary.three_way);
+        assert_eq!(conflicts(&merged.summary), [("b", Side::Local), ("c", Side::Remote)]);
+        assert_eq!(merged.summary.local.deleted, 1);
+        assert_eq!(merged.summary.remote.deleted, 1);
+        assert_eq!((merged.summary.local.added, merged.summary.remote.added), (2, 2));
+    }
+
+    #[test]
+    fn changes_on_one_side_win_without_conflict() {
+        let base = Base::of("target", &data(vec![task("a", T0), task("b", T0)]));
+        let local = data(vec![task("a", T1), task("b", T0)]);
+        let remote = data(vec![task("a", T0), task("b", T2)]);
+        let merged = merge(Some(&base), &local, &remote);
+
+        assert_eq!(versions(&merged.data), [("a", T1), ("b", T2)]);
+        assert!(merged.summary.conflicts.is_empty());
+        assert_eq!((merged.summary.local.updated, merged.summary.remote.updated), (1, 1));
+    }
+
+    #[test]
+    fn deletion_close_to_an_edit_wins_unless_the_edit_came_after() {
+        let base = Base::of("target", &data(vec![task("a", T0), task("b", T0)]));
+        let deleted = |id: &str, updated_at: &str, deleted_at: &str| {
+            let mut task = task(id, updated_at);
+            task["deletedAt"] = json!(deleted_at);
+            task
+        };
+        // Edited locally at 01:00. a was deleted remotely a minute later; b was deleted a minute
+        // earlier, though its copy carries a later `updatedAt`.
+        let local = data(vec![task("a", T1), task("b", T1)]);
+        let remote = data(vec![
+            deleted("a", "2024-01-01T01:01:00.000Z", "2024-01-01T01:01:00.000Z"),
+            deleted("b", "2024-01-01T01:02:00.000Z", "2024-01-01T00:59:00.000Z"),
+        ]);
+        let merged = merge(Some(&base), &local, &remote);
+
+        assert_eq!(conflicts(&merged.summary), [("a", Side::Remote), ("b", Side::Local)]);
+        assert!(merged.data.tasks[0].deleted_at.is_some());
+        assert!(merged.data.tasks[1].deleted_at.is_none());
+        assert_eq!(merged.summary.local.deleted, 1);
+
+        // Far apart, the newer copy wins even if it's the live one.
+        let local = data(vec![task("a", T2)]);
+        let remote = data(vec![deleted("a", T1, T1)]);
+        let merged = merge(Some(&base), &local, &remote);
+        assert_eq!(conflicts(&merged.summary), [("a", Side::Local)]);
+    }
+
+    #[test]
+    fn remote_winner_keeps_local_attachment_paths() {
+        let with_attachments = |updated_at: &str, attachments: Value| {
+            let mut task = task("a", updated_at);
+            task["attachments"] = attachments;
+            task
+        };
+        let local = data(vec![with_attachments(
+            T1,
+            json!([
+                { "id": "f", "kind": "file", "title": "old", "uri": "/home/me/f.pdf", "localStatus": "available" },
+                { "id": "l", "kind": "link", "uri": "https://old.example" },
+            ]),
+        )]);
+        let remote = data(vec![with_attachments(
+            T2,
+            json!([
+                { "id": "f", "kind": "file", "title": "new", "uri": "C:\\Users\\me\\f.pdf" },
+                { "id": "l", "kind": "link", "uri": "https://new.example" },
+                { "id": "g", "kind": "file", "uri": "C:\\Users\\me\\g.pdf", "localStatus": "missing" },
+            ]),
+        )]);
+        let merged = merge(None, &local, &remote);
+
+        assert_eq!(
+            merged.data.tasks[0].attachments,
+            Some(json!([
+                { "id": "f", "kind": "file", "title": "new", "uri": "/home/me/f.pdf", "localStatus": "available" },
+                { "id": "l", "kind": "link", "uri": "https://new.example" },
+                { "id": "g", "kind": "file", "uri": "C:\\Users\\me\\g.pdf", "localStatus": "missing" },
+            ]))
+        );
+    }
+
+    #[test]
+    fn base_is_kept_per_target() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        assert!(load_base(&conn, "target").unwrap().is_none());
+
+        store_base(&conn, &Base::of("target", &data(vec![task("a", T1)]))).unwrap();
+        let base = load_base(&conn, "target").unwrap().unwrap();
+        assert_eq!(base.versions["tasks"]["a"], T1);
+        assert!(load_base(&conn, "other").unwrap().is_none());
+    }
+}
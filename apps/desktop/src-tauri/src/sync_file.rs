@@ -0,0 +1,225 @@
+//! Lost-update protection for the file sync backend.
+//!
+//! Tools such as Syncthing and Dropbox can replace `data.json` in the sync folder at any time.
+//! A read records a stamp of the file (a SHA-256 of its bytes and its mtime), and a write only
+//! renames its temp file over `data.json` if the stamp still matches, so a copy another device
+//! wrote in between is merged rather than overwritten. Writers on this machine are kept apart by
+//! an advisory lock file next to `data.json`.
+
+use ring::digest::{digest, SHA256};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LOCK_FILE_NAME: &str = "data.json.lock";
+/// How long to wait for another writer to release the lock.
+const LOCK_WAIT: Duration = Duration::from_secs(5);
+const LOCK_POLL: Duration = Duration::from_millis(100);
+/// A lock older than this was left behind by a writer that crashed, or synced in from another
+/// device, and is taken over.
+const LOCK_STALE: Duration = Duration::from_secs(30);
+
+/// The state of a file when it was read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStamp {
+    sha256: String,
+    modified_ms: Option<u128>,
+}
+
+/// Stamps `path` as it is now, or returns None when there is no such file.
+pub fn stamp(path: &Path) -> Result<Option<FileStamp>, String> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let modified_ms = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_millis());
+    let sha256 = digest(&SHA256, &bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok(Some(FileStamp { sha256, modified_ms }))
+}
+
+/// Holds the sync folder's lock file until dropped.
+pub struct SyncLock {
+    path: PathBuf,
+}
+
+impl SyncLock {
+    pub fn acquire(dir: &Path) -> Result<SyncLock, String> {
+        SyncLock::acquire_within(dir, LOCK_WAIT)
+    }
+
+    fn acquire_within(dir: &Path, wait: Duration) -> Result<SyncLock, String> {
+        let path = dir.join(LOCK_FILE_NAME);
+        let started = SystemTime::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let _ = writeln!(file, "{}", std::process::id());
+                    return Ok(SyncLock { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(format!("Failed to lock the sync folder: {}", e)),
+            }
+            let age = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            if age.is_some_and(|age| age > LOCK_STALE) {
+                log::warn!("Removing stale sync lock {}", path.display());
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            if started.elapsed().unwrap_or_default() > wait {
+                return Err("The sync folder is locked by another writer; try again shortly".to_string());
+            }
+            std::thread::sleep(LOCK_POLL);
+        }
+    }
+}
+
+impl Drop for SyncLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::AppData;
+    use crate::{read_sync_dir, sync, write_merging, write_sync_dir, RemoteVersion, SyncTarget, WriteOutcome};
+    use serde_json::{json, Value};
+    use std::fs::File;
+
+    const T0: &str = "2024-01-01T00:00:00.000Z";
+    const T1: &str = "2024-01-01T01:00:00.000Z";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mindwtr-sync-file-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn task(id: &str, title: &str, updated_at: &str) -> Value {
+        json!({ "id": id, "title": title, "status": "inbox", "createdAt": T0, "updatedAt": updated_at })
+    }
+
+    fn document(tasks: Vec<Value>) -> Value {
+        json!({ "tasks": tasks, "projects": [], "sections": [], "areas": [], "settings": {} })
+    }
+
+    fn titles(data: &AppData) -> Vec<(&str, &str)> {
+        let mut titles: Vec<_> = data
+            .tasks
+            .iter()
+            .map(|task| (task.id.as_str(), task.title.as_str()))
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    fn read(dir: &Path) -> (AppData, Option<FileStamp>) {
+        let (value, stamp) = read_sync_dir(dir).unwrap();
+        (AppData::from_value(value.unwrap()).unwrap(), stamp)
+    }
+
+    #[test]
+    fn lock_waits_for_the_holder_then_times_out() {
+        let dir = temp_dir("lock-timeout");
+        let held = SyncLock::acquire(&dir).unwrap();
+
+        let started = SystemTime::now();
+        let error = SyncLock::acquire_within(&dir, Duration::from_millis(300)).err().unwrap();
+        assert!(error.contains("locked by another writer"), "{}", error);
+        assert!(started.elapsed().unwrap() >= Duration::from_millis(300));
+
+        drop(held);
+        assert!(!dir.join(LOCK_FILE_NAME).exists());
+        let _again = SyncLock::acquire_within(&dir, Duration::ZERO).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over() {
+        let dir = temp_dir("stale-lock");
+        let path = dir.join(LOCK_FILE_NAME);
+        fs::write(&path, "12345\n").unwrap();
+        let old = SystemTime::now() - LOCK_STALE - Duration::from_secs(1);
+        File::options().write(true).open(&path).unwrap().set_modified(old).unwrap();
+
+        let lock = SyncLock::acquire_within(&dir, Duration::ZERO).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), std::process::id().to_string());
+        drop(lock);
+        assert!(!path.exists());
+
+        // A fresh lock is not stale.
+        fs::write(&path, "12345\n").unwrap();
+        assert!(SyncLock::acquire_within(&dir, Duration::ZERO).is_err());
+        assert!(path.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn concurrent_writer_is_detected() {
+        let dir = temp_dir("concurrent");
+        let file = dir.join(crate::DATA_FILE_NAME);
+        assert_eq!(stamp(&file).unwrap(), None);
+        let WriteOutcome::Written(first) = write_sync_dir(&dir, &document(vec![]), &None).unwrap() else {
+            panic!("first write was refused");
+        };
+        assert_eq!(stamp(&file).unwrap().as_ref(), Some(&first));
+
+        // Another device replaces the file after this one read it.
+        let theirs = serde_json::to_string(&document(vec![task("a", "theirs", T0)])).unwrap();
+        fs::write(&file, &theirs).unwrap();
+        let outcome = write_sync_dir(&dir, &document(vec![task("b", "ours", T0)]), &Some(first)).unwrap();
+        assert!(matches!(outcome, WriteOutcome::RemoteChanged));
+        assert_eq!(fs::read_to_string(&file).unwrap(), theirs);
+        assert!(!dir.join(format!("{}.tmp", crate::DATA_FILE_NAME)).exists());
+        assert!(!dir.join(LOCK_FILE_NAME).exists());
+
+        // Nor may a write that expected no file replace one.
+        let outcome = write_sync_dir(&dir, &document(vec![]), &None).unwrap();
+        assert!(matches!(outcome, WriteOutcome::RemoteChanged));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stamp_mismatch_merges_against_the_base_and_retries() {
+        let dir = temp_dir("merge-retry");
+        let target = SyncTarget::File(dir.clone());
+        let shared = document(vec![task("a", "a", T0), task("b", "b", T0)]);
+        write_sync_dir(&dir, &shared, &None).unwrap();
+        let (read_back, stamp) = read(&dir);
+        let base = sync::Base::of(&target.key(), &read_back);
+
+        // Another device removes b and adds c; this one edits a and adds d.
+        let theirs = document(vec![task("a", "a", T0), task("c", "c", T0)]);
+        fs::write(dir.join(crate::DATA_FILE_NAME), serde_json::to_string(&theirs).unwrap()).unwrap();
+        let ours = document(vec![task("a", "edited", T1), task("b", "b", T0), task("d", "d", T0)]);
+        let (written, version) =
+            write_merging(&target, ours.clone(), RemoteVersion::File(stamp.clone()), Some(&base)).unwrap();
+
+        let expected = [("a", "edited"), ("c", "c"), ("d", "d")];
+        assert_eq!(titles(&written), expected);
+        let (on_disk, on_disk_stamp) = read(&dir);
+        assert_eq!(titles(&on_disk), expected);
+        assert!(matches!(version, RemoteVersion::File(ref written) if *written == on_disk_stamp));
+
+        // Without a base nothing counts as removed, so b comes back.
+        fs::write(dir.join(crate::DATA_FILE_NAME), serde_json::to_string(&theirs).unwrap()).unwrap();
+        let (written, _) = write_merging(&target, ours, RemoteVersion::File(stamp), None).unwrap();
+        assert_eq!(titles(&written), [("a", "edited"), ("b", "b"), ("c", "c"), ("d", "d")]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,549 @@
+//! Tag and context lookup tables.
+//!
+//! Tasks and projects keep tags and contexts as JSON lists, which remain the source of truth for
+//! sync and the frontend. Triggers mirror every list into a join table indexed by value, so
+//! filtering by a tag or counting its uses doesn't parse every row. Renaming, merging and
+//! deleting tags rewrite the JSON lists and let the triggers follow.
+//!
+//! Those operations match tags the way the core store does: ignoring case and a missing `#`, so
+//! `work`, `#work` and `#Work` are the same tag.
+
+use rusqlite::{params, params_from_iter, Connection, ToSql};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// A JSON list column mirrored into a join table.
+struct Link {
+    table: &'static str,
+    source: &'static str,
+    column: &'static str,
+    id_column: &'static str,
+    value_column: &'static str,
+}
+
+const TASK_TAGS: Link = Link {
+    table: "task_tags",
+    source: "tasks",
+    column: "tags",
+    id_column: "taskId",
+    value_column: "tag",
+};
+
+const TASK_CONTEXTS: Link = Link {
+    table: "task_contexts",
+    source: "tasks",
+    column: "contexts",
+    id_column: "taskId",
+    value_column: "context",
+};
+
+const PROJECT_TAGS: Link = Link {
+    table: "project_tags",
+    source: "projects",
+    column: "tagIds",
+    id_column: "projectId",
+    value_column: "tag",
+};
+
+const LINKS: &[&Link] = &[&TASK_TAGS, &TASK_CONTEXTS, &PROJECT_TAGS];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagUsage {
+    pub name: String,
+    /// Tasks and projects outside the trash that carry the tag.
+    pub task_count: i64,
+    pub project_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextUsage {
+    pub name: String,
+    pub task_count: i64,
+}
+
+/// How many entities a rename, merge or delete rewrote.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagChange {
+    pub tasks: usize,
+    pub projects: usize,
+}
+
+impl Link {
+    /// `json_each` over `{row}.{column}`, treating malformed JSON as an empty list rather than
+    /// failing the write that fired the trigger.
+    fn members(&self, row: &str) -> String {
+        format!(
+            "json_each(CASE WHEN json_valid({row}.{column}) THEN {row}.{column} ELSE '[]' END)",
+            row = row,
+            column = self.column
+        )
+    }
+}
+
+/// Creates the join tables and their triggers, and fills them from the existing rows.
+pub fn install(conn: &Connection) -> Result<(), String> {
+    for link in LINKS {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+               {id} TEXT NOT NULL,
+               {value} TEXT NOT NULL,
+               PRIMARY KEY ({id}, {value})
+             ) WITHOUT ROWID;
+             CREATE INDEX IF NOT EXISTS idx_{table}_{value} ON {table}({value}, {id});
+             DROP TRIGGER IF EXISTS {table}_ai;
+             DROP TRIGGER IF EXISTS {table}_au;
+             DROP TRIGGER IF EXISTS {table}_ad;
+             CREATE TRIGGER {table}_ai AFTER INSERT ON {source} BEGIN
+               INSERT OR IGNORE INTO {table} ({id}, {value})
+               SELECT new.id, value FROM {new_members} WHERE type = 'text';
+             END;
+             CREATE TRIGGER {table}_au AFTER UPDATE OF {column} ON {source} BEGIN
+               DELETE FROM {table} WHERE {id} = old.id;
+               INSERT OR IGNORE INTO {table} ({id}, {value})
+               SELECT new.id, value FROM {new_members} WHERE type = 'text';
+             END;
+             CREATE TRIGGER {table}_ad AFTER DELETE ON {source} BEGIN
+               DELETE FROM {table} WHERE {id} = old.id;
+             END;
+             DELETE FROM {table};
+             INSERT OR IGNORE INTO {table} ({id}, {value})
+             SELECT {source}.id, value FROM {source}, {row_members} WHERE type = 'text';",
+            table = link.table,
+            source = link.source,
+            column = link.column,
+            id = link.id_column,
+            value = link.value_column,
+            new_members = link.members("new"),
+            row_members = link.members(link.source),
+        ))
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// `EXISTS` clause matching tasks (aliased as `alias`) that carry `value` as a tag, or as a
+/// context when `context` is set. Hierarchical children such as `#work/calls` match `#work`
+/// when `children` is set. Parameters are appended to `params`.
+pub fn task_filter(
+    alias: &str,
+    context: bool,
+    value: &str,
+    children: bool,
+    params: &mut Vec<Box<dyn ToSql>>,
+) -> String {
+    let link = if context { &TASK_CONTEXTS } else { &TASK_TAGS };
+    params.push(Box::new(value.to_string()));
+    // Under binary collation every `value/...` sorts between `value/` and `value0`, so the
+    // children are an index range.
+    let children_sql = if children {
+        params.push(Box::new(format!("{}/", value)));
+        params.push(Box::new(format!("{}0", value)));
+        format!(
+            " OR ({table}.{value} >= ? AND {table}.{value} < ?)",
+            table = link.table,
+            value = link.value_column
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        "EXISTS (SELECT 1 FROM {table} WHERE {table}.{id} = {alias}.id AND ({table}.{value} = ?{children}))",
+        table = link.table,
+        id = link.id_column,
+        value = link.value_column,
+        alias = alias,
+        children = children_sql
+    )
+}
+
+/// Tags in use by tasks or projects outside the trash, by name.
+pub fn list_tags(conn: &Connection) -> Result<Vec<TagUsage>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT name, sum(tasks), sum(projects) FROM (
+               SELECT task_tags.tag AS name, 1 AS tasks, 0 AS projects
+               FROM task_tags JOIN tasks ON tasks.id = task_tags.taskId
+               WHERE tasks.deletedAt IS NULL
+               UNION ALL
+               SELECT project_tags.tag, 0, 1
+               FROM project_tags JOIN projects ON projects.id = project_tags.projectId
+               WHERE projects.deletedAt IS NULL
+             )
+             GROUP BY name
+             ORDER BY name COLLATE NOCASE, name",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(TagUsage {
+                name: row.get(0)?,
+                task_count: row.get(1)?,
+                project_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Contexts in use by tasks outside the trash, by name.
+pub fn list_contexts(conn: &Connection) -> Result<Vec<ContextUsage>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT task_contexts.context, COUNT(*)
+             FROM task_contexts JOIN tasks ON tasks.id = task_contexts.taskId
+             WHERE tasks.deletedAt IS NULL
+             GROUP BY task_contexts.context
+             ORDER BY task_contexts.context COLLATE NOCASE, task_contexts.context",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ContextUsage {
+                name: row.get(0)?,
+                task_count: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Trims `tag` and adds the `#` prefix if it is missing.
+fn require_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    if tag.trim_start_matches('#').trim().is_empty() {
+        return Err("Tag must not be empty".to_string());
+    }
+    Ok(if tag.starts_with('#') {
+        tag.to_string()
+    } else {
+        format!("#{}", tag)
+    })
+}
+
+/// The form tags are compared in, like the core's `normalizeTagId`.
+fn tag_key(tag: &str) -> String {
+    let tag = tag.trim();
+    if tag.starts_with('#') {
+        tag.to_lowercase()
+    } else {
+        format!("#{}", tag).to_lowercase()
+    }
+}
+
+/// Distinct values in `link`'s join table that match one of `keys`, other than `keep`.
+fn stored_variants(
+    conn: &Connection,
+    link: &Link,
+    keys: &HashSet<String>,
+    keep: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT DISTINCT {} FROM {}", link.value_column, link.table))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    let mut variants = Vec::new();
+    for value in rows {
+        let value = value.map_err(|e| e.to_string())?;
+        if keys.contains(&tag_key(&value)) && Some(value.as_str()) != keep {
+            variants.push(value);
+        }
+    }
+    Ok(variants)
+}
+
+/// Replaces each of `from` with `to` in every task's and project's tag list, or removes them
+/// when `to` is None. Tags match ignoring case and a missing `#`, and `to` gains the prefix if
+/// it lacks one, so renaming `#work` to `Work` also fixes its case. Lists are rewritten in place,
+/// keeping their order and dropping any duplicate the replacement creates; trashed entities are
+/// included so the change survives a restore. Rewritten rows get `updatedAt = now`.
+pub fn replace_tags(conn: &Connection, from: &[String], to: Option<&str>, now: &str) -> Result<TagChange, String> {
+    let to = to.map(require_tag).transpose()?;
+    let keys: HashSet<String> = from
+        .iter()
+        .map(|tag| require_tag(tag).map(|tag| tag_key(&tag)))
+        .collect::<Result<_, _>>()?;
+    let to_key = to.as_deref().map(tag_key);
+    // Spellings of `to` collapse into one entry; other duplicates are left as they were.
+    let is_target = |item: &Value| to_key.is_some() && item.as_str().map(tag_key) == to_key;
+
+    let mut change = TagChange::default();
+    for link in [&TASK_TAGS, &PROJECT_TAGS] {
+        let variants = stored_variants(conn, link, &keys, to.as_deref())?;
+        if variants.is_empty() {
+            continue;
+        }
+        let placeholders = vec!["?"; variants.len()].join(", ");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {source}.id, {source}.{column} FROM {source}
+                 WHERE {source}.id IN (SELECT {id} FROM {table} WHERE {value} IN ({placeholders}))",
+                source = link.source,
+                column = link.column,
+                table = link.table,
+                id = link.id_column,
+                value = link.value_column,
+                placeholders = placeholders
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params_from_iter(variants.iter()), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        for (id, raw) in rows {
+            let list: Vec<Value> = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+            let mut next: Vec<Value> = Vec::with_capacity(list.len());
+            for item in list {
+                let item = match item.as_str() {
+                    Some(tag) if keys.contains(&tag_key(tag)) => match &to {
+                        Some(to) => Value::String(to.clone()),
+                        None => continue,
+                    },
+                    _ => item,
+                };
+                let duplicate = next.contains(&item) || (is_target(&item) && next.iter().any(is_target));
+                if !duplicate {
+                    next.push(item);
+                }
+            }
+            let next = serde_json::to_string(&next).map_err(|e| e.to_string())?;
+            conn.execute(
+                &format!("UPDATE {} SET {} = ?1, updatedAt = ?2 WHERE id = ?3", link.source, link.column),
+                params![next, now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            if link.source == "tasks" {
+                change.tasks += 1;
+            } else {
+                change.projects += 1;
+            }
+        }
+    }
+    Ok(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: &str = "2024-02-01T00:00:00.000Z";
+
+    fn connection() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn).unwrap();
+        conn
+    }
+
+    fn add_task(conn: &Connection, id: &str, tags: &str, contexts: &str) {
+        conn.execute(
+            "INSERT INTO tasks (id, title, status, tags, contexts, createdAt, updatedAt)
+             VALUES (?1, ?1, 'inbox', ?2, ?3, '2024-01-01', '2024-01-01')",
+            [id, tags, contexts],
+        )
+        .unwrap();
+    }
+
+    fn add_project(conn: &Connection, id: &str, tags: &str) {
+        conn.execute(
+            "INSERT INTO projects (id, title, status, color, tagIds, createdAt, updatedAt)
+             VALUES (?1, ?1, 'active', '#000', ?2, '2024-01-01', '2024-01-01')",
+            [id, tags],
+        )
+        .unwrap();
+    }
+
+    /// The JSON list column and `updatedAt` of a task or project.
+    fn list(conn: &Connection, table: &str, id: &str) -> (String, String) {
+        let column = if table == "tasks" { "tags" } else { "tagIds" };
+        conn.query_row(
+            &format!("SELECT {}, updatedAt FROM {} WHERE id = ?1", column, table),
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    fn rows(conn: &Connection, table: &str) -> Vec<(String, String)> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM {} ORDER BY 1, 2", table))
+            .unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn join_tables_follow_the_json_lists() {
+        let conn = connection();
+        add_task(&conn, "t1", r##"["#a", "#b", 3, "#a"]"##, r#"["@home"]"#);
+        add_task(&conn, "t2", "not json", "[]");
+        add_project(&conn, "p1", r##"["#a"]"##);
+        assert_eq!(rows(&conn, "task_tags"), pairs(&[("t1", "#a"), ("t1", "#b")]));
+        assert_eq!(rows(&conn, "task_contexts"), pairs(&[("t1", "@home")]));
+        assert_eq!(rows(&conn, "project_tags"), pairs(&[("p1", "#a")]));
+
+        conn.execute_batch(
+            "UPDATE tasks SET tags = '[\"#c\"]' WHERE id = 't1';
+             UPDATE tasks SET tags = '[\"#d\"]' WHERE id = 't2';
+             DELETE FROM projects WHERE id = 'p1';",
+        )
+        .unwrap();
+        assert_eq!(rows(&conn, "task_tags"), pairs(&[("t1", "#c"), ("t2", "#d")]));
+        assert!(rows(&conn, "project_tags").is_empty());
+
+        // Installing again rebuilds the tables from the lists.
+        conn.execute_batch("DELETE FROM task_tags; DELETE FROM task_contexts;").unwrap();
+        install(&conn).unwrap();
+        assert_eq!(rows(&conn, "task_tags"), pairs(&[("t1", "#c"), ("t2", "#d")]));
+        assert_eq!(rows(&conn, "task_contexts"), pairs(&[("t1", "@home")]));
+    }
+
+    #[test]
+    fn usage_counts_skip_the_trash() {
+        let conn = connection();
+        add_task(&conn, "t1", r##"["#work", "#Errand"]"##, r#"["@phone"]"#);
+        add_task(&conn, "t2", r##"["#work"]"##, r#"["@phone", "@desk"]"#);
+        add_task(&conn, "trashed", r##"["#work", "#old"]"##, r#"["@desk"]"#);
+        add_project(&conn, "p1", r##"["#work"]"##);
+        conn.execute("UPDATE tasks SET deletedAt = ?1 WHERE id = 'trashed'", [NOW]).unwrap();
+
+        let usage: Vec<(String, i64, i64)> = list_tags(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|tag| (tag.name, tag.task_count, tag.project_count))
+            .collect();
+        assert_eq!(usage, [("#Errand".to_string(), 1, 0), ("#work".to_string(), 2, 1)]);
+        let contexts: Vec<(String, i64)> = list_contexts(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|context| (context.name, context.task_count))
+            .collect();
+        assert_eq!(contexts, [("@desk".to_string(), 1), ("@phone".to_string(), 2)]);
+    }
+
+    #[test]
+    fn rename_rewrites_tasks_and_projects_including_the_trash() {
+        let conn = connection();
+        add_task(&conn, "t1", r##"["#home", "#work"]"##, "[]");
+        add_task(&conn, "trashed", r##"["#work"]"##, "[]");
+        add_task(&conn, "other", r##"["#home"]"##, "[]");
+        add_project(&conn, "p1", r##"["#work"]"##);
+        conn.execute("UPDATE tasks SET deletedAt = '2024-01-02' WHERE id = 'trashed'", []).unwrap();
+
+        let change = replace_tags(&conn, &tags(&["#work"]), Some("#office"), NOW).unwrap();
+
+        assert_eq!((change.tasks, change.projects), (2, 1));
+        assert_eq!(list(&conn, "tasks", "t1"), (r##"["#home","#office"]"##.to_string(), NOW.to_string()));
+        assert_eq!(list(&conn, "tasks", "trashed").0, r##"["#office"]"##);
+        assert_eq!(list(&conn, "projects", "p1").0, r##"["#office"]"##);
+        assert_eq!(list(&conn, "tasks", "other"), (r##"["#home"]"##.to_string(), "2024-01-01".to_string()));
+        assert!(rows(&conn, "task_tags").iter().all(|(_, tag)| tag != "#work"));
+    }
+
+    #[test]
+    fn merge_keeps_one_copy_in_the_first_position() {
+        let conn = connection();
+        add_task(&conn, "t1", r##"["#x", "#b", "#y", "#a", "#c"]"##, "[]");
+        add_task(&conn, "t2", r##"["#a"]"##, "[]");
+
+        let change = replace_tags(&conn, &tags(&["#a", "#b", "#b"]), Some("#c"), NOW).unwrap();
+
+        assert_eq!((change.tasks, change.projects), (2, 0));
+        assert_eq!(list(&conn, "tasks", "t1").0, r##"["#x","#c","#y"]"##);
+        assert_eq!(list(&conn, "tasks", "t2").0, r##"["#c"]"##);
+    }
+
+    #[test]
+    fn delete_removes_the_tag() {
+        let conn = connection();
+        add_task(&conn, "t1", r##"["#a", "#b"]"##, "[]");
+        add_project(&conn, "p1", r##"["#b"]"##);
+
+        let change = replace_tags(&conn, &tags(&["#b"]), None, NOW).unwrap();
+
+        assert_eq!((change.tasks, change.projects), (1, 1));
+        assert_eq!(list(&conn, "tasks", "t1").0, r##"["#a"]"##);
+        assert_eq!(list(&conn, "projects", "p1").0, "[]");
+    }
+
+    #[test]
+    fn tags_match_ignoring_case_and_a_missing_hash() {
+        let conn = connection();
+        add_task(&conn, "t1", r##"["#Work", "#a", "#A", "#WORK"]"##, "[]");
+        add_task(&conn, "t2", r##"["work"]"##, "[]");
+
+        replace_tags(&conn, &tags(&["work"]), Some("Office"), NOW).unwrap();
+
+        // Both spellings become one `#Office`; the unrelated #a/#A pair is left alone.
+        assert_eq!(list(&conn, "tasks", "t1").0, r##"["#Office","#a","#A"]"##);
+        assert_eq!(list(&conn, "tasks", "t2").0, r##"["#Office"]"##);
+
+        replace_tags(&conn, &tags(&["#OFFICE"]), None, NOW).unwrap();
+        assert_eq!(list(&conn, "tasks", "t1").0, r##"["#a","#A"]"##);
+        assert_eq!(list(&conn, "tasks", "t2").0, "[]");
+    }
+
+    #[test]
+    fn case_only_rename_rewrites_the_other_spellings() {
+        let conn = connection();
+        add_task(&conn, "already", r##"["#Work"]"##, "[]");
+        add_task(&conn, "lower", r##"["#work", "#Work"]"##, "[]");
+
+        let change = replace_tags(&conn, &tags(&["#work"]), Some("#Work"), NOW).unwrap();
+
+        assert_eq!(change.tasks, 1);
+        assert_eq!(list(&conn, "tasks", "already"), (r##"["#Work"]"##.to_string(), "2024-01-01".to_string()));
+        assert_eq!(list(&conn, "tasks", "lower").0, r##"["#Work"]"##);
+        // Renaming a tag to itself changes nothing.
+        let unchanged = replace_tags(&conn, &tags(&["#Work"]), Some("#Work"), NOW).unwrap();
+        assert_eq!((unchanged.tasks, unchanged.projects), (0, 0));
+    }
+
+    #[test]
+    fn empty_tags_are_rejected() {
+        let conn = connection();
+        for empty in ["", "  ", "#", " # "] {
+            assert!(replace_tags(&conn, &tags(&[empty]), Some("#a"), NOW).is_err(), "{:?}", empty);
+            assert!(replace_tags(&conn, &tags(&["#a"]), Some(empty), NOW).is_err(), "{:?}", empty);
+        }
+    }
+
+    #[test]
+    fn task_filter_matches_nested_tags() {
+        let conn = connection();
+        add_task(&conn, "parent", r##"["#work"]"##, r#"["@home"]"#);
+        add_task(&conn, "child", r##"["#work/client"]"##, "[]");
+        add_task(&conn, "similar", r##"["#workshop"]"##, r#"["@homework"]"#);
+        let matching = |context: bool, value: &str, children: bool| -> Vec<String> {
+            let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+            let filter = task_filter("t", context, value, children, &mut params);
+            let mut stmt = conn
+                .prepare(&format!("SELECT id FROM tasks t WHERE {} ORDER BY id", filter))
+                .unwrap();
+            let rows = stmt
+                .query_map(params_from_iter(params.iter().map(|p| p.as_ref())), |row| row.get(0))
+                .unwrap();
+            rows.collect::<Result<_, _>>().unwrap()
+        };
+
+        assert_eq!(matching(false, "#work", true), ["child", "parent"]);
+        assert_eq!(matching(false, "#work", false), ["parent"]);
+        assert_eq!(matching(true, "@home", true), ["parent"]);
+    }
+}
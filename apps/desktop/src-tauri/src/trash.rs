@@ -0,0 +1,94 @@
+//! Permanent removal of trashed entities.
+//!
+//! Purging keeps each row as a tombstone (id, status, timestamps, `deletedAt` and `purgedAt`) so
+//! other devices learn about the removal on their next sync, but clears everything the user
+//! wrote. The FTS triggers drop purged rows from the search index.
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::app_meta;
+
+const RETENTION_KEY: &str = "trash_retention_days";
+
+/// Columns cleared on purge, per table. NOT NULL text columns are emptied instead.
+const PURGED_PAYLOAD: &[(&str, &str)] = &[
+    (
+        "tasks",
+        "title = '', priority = NULL, taskMode = NULL, startTime = NULL, dueDate = NULL, \
+         recurrence = NULL, pushCount = NULL, tags = NULL, contexts = NULL, checklist = NULL, \
+         description = NULL, attachments = NULL, location = NULL, projectId = NULL, \
+         sectionId = NULL, areaId = NULL, isFocusedToday = 0, timeEstimate = NULL, \
+         reviewAt = NULL, completedAt = NULL, extra = NULL",
+    ),
+    (
+        "projects",
+        "title = '', tagIds = NULL, isSequential = 0, isFocused = 0, supportNotes = NULL, \
+         attachments = NULL, reviewAt = NULL, areaId = NULL, areaTitle = NULL, extra = NULL",
+    ),
+    ("sections", "title = '', description = NULL, extra = NULL"),
+];
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeReport {
+    pub tasks: usize,
+    pub projects: usize,
+    pub sections: usize,
+    /// Timestamp written to `purgedAt` and `updatedAt` of every purged row.
+    pub purged_at: String,
+}
+
+impl PurgeReport {
+    pub fn total(&self) -> usize {
+        self.tasks + self.projects + self.sections
+    }
+}
+
+/// Purges every trashed task, project and section deleted at or before `cutoff`, or all of them
+/// when there is no cutoff. Both timestamps are ISO-8601 UTC strings, which compare correctly as
+/// text.
+pub fn purge(conn: &Connection, cutoff: Option<&str>, now: &str) -> Result<PurgeReport, String> {
+    let mut report = PurgeReport {
+        purged_at: now.to_string(),
+        ..PurgeReport::default()
+    };
+    for (table, payload) in PURGED_PAYLOAD {
+        let purged = conn
+            .execute(
+                &format!(
+                    "UPDATE {table} SET {payload}, purgedAt = ?1, updatedAt = ?1
+                     WHERE deletedAt IS NOT NULL AND purgedAt IS NULL AND (?2 IS NULL OR deletedAt <= ?2)",
+                    table = table,
+                    payload = payload
+                ),
+                rusqlite::params![now, cutoff],
+            )
+            .map_err(|e| e.to_string())?;
+        match *table {
+            "tasks" => report.tasks = purged,
+            "projects" => report.projects = purged,
+            _ => report.sections = purged,
+        }
+    }
+    Ok(report)
+}
+
+/// Days an entity stays in the trash before it is purged automatically, if a policy is set.
+pub fn load_retention_days(conn: &Connection) -> Result<Option<u32>, String> {
+    match app_meta::get(conn, RETENTION_KEY)? {
+        Some(raw) => raw
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid trash retention value: {}", raw)),
+        None => Ok(None),
+    }
+}
+
+pub fn save_retention_days(conn: &Connection, days: Option<u32>) -> Result<(), String> {
+    match days {
+        Some(0) => Err("Trash retention must be at least one day".to_string()),
+        Some(days) => app_meta::set(conn, RETENTION_KEY, &days.to_string()),
+        None => app_meta::remove(conn, RETENTION_KEY),
+    }
+}
@@ -0,0 +1,440 @@
+//! Validation of the data documents `save_data` receives.
+//!
+//! Typed parsing alone stops at the first error and lets through empty ids, duplicate ids,
+//! malformed dates and references to entities that aren't in the document. Validation walks the
+//! raw JSON instead, so every problem is reported at once, and can repair the document rather
+//! than reject it: fixable problems are corrected in place and, only when asked to, entities with
+//! problems are dropped. A dropped entity is deleted by the save, so `Fix` never drops anything.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::model::{AppData, Area, Project, ProjectStatus, Section, Task, TaskMode, TaskPriority, TaskStatus};
+
+/// Color given to projects whose color is missing, matching the adapter's fallback.
+const FALLBACK_PROJECT_COLOR: &str = "#6B7280";
+/// Problems listed in a rejection message before the rest are only counted.
+const MESSAGE_PROBLEM_LIMIT: usize = 5;
+
+/// What to do with a document that has problems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Repair {
+    /// Save nothing and report the problems.
+    #[default]
+    Reject,
+    /// Correct what can be corrected. If anything can't be, nothing is saved, as with `Reject`.
+    Fix,
+    /// Drop every entity that has a problem.
+    Drop,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Problem {
+    /// "task", "project", "section" or "area".
+    pub entity_type: String,
+    /// Position of the entity in its list.
+    pub index: usize,
+    pub id: Option<String>,
+    /// The offending field, or None for problems with the entity as a whole.
+    pub field: Option<String>,
+    pub message: String,
+    /// "fixed" or "dropped" when the document was repaired; absent when the problem was left as it
+    /// is, in which case the document was rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s[{}]", self.entity_type, self.index)?;
+        if let Some(id) = &self.id {
+            write!(f, " (id \"{}\")", id)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, ".{}", field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The outcome of validating a document: the data to save, unless the document was rejected,
+/// and every problem found.
+pub struct Validated {
+    pub data: Option<AppData>,
+    pub problems: Vec<Problem>,
+}
+
+/// How a problem can be corrected.
+enum Fix {
+    Set(Value),
+    Remove,
+}
+
+/// An enumerated field: the field, whether a value is known, and the value a repair fills in.
+/// Fields without a fallback are optional and removed when unknown.
+type EnumRule = (&'static str, fn(&str) -> bool, Option<&'static str>);
+
+/// Field rules for one entity list.
+struct Kind {
+    key: &'static str,
+    entity_type: &'static str,
+    /// Required text fields, with the value a repair fills in, if any.
+    text: &'static [(&'static str, Option<&'static str>)],
+    enums: &'static [EnumRule],
+    /// Whether `createdAt` and `updatedAt` must be present. Either way they must be ISO-8601.
+    timestamps_required: bool,
+    /// Optional dates, removed when malformed.
+    dates: &'static [&'static str],
+    /// References to other lists: (field, referenced list, required). Optional references that
+    /// don't resolve are removed.
+    references: &'static [(&'static str, &'static str, bool)],
+    /// Full typed parse, catching anything the rules above don't cover.
+    parse: fn(Value) -> Result<(), String>,
+}
+
+/// Tombstone dates, reset to now when malformed so a deletion is never lost.
+const TOMBSTONE_DATES: &[&str] = &["deletedAt", "purgedAt"];
+
+/// In reference order, so each list is checked against the surviving entities of the lists it
+/// points at.
+const KINDS: &[Kind] = &[
+    Kind {
+        key: "areas",
+        entity_type: "area",
+        text: &[("name", None)],
+        enums: &[],
+        timestamps_required: false,
+        dates: &[],
+        references: &[],
+        parse: |value| serde_json::from_value::<Area>(value).map(|_| ()).map_err(|e| e.to_string()),
+    },
+    Kind {
+        key: "projects",
+        entity_type: "project",
+        text: &[("title", Some("")), ("color", Some(FALLBACK_PROJECT_COLOR))],
+        enums: &[("status", |raw| ProjectStatus::parse(raw).is_some(), Some("active"))],
+        timestamps_required: true,
+        dates: &["reviewAt"],
+        references: &[("areaId", "areas", false)],
+        parse: |value| serde_json::from_value::<Project>(value).map(|_| ()).map_err(|e| e.to_string()),
+    },
+    Kind {
+        key: "sections",
+        entity_type: "section",
+        text: &[("title", Some(""))],
+        enums: &[],
+        timestamps_required: true,
+        dates: &[],
+        references: &[("projectId", "projects", true)],
+        parse: |value| serde_json::from_value::<Section>(value).map(|_| ()).map_err(|e| e.to_string()),
+    },
+    Kind {
+        key: "tasks",
+        entity_type: "task",
+        text: &[("title", Some(""))],
+        enums: &[
+            ("status", |raw| TaskStatus::parse(raw).is_some(), Some("inbox")),
+            ("priority", |raw| TaskPriority::parse(raw).is_some(), None),
+            ("taskMode", |raw| TaskMode::parse(raw).is_some(), None),
+        ],
+        timestamps_required: true,
+        dates: &["dueDate", "startTime", "reviewAt", "completedAt"],
+        references: &[("projectId", "projects", false), ("sectionId", "sections", false), ("areaId", "areas", false)],
+        parse: |value| serde_json::from_value::<Task>(value).map(|_| ()).map_err(|e| e.to_string()),
+    },
+];
+
+/// True for the ISO-8601 forms the apps write: a date, a local date and time, or a full
+/// timestamp with an offset.
+pub fn is_iso_date(raw: &str) -> bool {
+    DateTime::parse_from_rfc3339(raw).is_ok()
+        || NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+        || NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M").is_ok()
+        || NaiveDate::parse_from_str(raw, "%Y-%m-%d").is_ok()
+}
+
+fn text_field<'a>(entity: &'a Map<String, Value>, field: &str) -> Option<&'a str> {
+    entity.get(field).and_then(Value::as_str)
+}
+
+/// Collects problems for one entity and applies the repair policy to each.
+struct Checker<'a> {
+    repair: Repair,
+    kind: &'a Kind,
+    index: usize,
+    id: Option<String>,
+    problems: &'a mut Vec<Problem>,
+    /// Problems in this entity left as they are.
+    unfixed: usize,
+    /// Cleared once the entity is dropped.
+    keep: bool,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, entity: &mut Map<String, Value>, field: Option<&str>, message: String, fix: Option<Fix>) {
+        let action = match (self.repair, fix) {
+            (Repair::Fix, Some(fix)) => {
+                let field = field.expect("fixes always target a field").to_string();
+                match fix {
+                    Fix::Set(value) => entity.insert(field, value),
+                    Fix::Remove => entity.remove(&field),
+                };
+                Some("fixed")
+            }
+            (Repair::Drop, _) => {
+                self.keep = false;
+                Some("dropped")
+            }
+            (Repair::Reject, _) | (Repair::Fix, None) => {
+                self.unfixed += 1;
+                None
+            }
+        };
+        self.problems.push(Problem {
+            entity_type: self.kind.entity_type.to_string(),
+            index: self.index,
+            id: self.id.clone(),
+            field: field.map(str::to_string),
+            message,
+            action: action.map(str::to_string),
+        });
+    }
+
+    fn check_fields(&mut self, entity: &mut Map<String, Value>, now: &str) {
+        for (field, fallback) in self.kind.text {
+            if text_field(entity, field).is_none() {
+                let fix = fallback.map(|value| Fix::Set(Value::String(value.to_string())));
+                self.report(entity, Some(field), "is required and must be a string".to_string(), fix);
+            }
+        }
+
+        for (field, known, fallback) in self.kind.enums {
+            let message = match entity.get(*field) {
+                None | Some(Value::Null) if fallback.is_none() => continue,
+                None | Some(Value::Null) => "is required".to_string(),
+                Some(Value::String(raw)) if known(raw) => continue,
+                Some(value) => format!("unknown value {}", value),
+            };
+            let fix = match fallback {
+                Some(value) => Fix::Set(Value::String(value.to_string())),
+                None => Fix::Remove,
+            };
+            self.report(entity, Some(field), message, Some(fix));
+        }
+
+        let created_at = text_field(entity, "createdAt").filter(|raw| is_iso_date(raw)).map(str::to_string);
+        let updated_at = text_field(entity, "updatedAt").filter(|raw| is_iso_date(raw)).map(str::to_string);
+        for (field, valid, other) in [("createdAt", &created_at, &updated_at), ("updatedAt", &updated_at, &created_at)] {
+            let message = match entity.get(field) {
+                _ if valid.is_some() => continue,
+                None | Some(Value::Null) if !self.kind.timestamps_required => continue,
+                None | Some(Value::Null) => "is required".to_string(),
+                Some(value) => format!("{} is not an ISO-8601 date", value),
+            };
+            let fallback = other.clone().unwrap_or_else(|| now.to_string());
+            self.report(entity, Some(field), message, Some(Fix::Set(Value::String(fallback))));
+        }
+
+        for field in self.kind.dates.iter().chain(TOMBSTONE_DATES) {
+            match entity.get(*field) {
+                None | Some(Value::Null) => continue,
+                Some(Value::String(raw)) if is_iso_date(raw) => continue,
+                Some(value) => {
+                    let message = format!("{} is not an ISO-8601 date", value);
+                    let fix = if TOMBSTONE_DATES.contains(field) {
+                        Fix::Set(Value::String(now.to_string()))
+                    } else {
+                        Fix::Remove
+                    };
+                    self.report(entity, Some(field), message, Some(fix));
+                }
+            }
+        }
+    }
+
+    fn check_references(&mut self, entity: &mut Map<String, Value>, ids: &HashMap<&str, HashSet<String>>) {
+        for (field, target, required) in self.kind.references {
+            let message = match entity.get(*field) {
+                None | Some(Value::Null) if !required => continue,
+                None | Some(Value::Null) => "is required".to_string(),
+                Some(Value::String(id)) if ids.get(target).is_some_and(|ids| ids.contains(id)) => continue,
+                Some(value) => format!("{} doesn't match any entity in {}", value, target),
+            };
+            let fix = if *required { None } else { Some(Fix::Remove) };
+            self.report(entity, Some(field), message, fix);
+        }
+    }
+}
+
+/// The index of the copy of each id to keep: the one with the latest `updatedAt`, or the first
+/// among equals. Entities without a usable id are left out.
+fn chosen_copies(items: &[Value]) -> HashMap<String, usize> {
+    let mut chosen: HashMap<String, usize> = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        let Some(id) = item.get("id").and_then(Value::as_str).filter(|id| !id.trim().is_empty()) else {
+            continue;
+        };
+        let updated_at = |item: &Value| item.get("updatedAt").and_then(Value::as_str).unwrap_or("").to_string();
+        match chosen.get(id) {
+            Some(&current) if updated_at(&items[current]) >= updated_at(item) => {}
+            _ => {
+                chosen.insert(id.to_string(), index);
+            }
+        }
+    }
+    chosen
+}
+
+/// Validates `value` and, unless `repair` is `Reject`, repairs it. The data is None when a problem
+/// was left as it is. Only a document whose shape is unusable (not an object, or lists that aren't
+/// arrays) is an error; everything else is reported as problems. `now` fills in timestamps a repair
+/// has nothing better for.
+pub fn validate(value: Value, repair: Repair, now: &str) -> Result<Validated, String> {
+    let Value::Object(mut document) = value else {
+        return Err("data: expected an object".to_string());
+    };
+    let mut problems = Vec::new();
+    let mut unfixed = 0;
+    let mut ids: HashMap<&str, HashSet<String>> = HashMap::new();
+
+    for kind in KINDS {
+        let items = match document.remove(kind.key) {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(items)) => items,
+            Some(_) => return Err(format!("{}: expected an array", kind.key)),
+        };
+        let chosen = chosen_copies(&items);
+        let mut kept = Vec::with_capacity(items.len());
+        let mut kept_ids = HashSet::new();
+
+        for (index, item) in items.into_iter().enumerate() {
+            let id = item.get("id").and_then(Value::as_str).map(str::to_string);
+            let mut checker = Checker {
+                repair,
+                kind,
+                index,
+                id: id.clone(),
+                problems: &mut problems,
+                unfixed: 0,
+                keep: true,
+            };
+            let Value::Object(mut entity) = item else {
+                checker.report(&mut Map::new(), None, "expected an object".to_string(), None);
+                unfixed += checker.unfixed;
+                continue;
+            };
+            match id.as_deref().filter(|id| !id.trim().is_empty()) {
+                None => checker.report(&mut entity, Some("id"), "is required and must not be empty".to_string(), None),
+                Some(id) if chosen.get(id) != Some(&index) => {
+                    let message = format!("duplicates the entity at index {}", chosen[id]);
+                    checker.report(&mut entity, Some("id"), message, None);
+                }
+                Some(_) => {}
+            }
+            checker.check_fields(&mut entity, now);
+            checker.check_references(&mut entity, &ids);
+            // An entity is only parsed once every problem so far was fixed, so serde doesn't
+            // report the same missing field again.
+            if checker.keep && checker.unfixed == 0 {
+                if let Err(message) = (kind.parse)(Value::Object(entity.clone())) {
+                    checker.report(&mut entity, None, message, None);
+                }
+            }
+            unfixed += checker.unfixed;
+            if checker.keep {
+                if let Some(id) = id {
+                    kept_ids.insert(id);
+                }
+                kept.push(Value::Object(entity));
+            }
+        }
+        ids.insert(kind.key, kept_ids);
+        document.insert(kind.key.to_string(), Value::Array(kept));
+    }
+
+    if unfixed > 0 {
+        return Ok(Validated { data: None, problems });
+    }
+    let data = AppData::from_value(Value::Object(document))?;
+    Ok(Validated {
+        data: Some(data),
+        problems,
+    })
+}
+
+/// The message for a rejected document, `outcome` saying what didn't happen. Lists the problems
+/// that were left as they are; fixed ones didn't cause the rejection.
+pub fn rejection_message(outcome: &str, problems: &[Problem]) -> String {
+    let left: Vec<&Problem> = problems.iter().filter(|problem| problem.action.is_none()).collect();
+    let mut listed: Vec<String> = left
+        .iter()
+        .take(MESSAGE_PROBLEM_LIMIT)
+        .map(|problem| problem.to_string())
+        .collect();
+    if left.len() > MESSAGE_PROBLEM_LIMIT {
+        listed.push(format!("and {} more", left.len() - MESSAGE_PROBLEM_LIMIT));
+    }
+    format!(
+        "{} because it has {} problem{}: {}",
+        outcome,
+        left.len(),
+        if left.len() == 1 { "" } else { "s" },
+        listed.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NOW: &str = "2026-01-02T00:00:00.000Z";
+
+    fn task(id: &str, extra: Value) -> Value {
+        let mut task = json!({
+            "id": id,
+            "title": id,
+            "status": "inbox",
+            "tags": [],
+            "contexts": [],
+            "createdAt": NOW,
+            "updatedAt": NOW,
+        });
+        task.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        task
+    }
+
+    fn task_ids(data: &AppData) -> Vec<&str> {
+        data.tasks.iter().map(|task| task.id.as_str()).collect()
+    }
+
+    #[test]
+    fn fix_corrects_what_it_can() {
+        let document = json!({ "tasks": [task("a", json!({ "status": "someday?", "projectId": "gone" }))] });
+        let validated = validate(document, Repair::Fix, NOW).unwrap();
+        let data = validated.data.expect("every problem is fixable");
+        assert_eq!(task_ids(&data), ["a"]);
+        assert_eq!(validated.problems.len(), 2);
+        assert!(validated.problems.iter().all(|problem| problem.action.as_deref() == Some("fixed")));
+    }
+
+    #[test]
+    fn fix_rejects_rather_than_drops() {
+        let document = json!({
+            "tasks": [task("a", json!({ "status": "someday?" })), task("b", json!({ "createdAt": 5 })), task("b", json!({}))],
+            "sections": [{ "id": "s", "title": "s", "projectId": "gone", "createdAt": NOW, "updatedAt": NOW }],
+        });
+        let validated = validate(document.clone(), Repair::Fix, NOW).unwrap();
+        assert!(validated.data.is_none());
+        let message = rejection_message("Data was not saved", &validated.problems);
+        assert!(message.starts_with("Data was not saved because it has 2 problems: "), "{message}");
+
+        let dropped = validate(document, Repair::Drop, NOW).unwrap().data.unwrap();
+        assert!(dropped.tasks.is_empty() && dropped.sections.is_empty());
+    }
+}
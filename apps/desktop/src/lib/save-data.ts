@@ -0,0 +1,57 @@
+import type { AppData } from '@mindwtr/core';
+import { logWarn } from './app-log';
+
+/** A problem the backend found in a saved document; `action` is set when it corrected it. */
+export type DataProblem = {
+    entityType: string;
+    index: number;
+    id?: string | null;
+    field?: string | null;
+    message: string;
+    action?: 'fixed' | 'dropped';
+};
+
+/** Thrown when the backend refused a document; nothing was saved. */
+export class InvalidDataError extends Error {
+    constructor(message: string, readonly problems: DataProblem[]) {
+        super(message);
+    }
+}
+
+const describeProblem = (problem: DataProblem): string => {
+    const id = problem.id ? ` (id "${problem.id}")` : '';
+    const field = problem.field ? `.${problem.field}` : '';
+    return `${problem.entityType}s[${problem.index}]${id}${field}: ${problem.message}`;
+};
+
+/** Converts the `{ kind, message, problems }` errors of `save_data`. */
+const toSaveDataError = (error: unknown): Error => {
+    if (error && typeof error === 'object' && 'kind' in error) {
+        const { kind, message, problems } = error as { kind: string; message?: string; problems?: DataProblem[] };
+        const text = message || 'Data was not saved';
+        return kind === 'invalid' ? new InvalidDataError(text, problems ?? []) : new Error(text);
+    }
+    return error instanceof Error ? error : new Error(String(error));
+};
+
+/**
+ * Saves a whole document in the Tauri backend. Data written by older builds can hold references
+ * the backend now rejects, so it corrects what it can; the corrections are logged. A document with
+ * problems it can't correct is not saved and an `InvalidDataError` lists them.
+ */
+export const saveTauriData = async (data: AppData): Promise<DataProblem[]> => {
+    const { invoke } = await import('@tauri-apps/api/core');
+    let problems: DataProblem[];
+    try {
+        problems = await invoke<DataProblem[]>('save_data', { data, repair: 'fix' });
+    } catch (error) {
+        throw toSaveDataError(error);
+    }
+    if (problems.length > 0) {
+        void logWarn(`Corrected ${problems.length} problem(s) in saved data`, {
+            scope: 'storage',
+            extra: { problems: problems.map(describeProblem).join('; ') },
+        });
+    }
+    return problems;
+};
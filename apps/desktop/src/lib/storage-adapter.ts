@@ -1,6 +1,14 @@
-import { AppData, StorageAdapter, TaskQueryOptions } from '@mindwtr/core';
+import { AppData, StorageAdapter, Task, TaskQueryOptions } from '@mindwtr/core';
 import { invoke } from '@tauri-apps/api/core';
 import { reportError } from './report-error';
+import { saveTauriData } from './save-data';
+
+/** One page of `query_tasks` results; `total` counts every match, ignoring limit and offset. */
+type TaskPage = {
+    tasks: Task[];
+    total: number;
+    nextOffset: number | null;
+};
 
 const invokeWithError = async <T>(
     action: string,
@@ -20,10 +28,16 @@ export const tauriStorage: StorageAdapter = {
         return invokeWithError<AppData>('load data', 'get_data');
     },
     saveData: async (data: AppData): Promise<void> => {
-        await invokeWithError<void>('save data', 'save_data', { data });
+        try {
+            await saveTauriData(data);
+        } catch (error) {
+            reportError('Failed to save data', error);
+            throw new Error('Failed to save data.');
+        }
     },
     queryTasks: async (options: TaskQueryOptions) => {
-        return invokeWithError('query tasks', 'query_tasks', { options });
+        const page = await invokeWithError<TaskPage>('query tasks', 'query_tasks', { options });
+        return page.tasks;
     },
     searchAll: async (query: string) => {
         return invokeWithError('search', 'search_fts', { query });
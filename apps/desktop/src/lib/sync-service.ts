@@ -31,6 +31,7 @@ import { isTauriRuntime } from './runtime';
 import { reportError } from './report-error';
 import { logInfo, logSyncError, logWarn, sanitizeLogMessage } from './app-log';
 import { webStorage } from './storage-adapter-web';
+import { saveTauriData } from './save-data';
 
 type SyncBackend = 'off' | 'file' | 'webdav' | 'cloud';
 
@@ -79,6 +80,21 @@ const logSyncWarning = (message: string, error?: unknown) => {
     void logWarn(message, { scope: 'sync', extra });
 };
 
+/** Thrown when the remote document changed after it was read; the cycle is run again. */
+class RemoteChangedError extends Error {}
+
+const REMOTE_CHANGED_ATTEMPTS = 3;
+
+/** Converts the `{ kind, message }` errors of the backend's remote write commands. */
+const toRemoteWriteError = (error: unknown): Error => {
+    if (error && typeof error === 'object' && 'kind' in error) {
+        const { kind, message } = error as { kind: string; message?: string };
+        const text = message || 'Remote write failed';
+        return kind === 'remoteChanged' ? new RemoteChangedError(text) : new Error(text);
+    }
+    return error instanceof Error ? error : new Error(String(error));
+};
+
 const normalizePath = (input: string) => input.replace(/\\/g, '/').toLowerCase();
 
 const isSyncFilePath = (path: string) => {
@@ -401,8 +417,22 @@ async function tauriInvoke<T>(command: string, args?: Record<string, unknown>):
     return mod.invoke<T>(command as any, args as any);
 }
 
-type WebDavConfig = { url: string; username: string; password?: string; hasPassword?: boolean };
-type CloudConfig = { url: string; token: string };
+/** HTTP client settings of a remote sync backend in the desktop app; empty fields take defaults. */
+type SyncClientOptions = {
+    caCertificatePath?: string | null;
+    pinnedSha256?: string | null;
+    timeoutSeconds?: number | null;
+    connectTimeoutSeconds?: number | null;
+    maxRetries?: number | null;
+};
+type WebDavConfig = {
+    url: string;
+    username: string;
+    password?: string;
+    hasPassword?: boolean;
+    client?: SyncClientOptions;
+};
+type CloudConfig = { url: string; token: string; client?: SyncClientOptions };
 
 function normalizeSyncBackend(raw: string | null): SyncBackend {
     if (raw === 'off' || raw === 'file' || raw === 'webdav' || raw === 'cloud') return raw;
@@ -1029,6 +1059,34 @@ export class SyncService {
         }
     }
 
+    /**
+     * Saves the desktop WebDAV client's timeouts, retries and certificate trust. Throws when the
+     * CA certificate can't be read or the fingerprint is malformed.
+     */
+    static async setWebDavClientOptions(options: SyncClientOptions): Promise<void> {
+        if (!isTauriRuntime()) return;
+        try {
+            await tauriInvoke('set_webdav_client_options', { options });
+        } catch (error) {
+            reportError('Failed to set WebDAV client options', error);
+            throw error;
+        }
+    }
+
+    /**
+     * Saves the desktop self-hosted cloud client's timeouts, retries and certificate trust. Throws
+     * when the CA certificate can't be read or the fingerprint is malformed.
+     */
+    static async setCloudClientOptions(options: SyncClientOptions): Promise<void> {
+        if (!isTauriRuntime()) return;
+        try {
+            await tauriInvoke('set_cloud_client_options', { options });
+        } catch (error) {
+            reportError('Failed to set Self-Hosted client options', error);
+            throw error;
+        }
+    }
+
     static async getCloudConfig(options?: { silent?: boolean }): Promise<CloudConfig> {
         if (!isTauriRuntime()) return SyncService.getCloudConfigLocal();
         await SyncService.maybeMigrateLegacyLocalStorageToConfig();
@@ -1190,7 +1248,7 @@ export class SyncService {
         const backend = await SyncService.getSyncBackend();
         const data = await tauriInvoke<AppData>('get_data');
         const cleaned = await cleanupOrphanedAttachments(data, backend);
-        await tauriInvoke('save_data', { data: cleaned });
+        await saveTauriData(cleaned);
         await useTaskStore.getState().fetchData({ silent: true });
     }
 
@@ -1242,13 +1300,13 @@ export class SyncService {
                         preMutated = await syncCloudAttachments(localData, cloudConfig, baseUrl);
                     }
                     if (preMutated) {
-                        await tauriInvoke('save_data', { data: localData });
+                        await saveTauriData(localData);
                     }
                 } catch (error) {
                     logSyncWarning('Attachment pre-sync warning', error);
                 }
             }
-            const syncResult = await performSyncCycle({
+            const runCycle = () => performSyncCycle({
                 readLocal: async () => (
                     isTauriRuntime()
                         ? await tauriInvoke<AppData>('get_data')
@@ -1281,6 +1339,9 @@ export class SyncService {
                         }
                         const normalizedUrl = normalizeCloudUrl(cloudConfig.url);
                         syncUrl = normalizedUrl;
+                        if (isTauriRuntime()) {
+                            return await tauriInvoke<AppData | null>('cloud_get_json');
+                        }
                         const fetcher = await getTauriFetch();
                         return await cloudGetJson<AppData>(normalizedUrl, { token: cloudConfig.token, fetcher });
                     }
@@ -1291,7 +1352,7 @@ export class SyncService {
                 },
                 writeLocal: async (data) => {
                     if (isTauriRuntime()) {
-                        await tauriInvoke('save_data', { data });
+                        await saveTauriData(data);
                     } else {
                         await webStorage.saveData(data);
                     }
@@ -1300,7 +1361,11 @@ export class SyncService {
                     const sanitized = sanitizeAppDataForRemote(data);
                     if (backend === 'webdav') {
                         if (isTauriRuntime()) {
-                            await tauriInvoke('webdav_put_json', { data: sanitized });
+                            try {
+                                await tauriInvoke('webdav_put_json', { data: sanitized });
+                            } catch (error) {
+                                throw toRemoteWriteError(error);
+                            }
                             return;
                         }
                         const { url, username, password } = await SyncService.getWebDavConfig();
@@ -1310,6 +1375,14 @@ export class SyncService {
                         return;
                     }
                     if (backend === 'cloud') {
+                        if (isTauriRuntime()) {
+                            try {
+                                await tauriInvoke('cloud_put_json', { data: sanitized });
+                            } catch (error) {
+                                throw toRemoteWriteError(error);
+                            }
+                            return;
+                        }
                         const { url, token } = await SyncService.getCloudConfig();
                         const normalizedUrl = normalizeCloudUrl(url);
                         const fetcher = await getTauriFetch();
@@ -1323,6 +1396,19 @@ export class SyncService {
                     step = next;
                 },
             });
+            // Local data already holds this cycle's merge, so running again merges in whatever
+            // another device wrote in the meantime.
+            const runCycleUntilWritten = async (): ReturnType<typeof runCycle> => {
+                for (let attempt = 1; ; attempt += 1) {
+                    try {
+                        return await runCycle();
+                    } catch (error) {
+                        if (!(error instanceof RemoteChangedError) || attempt >= REMOTE_CHANGED_ATTEMPTS) throw error;
+                        logSyncWarning('Remote data changed during sync; merging again', error);
+                    }
+                }
+            };
+            const syncResult = await runCycleUntilWritten();
             const stats = syncResult.stats;
             let mergedData = syncResult.data;
             const conflictCount = (stats.tasks.conflicts || 0) + (stats.projects.conflicts || 0);
@@ -1356,14 +1442,14 @@ export class SyncService {
                         if (baseUrl) {
                             const mutated = await syncAttachments(mergedData, config, baseUrl);
                             if (mutated) {
-                                await tauriInvoke('save_data', { data: mergedData });
+                                await saveTauriData(mergedData);
                             }
                         }
                     } else if (backend === 'file') {
                         if (fileBaseDir) {
                             const mutated = await syncFileAttachments(mergedData, fileBaseDir);
                             if (mutated) {
-                                await tauriInvoke('save_data', { data: mergedData });
+                                await saveTauriData(mergedData);
                             }
                         }
                     } else if (backend === 'cloud') {
@@ -1372,7 +1458,7 @@ export class SyncService {
                         if (baseUrl) {
                             const mutated = await syncCloudAttachments(mergedData, config, baseUrl);
                             if (mutated) {
-                                await tauriInvoke('save_data', { data: mergedData });
+                                await saveTauriData(mergedData);
                             }
                         }
                     }
@@ -1386,7 +1472,7 @@ export class SyncService {
             if (isTauriRuntime() && shouldRunAttachmentCleanup(mergedData.settings.attachments?.lastCleanupAt)) {
                 step = 'attachments_cleanup';
                 mergedData = await cleanupOrphanedAttachments(mergedData, backend);
-                await tauriInvoke('save_data', { data: mergedData });
+                await saveTauriData(mergedData);
             }
 
             // 7. Refresh UI Store
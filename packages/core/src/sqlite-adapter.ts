@@ -2,6 +2,7 @@ import type { AppData, Area, Attachment, Project, Task, Section } from './types'
 import type { TaskQueryOptions, SearchResults } from './storage';
 import { SQLITE_SCHEMA } from './sqlite-schema';
 import { normalizeTaskStatus } from './task-status';
+import { UNKNOWN_AREA_TIMESTAMP } from './sync';
 import { logWarn } from './logger';
 
 export interface SqliteClient {
@@ -150,6 +151,7 @@ export class SqliteAdapter {
         await this.ensureTaskAreaColumn();
         await this.ensureTaskSectionColumn();
         await this.ensureProjectOrderColumn();
+        await this.ensureAreaLifecycleColumns();
         // FTS operations are optional - don't block startup if they fail
         try {
             await this.ensureFtsTriggers();
@@ -270,6 +272,16 @@ export class SqliteAdapter {
         );
     }
 
+    private async ensureAreaLifecycleColumns() {
+        const columns = await this.client.all<{ name?: string }>('PRAGMA table_info(areas)');
+        if (!columns.some((col) => col.name === 'isArchived')) {
+            await this.client.run('ALTER TABLE areas ADD COLUMN isArchived INTEGER');
+        }
+        if (!columns.some((col) => col.name === 'deletedAt')) {
+            await this.client.run('ALTER TABLE areas ADD COLUMN deletedAt TEXT');
+        }
+    }
+
     private async ensureFtsPopulated(forceRebuild = false) {
         try {
             const totals = await this.client.get<{
@@ -452,8 +464,10 @@ export class SqliteAdapter {
             color: row.color as string | undefined,
             icon: row.icon as string | undefined,
             order: Number(row.orderNum ?? 0),
-            createdAt: row.createdAt as string | undefined,
-            updatedAt: row.updatedAt as string | undefined,
+            isArchived: fromBool(row.isArchived),
+            createdAt: String(row.createdAt ?? row.updatedAt ?? UNKNOWN_AREA_TIMESTAMP),
+            updatedAt: String(row.updatedAt ?? row.createdAt ?? UNKNOWN_AREA_TIMESTAMP),
+            deletedAt: row.deletedAt as string | undefined,
         }));
 
         const settings = settingsRow?.data ? fromJson<AppData['settings']>(settingsRow.data, {}) : {};
@@ -788,8 +802,10 @@ export class SqliteAdapter {
                     'color',
                     'icon',
                     'orderNum',
+                    'isArchived',
                     'createdAt',
                     'updatedAt',
+                    'deletedAt',
                 ],
                 data.areas.map((area) => [
                     area.id,
@@ -797,15 +813,19 @@ export class SqliteAdapter {
                     area.color ?? null,
                     area.icon ?? null,
                     area.order,
-                    area.createdAt ?? null,
-                    area.updatedAt ?? null,
+                    toBool(area.isArchived),
+                    area.createdAt,
+                    area.updatedAt,
+                    area.deletedAt ?? null,
                 ]),
                 `name=excluded.name,
                  color=excluded.color,
                  icon=excluded.icon,
                  orderNum=excluded.orderNum,
+                 isArchived=excluded.isArchived,
                  createdAt=excluded.createdAt,
-                 updatedAt=excluded.updatedAt`,
+                 updatedAt=excluded.updatedAt,
+                 deletedAt=excluded.deletedAt`,
             );
 
             await this.client.run(
@@ -63,8 +63,10 @@ CREATE TABLE IF NOT EXISTS areas (
   color TEXT,
   icon TEXT,
   orderNum INTEGER NOT NULL,
-  createdAt TEXT,
-  updatedAt TEXT
+  isArchived INTEGER,
+  createdAt TEXT NOT NULL,
+  updatedAt TEXT NOT NULL,
+  deletedAt TEXT
 );
 
 CREATE TABLE IF NOT EXISTS sections (
@@ -1,4 +1,4 @@
-import type { AppData, Task, Project, TaskStatus } from './types';
+import type { AppData, Task, Project, Section, Area, TaskStatus } from './types';
 
 export type TaskQueryOptions = {
     status?: TaskStatus | 'all';
@@ -11,6 +11,8 @@ export type TaskQueryOptions = {
 export type SearchResults = {
     tasks: Task[];
     projects: Project[];
+    sections?: Section[];
+    areas?: Area[];
 };
 
 export interface StorageAdapter {
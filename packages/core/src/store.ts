@@ -7,6 +7,7 @@ import { StorageAdapter, TaskQueryOptions, noopStorage } from './storage';
 import { createNextRecurringTask } from './recurrence';
 import { safeParseDate } from './date';
 import { normalizeTaskForLoad } from './task-status';
+import { normalizeAreaForLoad } from './sync';
 import { rescheduleTask } from './task-utils';
 import { logError } from './logger';
 
@@ -572,7 +573,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                 });
                 allAreas = rawAreas
                     .map((area, index) => ({
-                        ...area,
+                        ...normalizeAreaForLoad(area),
                         order: Number.isFinite(area.order) ? area.order : index,
                     }))
                     .sort((a, b) => a.order - b.order);
@@ -591,6 +592,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                 const nameSet = new Set<string>();
                 let hasDuplicateNames = false;
                 for (const area of allAreas) {
+                    if (area.deletedAt) continue;
                     const normalizedName = typeof area?.name === 'string' ? area.name.trim().toLowerCase() : '';
                     if (!normalizedName) continue;
                     if (nameSet.has(normalizedName)) {
@@ -606,7 +608,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                     const uniqueAreas: Area[] = [];
                     allAreas.forEach((area) => {
                         const normalizedName = typeof area?.name === 'string' ? area.name.trim().toLowerCase() : '';
-                        if (!normalizedName) {
+                        if (!normalizedName || area.deletedAt) {
                             uniqueAreas.push(area);
                             return;
                         }
@@ -712,11 +714,12 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
             const visibleTasks = allTasks.filter(t => !t.deletedAt && t.status !== 'archived');
             const visibleProjects = allProjects.filter(p => !p.deletedAt);
             const visibleSections = allSections.filter((section) => !section.deletedAt);
+            const visibleAreas = allAreas.filter((area) => !area.deletedAt);
             set({
                 tasks: visibleTasks,
                 projects: visibleProjects,
                 sections: visibleSections,
-                areas: allAreas,
+                areas: visibleAreas,
                 settings: nextSettings,
                 _allTasks: allTasks,
                 _allProjects: allProjects,
@@ -1515,7 +1518,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
         let existingAreaId: string | null = null;
         set((state) => {
             const allAreas = state._allAreas;
-            const existing = allAreas.find((area) => area?.name?.trim().toLowerCase() === normalized);
+            const existing = allAreas.find((area) => !area.deletedAt && area?.name?.trim().toLowerCase() === normalized);
             if (existing) {
                 existingAreaId = existing.id;
                 return state;
@@ -1537,7 +1540,11 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
             const newAllAreas = [...allAreas, newArea].sort((a, b) => a.order - b.order);
             derivedCache = null;
             snapshot = buildSaveSnapshot(state, { areas: newAllAreas });
-            return { areas: newAllAreas, _allAreas: newAllAreas, lastDataChangeAt: changeAt };
+            return {
+                areas: newAllAreas.filter((area) => !area.deletedAt),
+                _allAreas: newAllAreas,
+                lastDataChangeAt: changeAt,
+            };
         });
         if (existingAreaId) {
             if (initialProps && Object.keys(initialProps).length > 0) {
@@ -1561,7 +1568,9 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                 const trimmedName = updates.name.trim();
                 if (!trimmedName) return state;
                 const normalized = trimmedName.toLowerCase();
-                const existing = allAreas.find((a) => a.id !== id && a?.name?.trim().toLowerCase() === normalized);
+                const existing = allAreas.find(
+                    (a) => a.id !== id && !a.deletedAt && a?.name?.trim().toLowerCase() === normalized
+                );
                 if (existing) {
                     const now = new Date().toISOString();
                     const mergedArea: Area = { ...existing, ...updates, name: trimmedName, updatedAt: now };
@@ -1577,7 +1586,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                     derivedCache = null;
                     snapshot = buildSaveSnapshot(state, { areas: newAllAreas, projects: newAllProjects });
                     return {
-                        areas: newAllAreas,
+                        areas: newAllAreas.filter((a) => !a.deletedAt),
                         _allAreas: newAllAreas,
                         projects: newVisibleProjects,
                         _allProjects: newAllProjects,
@@ -1594,7 +1603,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                 .sort((a, b) => a.order - b.order);
             derivedCache = null;
             snapshot = buildSaveSnapshot(state, { areas: newAllAreas });
-            return { areas: newAllAreas, _allAreas: newAllAreas, lastDataChangeAt: changeAt };
+            return { areas: newAllAreas.filter((a) => !a.deletedAt), _allAreas: newAllAreas, lastDataChangeAt: changeAt };
         });
         if (snapshot) {
             debouncedSave(snapshot, (msg) => set({ error: msg }));
@@ -1607,9 +1616,12 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
         let snapshot: AppData | null = null;
         set((state) => {
             const allAreas = state._allAreas;
-            const areaExists = allAreas.some(a => a.id === id);
+            const areaExists = allAreas.some(a => a.id === id && !a.deletedAt);
             if (!areaExists) return state;
-            const newAllAreas = allAreas.filter(a => a.id !== id).sort((a, b) => a.order - b.order);
+            // Keep a tombstone so the deletion syncs instead of the area coming back from other devices.
+            const newAllAreas = allAreas
+                .map(a => (a.id === id ? { ...a, deletedAt: now, updatedAt: now } : a))
+                .sort((a, b) => a.order - b.order);
             const newAllProjects = state._allProjects.map((project) => {
                 if (project.areaId !== id) return project;
                 return { ...project, areaId: undefined, areaTitle: undefined, updatedAt: now };
@@ -1627,7 +1639,7 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
                 areas: newAllAreas,
             });
             return {
-                areas: newAllAreas,
+                areas: newAllAreas.filter(a => !a.deletedAt),
                 _allAreas: newAllAreas,
                 projects: newVisibleProjects,
                 _allProjects: newAllProjects,
@@ -1653,24 +1665,25 @@ export const useTaskStore = create<TaskStore>((set, get) => ({
             const reordered: Area[] = [];
             orderedIds.forEach((id, index) => {
                 const area = areaById.get(id);
-                if (!area) return;
+                if (!area || area.deletedAt) return;
                 seen.add(id);
                 reordered.push({ ...area, order: index, updatedAt: now });
             });
 
             const remaining = allAreas
-                .filter(area => !seen.has(area.id))
+                .filter(area => !seen.has(area.id) && !area.deletedAt)
                 .sort((a, b) => a.order - b.order)
                 .map((area, idx) => ({
                     ...area,
                     order: reordered.length + idx,
                     updatedAt: now,
                 }));
+            const tombstones = allAreas.filter(area => area.deletedAt);
 
-            const newAllAreas = [...reordered, ...remaining];
+            const newAllAreas = [...reordered, ...remaining, ...tombstones];
             derivedCache = null;
             snapshot = buildSaveSnapshot(state, { areas: newAllAreas });
-            return { areas: newAllAreas, _allAreas: newAllAreas, lastDataChangeAt: Date.now() };
+            return { areas: [...reordered, ...remaining], _allAreas: newAllAreas, lastDataChangeAt: Date.now() };
         });
         if (snapshot) {
             debouncedSave(snapshot, (msg) => set({ error: msg }));
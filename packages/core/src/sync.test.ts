@@ -1,6 +1,6 @@
 import { describe, it, expect } from 'vitest';
 import { mergeAppData, mergeAppDataWithStats, filterDeleted } from './sync';
-import { AppData, Task, Project, Attachment, Section } from './types';
+import { AppData, Area, Task, Project, Attachment, Section } from './types';
 
 describe('Sync Logic', () => {
     const createMockTask = (id: string, updatedAt: string, deletedAt?: string): Task => ({
@@ -299,6 +299,30 @@ describe('Sync Logic', () => {
             expect(merged.tasks[0].updatedAt).toBe('2023-01-02');
         });
 
+        it('should sync area deletions and fill in missing area timestamps', () => {
+            const local: AppData = {
+                ...mockAppData(),
+                areas: [
+                    { id: 'a1', name: 'Work', order: 0, createdAt: '2023-01-01', updatedAt: '2023-01-03', deletedAt: '2023-01-03' },
+                    { id: 'a2', name: 'Home', order: 1 } as Area,
+                ],
+            };
+            const incoming: AppData = {
+                ...mockAppData(),
+                areas: [
+                    { id: 'a1', name: 'Work', order: 0, createdAt: '2023-01-01', updatedAt: '2023-01-02' },
+                    { id: 'a2', name: 'Home (renamed)', order: 1, createdAt: '2023-01-01', updatedAt: '2023-01-02' },
+                ],
+            };
+
+            const merged = mergeAppData(local, incoming);
+
+            expect(merged.areas).toHaveLength(2);
+            expect(merged.areas[0].deletedAt).toBe('2023-01-03');
+            expect(merged.areas[1].name).toBe('Home (renamed)');
+            expect(filterDeleted(merged.areas).map((area) => area.id)).toEqual(['a2']);
+        });
+
         it('should preserve local settings regardless of incoming settings', () => {
             const local: AppData = { ...mockAppData(), settings: { theme: 'dark' } };
             const incoming: AppData = { ...mockAppData(), settings: { theme: 'light' } };
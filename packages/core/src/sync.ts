@@ -212,38 +212,23 @@ function mergeEntities<T extends { id: string; updatedAt: string; deletedAt?: st
     return mergeEntitiesWithStats(local, incoming).merged;
 }
 
-function mergeAreas(local: Area[], incoming: Area[]): Area[] {
-    const localMap = new Map<string, Area>(local.map(area => [area.id, area]));
-    const incomingMap = new Map<string, Area>(incoming.map(area => [area.id, area]));
-    const allIds = new Set<string>([...localMap.keys(), ...incomingMap.keys()]);
-    const merged: Area[] = [];
+/**
+ * Fallback for areas saved before their timestamps were required.
+ * Being the epoch, it loses every Last-Write-Wins comparison.
+ */
+export const UNKNOWN_AREA_TIMESTAMP = '1970-01-01T00:00:00.000Z';
 
-    const resolveTime = (area?: Area): number => {
-        const timestamp = area?.updatedAt || area?.createdAt;
-        const parsed = timestamp ? new Date(timestamp).getTime() : 0;
-        return Number.isFinite(parsed) ? parsed : 0;
+export function normalizeAreaForLoad(area: Area): Area {
+    if (area.createdAt && area.updatedAt) return area;
+    return {
+        ...area,
+        createdAt: area.createdAt || area.updatedAt || UNKNOWN_AREA_TIMESTAMP,
+        updatedAt: area.updatedAt || area.createdAt || UNKNOWN_AREA_TIMESTAMP,
     };
+}
 
-    for (const id of allIds) {
-        const localArea = localMap.get(id);
-        const incomingArea = incomingMap.get(id);
-
-        if (localArea && !incomingArea) {
-            merged.push(localArea);
-            continue;
-        }
-        if (incomingArea && !localArea) {
-            merged.push(incomingArea);
-            continue;
-        }
-        if (!localArea || !incomingArea) continue;
-
-        const localTime = resolveTime(localArea);
-        const incomingTime = resolveTime(incomingArea);
-        const winner = incomingTime > localTime ? incomingArea : localArea;
-        merged.push(winner);
-    }
-
+function mergeAreas(local: Area[], incoming: Area[]): Area[] {
+    const merged = mergeEntities(local.map(normalizeAreaForLoad), incoming.map(normalizeAreaForLoad));
     return merged
         .map((area, index) => ({
             ...area,
@@ -262,7 +247,7 @@ export function filterDeleted<T extends { deletedAt?: string }>(items: T[]): T[]
 
 /**
  * Merge two AppData objects for synchronization.
- * Uses Last-Write-Wins for tasks and projects.
+ * Uses Last-Write-Wins for tasks, projects, sections and areas.
  * Preserves local settings (device-specific preferences).
  */
 export function mergeAppDataWithStats(local: AppData, incoming: AppData): MergeResult {
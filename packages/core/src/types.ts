@@ -62,6 +62,7 @@ export interface Project {
     createdAt: string;
     updatedAt: string;
     deletedAt?: string; // Soft-delete: if set, this item is considered deleted
+    purgedAt?: string; // Permanently removed from trash, kept for sync tombstone
 }
 
 export interface Section {
@@ -74,6 +75,7 @@ export interface Section {
     createdAt: string;
     updatedAt: string;
     deletedAt?: string; // Soft-delete: if set, this item is considered deleted
+    purgedAt?: string; // Permanently removed from trash, kept for sync tombstone
 }
 
 export interface Area {
@@ -82,8 +84,10 @@ export interface Area {
     color?: string; // Hex code
     icon?: string; // Emoji or icon name
     order: number; // For sorting in the sidebar
-    createdAt?: string;
-    updatedAt?: string;
+    isArchived?: boolean;
+    createdAt: string;
+    updatedAt: string;
+    deletedAt?: string; // Soft-delete: if set, this area is considered deleted
 }
 
 export type AttachmentKind = 'file' | 'link';